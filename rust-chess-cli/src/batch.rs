@@ -0,0 +1,259 @@
+use crate::console::result_tag;
+use crate::option_value;
+use rust_chess_core::board::PieceColor::White;
+use rust_chess_core::game::{Game, Termination};
+use rust_chess_core::r#move::Move;
+use rust_chess_core::pgn::parse_pgn;
+use std::io::Read;
+
+const USAGE: &str = "Usage: rust-chess-cli batch [--moves <file>|-] [--fen <fen>] [--json]";
+
+// exit codes
+const EXIT_OK: i32 = 0;
+// a move couldn't be parsed or isn't legal
+const EXIT_BAD_MOVE: i32 = 1;
+// wrong arguments, unreadable input, PGN syntax errors or an invalid FEN
+const EXIT_USAGE: i32 = 2;
+
+/// Where the moves stopped
+struct MoveError {
+    // 1-based ply of the move that failed
+    ply: usize,
+    token: String,
+    message: String,
+    // position before the failing move
+    fen: String,
+}
+
+/// Plays the moves read from a file or the standard input, in SAN or UCI notation, and prints the final
+/// position, the result and the legal moves, or where the moves went wrong. `args` are the arguments after "batch".
+/// The moves are read as PGN movetext, so that a PGN game can be piped in: move numbers, tags, comments, NAGs,
+/// variations and the result are skipped. Lines starting with '#' are skipped too.
+/// Returns the exit code of the program.
+pub fn run(args: &[String]) -> i32 {
+    let json = args.iter().any(|arg| arg == "--json");
+    match play(args) {
+        Ok(Ok(game)) => {
+            print_game(&game, json);
+            EXIT_OK
+        }
+        Ok(Err(err)) => {
+            print_move_error(&err, json);
+            EXIT_BAD_MOVE
+        }
+        Err(err) => {
+            match json {
+                true => println!("{{\"ok\":false,\"error\":{}}}", json_string(&err)),
+                false => eprintln!("{}", err),
+            }
+            EXIT_USAGE
+        }
+    }
+}
+
+/// The game after the moves, an error about the moves inside, or an error about the arguments or the input outside
+fn play(args: &[String]) -> Result<Result<Game, MoveError>, String> {
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--moves" | "--fen" => {
+                args_iter.next();
+            }
+            "--json" => {}
+            _ => return Err(format!("Unknown argument {}\n{}", arg, USAGE)),
+        }
+    }
+    let mut game = match option_value(args, "--fen") {
+        Some(fen) => Game::from_fen(fen).map_err(|err| format!("Invalid FEN: {}", err))?,
+        None => Game::new(),
+    };
+    let text = match option_value(args, "--moves") {
+        None | Some("-") => {
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|err| format!("Error reading the standard input: {}", err))?;
+            text
+        }
+        Some(path) => std::fs::read_to_string(path).map_err(|err| format!("Can't read {}: {}", path, err))?,
+    };
+
+    let text: String = text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .map(|line| line.to_string() + "\n")
+        .collect();
+    let mut games = parse_pgn(&text);
+    if games.len() > 1 {
+        return Err(format!("Expected the moves of one game, found {} games", games.len()));
+    }
+    let Some(pgn) = games.pop() else {
+        return Ok(Ok(game));
+    };
+    if let Some(err) = pgn.syntax_errors.first() {
+        return Err(format!("Error at line {}, column {}: {}", err.line, err.column, err.message));
+    }
+    for (i, mv) in pgn.moves.iter().enumerate() {
+        if let Err(message) = play_move(&mut game, &mv.san) {
+            return Ok(Err(MoveError {
+                ply: i + 1,
+                token: mv.san.clone(),
+                message,
+                fen: game.to_fen(),
+            }));
+        }
+    }
+    Ok(Ok(game))
+}
+
+/// Plays a move in UCI notation like "e2e4", or else in SAN like "Nf3"
+fn play_move(game: &mut Game, token: &str) -> Result<(), String> {
+    let mv = match Move::parse_long_notation(token) {
+        Ok(mv) => mv,
+        Err(_) => game.parse_short_notation(token)?,
+    };
+    Ok(game.make_move(&mv)?)
+}
+
+fn print_game(game: &Game, json: bool) {
+    let plies = game.history().moves().len();
+    let legal_moves: Vec<String> = game
+        .possible_moves()
+        .iter()
+        .map(|mv| game.to_short_notation(mv).unwrap_or_else(|_| mv.to_string()))
+        .collect();
+    let termination = game.result().as_ref().map(|result| match result.termination {
+        Termination::Checkmate => "checkmate",
+        Termination::Stalemate => "stalemate",
+        Termination::Timeout => "timeout",
+    });
+    if !json {
+        println!("fen: {}", game.to_fen());
+        println!("plies: {}", plies);
+        println!("result: {}{}", result_tag(game), termination.map(|t| format!(" ({})", t)).unwrap_or_default());
+        println!("legal moves: {}", legal_moves.join(" "));
+        return;
+    }
+    let uci_moves: Vec<String> = game.possible_moves().iter().map(|mv| mv.to_string()).collect();
+    println!(
+        "{{\"ok\":true,\"plies\":{},\"fen\":{},\"turn\":\"{}\",\"check\":{},\"result\":\"{}\",\"termination\":{},\
+         \"legal_moves\":{},\"legal_moves_uci\":{}}}",
+        plies,
+        json_string(&game.to_fen()),
+        if game.turn() == White { "white" } else { "black" },
+        game.is_check(),
+        result_tag(game),
+        termination.map_or("null".to_string(), json_string),
+        json_array(&legal_moves),
+        json_array(&uci_moves)
+    );
+}
+
+fn print_move_error(err: &MoveError, json: bool) {
+    if !json {
+        eprintln!("Error at ply {} ({}): {}", err.ply, err.token, err.message);
+        eprintln!("fen: {}", err.fen);
+        return;
+    }
+    println!(
+        "{{\"ok\":false,\"error\":{},\"ply\":{},\"move\":{},\"fen\":{}}}",
+        json_string(&err.message),
+        err.ply,
+        json_string(&err.token),
+        json_string(&err.fen)
+    );
+}
+
+fn json_string(text: &str) -> String {
+    let mut result = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn json_array(items: &[String]) -> String {
+    let items: Vec<String> = items.iter().map(|item| json_string(item)).collect();
+    format!("[{}]", items.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static FILES: AtomicUsize = AtomicUsize::new(0);
+
+    // runs `f` with the arguments, "{moves}" replaced by a file holding the moves
+    fn with_moves<T>(moves: &str, args: &[&str], f: impl FnOnce(&[String]) -> T) -> T {
+        let number = FILES.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("rust-chess-batch-{}-{}.txt", std::process::id(), number));
+        std::fs::write(&path, moves).unwrap();
+        let args: Vec<String> =
+            args.iter().map(|arg| arg.replace("{moves}", &path.display().to_string())).collect();
+        let result = f(&args);
+        std::fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn escapes_json_strings() {
+        assert_eq!(json_string("e4"), "\"e4\"");
+        assert_eq!(json_string("a \"b\" \\ c\nd\te"), "\"a \\\"b\\\" \\\\ c\\nd\\u0009e\"");
+        assert_eq!(json_array(&["a".to_string(), "b".to_string()]), "[\"a\",\"b\"]");
+        assert_eq!(json_array(&[]), "[]");
+    }
+
+    #[test]
+    fn plays_pgn_movetext() {
+        let moves = "# Scholar's mate\n1. e4 e5 2.Bc4 Nc6\n3. d1h5 Nf6 4. Qxf7# 1-0\n";
+        let game = match with_moves(moves, &["--moves", "{moves}"], play) {
+            Ok(Ok(game)) => game,
+            _ => panic!("the moves weren't played"),
+        };
+        assert_eq!(game.history().moves().len(), 7);
+        assert_eq!(game.result().map(|result| result.termination), Some(Termination::Checkmate));
+
+        // tags, comments, NAGs, variations and glued move numbers are skipped
+        let moves = "[Event \"x\"]\n\n1. e4 {best} e5 $1 (1... c5 2. Nf3) 2.Nf3 ; the main line\n2... Nc6 *\n";
+        let game = match with_moves(moves, &["--moves", "{moves}"], play) {
+            Ok(Ok(game)) => game,
+            _ => panic!("the moves weren't played"),
+        };
+        assert_eq!(game.history().moves().len(), 4);
+        assert_eq!(with_moves(moves, &["--moves", "{moves}"], run), EXIT_OK);
+    }
+
+    #[test]
+    fn rejects_malformed_movetext() {
+        assert!(with_moves("e4 e5 )", &["--moves", "{moves}"], play).is_err());
+        assert!(with_moves("e4 e5 1-0\n\n[Event \"y\"]\n d4 *", &["--moves", "{moves}"], play).is_err());
+    }
+
+    #[test]
+    fn reports_the_failing_move() {
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1";
+        let err = match with_moves("e4 Ke7 e5 Ke5", &["--fen", fen, "--moves", "{moves}"], play) {
+            Ok(Err(err)) => err,
+            _ => panic!("the moves were played"),
+        };
+        assert_eq!((err.ply, err.token.as_str()), (4, "Ke5"));
+        assert_eq!(err.fen, "8/4k3/8/4P3/8/8/8/4K3 b - - 0 2");
+    }
+
+    #[test]
+    fn returns_exit_codes() {
+        assert_eq!(with_moves("e4 e5", &["--moves", "{moves}"], run), EXIT_OK);
+        assert_eq!(with_moves("e4 e4", &["--moves", "{moves}", "--json"], run), EXIT_BAD_MOVE);
+        assert_eq!(with_moves("e4", &["--moves", "{moves}", "--fen", "8/8/8/8/8/8/8/8 w - - 0 1"], run), EXIT_USAGE);
+        assert_eq!(run(&["--depth".to_string()]), EXIT_USAGE);
+        assert_eq!(run(&["--moves".to_string(), "/nonexistent/moves.txt".to_string()]), EXIT_USAGE);
+    }
+}
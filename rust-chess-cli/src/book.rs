@@ -0,0 +1,30 @@
+use rust_chess_core::game::Game;
+use rust_chess_core::polyglot::{BookSelection, PolyglotBook};
+use rust_chess_core::random::Random;
+use rust_chess_core::r#move::Move;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+
+/// Polyglot book the engine plays from while the position is in it
+pub struct OpeningBook {
+    book: PolyglotBook<BufReader<File>>,
+    random: Random,
+}
+
+impl OpeningBook {
+    pub fn open(path: &str) -> io::Result<OpeningBook> {
+        Ok(OpeningBook {
+            book: PolyglotBook::open(path)?,
+            random: Random::from_time(),
+        })
+    }
+
+    /// Picks a random book move weighted by how good the book considers it, None if the position is not in the book
+    pub fn choose_move(&mut self, game: &Game) -> Option<Move> {
+        // a broken book shouldn't stop the engine from playing, it just thinks on its own then
+        self.book
+            .choose_move(game, BookSelection::WeightedRandom, &mut self.random)
+            .unwrap_or(None)
+    }
+}
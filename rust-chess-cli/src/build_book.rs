@@ -0,0 +1,152 @@
+use crate::option_value;
+use rust_chess_core::book_builder::{BookBuilder, BookBuilderOptions};
+use rust_chess_core::pgn::parse_pgn;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "Usage: rust-chess-cli build-book <pgn files or directories...> --output <book.bin> \
+                     [--tree <tree.txt>] [--max-ply N] [--player NAME] [--min-games N]";
+
+// options that take a value, everything else after the subcommand is an input path
+const OPTIONS: [&str; 5] = ["--output", "--tree", "--max-ply", "--player", "--min-games"];
+
+// what the arguments of "build-book" ask for
+struct BuildBookOptions<'a> {
+    // PGN files or directories, as given
+    paths: Vec<&'a str>,
+    output: &'a str,
+    tree: Option<&'a str>,
+    book: BookBuilderOptions,
+}
+
+/// Builds a Polyglot book from PGN files. `args` are the arguments after "build-book".
+pub fn run(args: &[String]) -> Result<(), String> {
+    let BuildBookOptions { paths, output, tree, book } = parse_args(args)?;
+    let mut inputs = Vec::new();
+    for path in paths {
+        collect_pgn_files(Path::new(path), &mut inputs)?;
+    }
+
+    let mut builder = BookBuilder::new(book);
+    let mut skipped = 0;
+    for input in &inputs {
+        let content = std::fs::read_to_string(input).map_err(|err| format!("Can't read {}: {}", input.display(), err))?;
+        for game in parse_pgn(&content) {
+            if let Err(err) = builder.add_game(&game) {
+                eprintln!("Skipping game: {}:{}", input.display(), err);
+                skipped += 1;
+            }
+        }
+    }
+
+    let file = File::create(output).map_err(|err| format!("Can't create {}: {}", output, err))?;
+    builder
+        .write_polyglot(&mut BufWriter::new(file))
+        .map_err(|err| format!("Can't write {}: {}", output, err))?;
+    if let Some(tree) = tree {
+        let file = File::create(tree).map_err(|err| format!("Can't create {}: {}", tree, err))?;
+        builder
+            .write_tree(&mut BufWriter::new(file))
+            .map_err(|err| format!("Can't write {}: {}", tree, err))?;
+    }
+
+    println!(
+        "Added {} games ({} skipped), {} positions, {} book entries written to {}",
+        builder.games_added(),
+        skipped,
+        builder.position_count(),
+        builder.entries().len(),
+        output
+    );
+    Ok(())
+}
+
+fn parse_args(args: &[String]) -> Result<BuildBookOptions<'_>, String> {
+    let output = option_value(args, "--output").ok_or(USAGE)?;
+    let mut book = BookBuilderOptions {
+        player: option_value(args, "--player").map(|player| player.to_string()),
+        ..Default::default()
+    };
+    if let Some(max_ply) = option_value(args, "--max-ply") {
+        book.max_ply = max_ply.parse().map_err(|_| format!("Invalid --max-ply {}", max_ply))?;
+    }
+    if let Some(min_games) = option_value(args, "--min-games") {
+        book.min_games = min_games.parse().map_err(|_| format!("Invalid --min-games {}", min_games))?;
+    }
+
+    let mut paths = Vec::new();
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        if OPTIONS.contains(&arg.as_str()) {
+            args_iter.next();
+        } else {
+            paths.push(arg.as_str());
+        }
+    }
+    if paths.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    Ok(BuildBookOptions { paths, output, tree: option_value(args, "--tree"), book })
+}
+
+/// Adds the path if it is a file, or all .pgn files in it if it is a directory
+fn collect_pgn_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let entries = std::fs::read_dir(path).map_err(|err| format!("Can't read {}: {}", path.display(), err))?;
+    let mut pgn_files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "pgn"))
+        .collect();
+    pgn_files.sort();
+    files.extend(pgn_files);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parses_args() {
+        let args = args("a.pgn --output book.bin games --max-ply 12 --player Anna --min-games 3 --tree tree.txt");
+        let options = parse_args(&args).unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(options.paths, ["a.pgn", "games"]);
+        assert_eq!((options.output, options.tree), ("book.bin", Some("tree.txt")));
+        assert_eq!((options.book.max_ply, options.book.min_games), (12, 3));
+        assert_eq!(options.book.player.as_deref(), Some("Anna"));
+
+        let args = self::args("a.pgn --output book.bin");
+        let options = parse_args(&args).unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(options.tree, None);
+        assert_eq!(options.book.max_ply, BookBuilderOptions::default().max_ply);
+
+        let error = |line| parse_args(&self::args(line)).err().unwrap_or_default();
+        assert_eq!(error("a.pgn"), USAGE);
+        assert_eq!(error("--output book.bin"), USAGE);
+        assert_eq!(error("a.pgn --output book.bin --max-ply x"), "Invalid --max-ply x");
+        assert_eq!(error("a.pgn --output book.bin --min-games -1"), "Invalid --min-games -1");
+    }
+
+    #[test]
+    fn collects_pgn_files_from_directories() {
+        let dir = std::env::temp_dir().join(format!("rust-chess-build-book-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["b.pgn", "a.pgn", "notes.txt"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let mut files = Vec::new();
+        let result =
+            collect_pgn_files(&dir, &mut files).and_then(|_| collect_pgn_files(Path::new("c.pgn"), &mut files));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(result, Ok(()));
+        assert_eq!(files, [dir.join("a.pgn"), dir.join("b.pgn"), PathBuf::from("c.pgn")]);
+    }
+}
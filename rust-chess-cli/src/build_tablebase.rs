@@ -0,0 +1,42 @@
+use crate::option_value;
+use rust_chess_core::tablebase::Tablebases;
+use std::path::Path;
+use std::time::Instant;
+
+const USAGE: &str = "Usage: rust-chess-cli build-tablebase <signatures like KQvK KRvK KPvK...> --output <directory>";
+
+/// Generates endgame tablebases and writes them to a directory. `args` are the arguments after "build-tablebase".
+pub fn run(args: &[String]) -> Result<(), String> {
+    let Some(output) = option_value(args, "--output") else {
+        return Err(USAGE.to_string());
+    };
+    let mut signatures = Vec::new();
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        if arg == "--output" {
+            args_iter.next();
+        } else {
+            signatures.push(arg);
+        }
+    }
+    if signatures.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    let start = Instant::now();
+    let mut tablebases = Tablebases::new();
+    for signature in signatures {
+        tablebases.generate(signature)?;
+    }
+    let dir = Path::new(output);
+    std::fs::create_dir_all(dir).map_err(|err| format!("Can't create {}: {}", output, err))?;
+    tablebases.save_dir(dir)?;
+
+    println!(
+        "Generated {} written to {} in {} ms",
+        tablebases.signatures().join(", "),
+        output,
+        start.elapsed().as_millis()
+    );
+    Ok(())
+}
@@ -0,0 +1,633 @@
+use rust_chess_core::board::PieceColor::{Black, White};
+use rust_chess_core::board::PieceColor;
+use rust_chess_core::clock::{Bonus, Clock, TimeControl};
+use rust_chess_core::display::{DisplayOptions, render};
+use rust_chess_core::game::{Game, Termination};
+use rust_chess_core::mcts::{MctsLimits, MctsPlayer};
+use rust_chess_core::pgn::{format_moves, parse_pgn, write_pgn};
+use rust_chess_core::pos::Pos;
+use rust_chess_core::r#move::Move;
+use rust_chess_core::random::Random;
+use rust_chess_core::search::{Score, Search, SearchLimits};
+use rust_chess_core::skill::SkillLevel;
+use rust_chess_core::time_manager::TimeManager;
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+// thinking time of the engine in the interactive mode
+const ENGINE_MOVE_TIME: Duration = Duration::from_secs(2);
+// depth of the search that decides whether the engine accepts a draw offer
+const DRAW_OFFER_DEPTH: u32 = 4;
+// thinking time for a hint, which is always searched at full strength
+const HINT_TIME: Duration = Duration::from_secs(1);
+// time the engine keeps on its clock for printing its move, in a timed game
+const ENGINE_MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+// below this the clocks show tenths of seconds
+const SHOW_TENTHS_BELOW: Duration = Duration::from_secs(20);
+
+const HELP: &str = "\
+Enter moves in short algebraic notation, e.g. e4, Nf3, exd5, O-O or e8=Q, or one of the commands:
+  undo              take back the last move (and the engine's reply), not in a timed game
+  new               start a new game
+  fen               print the position as FEN
+  load <fen|file>   continue from a FEN position or from the end of the first game of a PGN file
+  save <file>       save the game as PGN
+  moves [square]    list the legal moves, or show the moves of the piece on the square on the board
+  flip [auto]       turn the board around, or keep the side to move at the bottom
+  resign            resign the game
+  draw              offer a draw, the engine accepts it unless it thinks it is better
+  hint              show the engine's suggestion for the side to move
+  history           list the moves of the game
+  help              show this help
+  quit              leave the program";
+
+/// Who answers the moves entered in the console
+pub enum Opponent {
+    // both sides play at the keyboard
+    Human,
+    Engine(SkillLevel),
+    Mcts(Box<MctsPlayer>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Move(String),
+    Undo,
+    New,
+    Fen,
+    Load(String),
+    Save(String),
+    Moves(Option<String>),
+    // true to follow the side to move
+    Flip(bool),
+    Resign,
+    Draw,
+    Hint,
+    History,
+    Help,
+    Quit,
+}
+
+// how a game ended that `Game::result` doesn't know about
+struct Outcome {
+    result: &'static str,
+    message: String,
+}
+
+struct Console {
+    game: Game,
+    opponent: Opponent,
+    // the side the engine plays, unless both sides are human
+    engine_color: PieceColor,
+    random: Random,
+    // piece style and colours of the board, the rest of the options is set when drawing it
+    display: DisplayOptions,
+    // the side at the bottom of the board, unless `auto_flip` shows the side to move there
+    perspective: PieceColor,
+    auto_flip: bool,
+    outcome: Option<Outcome>,
+    // set when the board has to be printed again
+    redraw: bool,
+    // None for an untimed game
+    clock: Option<Clock>,
+}
+
+/// Plays a game in the terminal, reading moves and commands line by line until "quit" or the end of the input.
+/// The engine, if any, plays `engine_color`, and the board is shown from the player's side, or with `auto_flip`
+/// from the side to move. With a time control, both clocks are shown above the board and a player whose time
+/// runs out loses.
+pub fn run(
+    opponent: Opponent,
+    engine_color: PieceColor,
+    time_control: Option<TimeControl>,
+    display: DisplayOptions,
+    auto_flip: bool,
+) {
+    let mut console = Console {
+        game: Game::new(),
+        opponent,
+        engine_color,
+        random: Random::from_time(),
+        display,
+        perspective: engine_color.opposite(),
+        auto_flip,
+        outcome: None,
+        redraw: true,
+        clock: time_control.map(Clock::new),
+    };
+    println!("Type 'help' for the list of commands");
+    console.start_clock();
+    console.play_engine_move();
+
+    let input = read_lines();
+    loop {
+        if console.redraw {
+            if console.is_over() {
+                console.stop_clock();
+            }
+            console.print_clocks();
+            print!("{}", console.board_display(None));
+            console.print_game_over();
+            console.redraw = false;
+        }
+        print!("Your move: ");
+        io::stdout().flush().unwrap();
+
+        let Some(input) = console.wait_for_input(&input) else {
+            return;
+        };
+        let line = input.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let result = match parse_command(line) {
+            Ok(Command::Quit) => return,
+            Ok(command) => console.execute(command),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            println!("Error: {}", err);
+        }
+    }
+}
+
+/// Reads the lines of the standard input on another thread, so that the clock can flag a player
+/// while waiting for a move. The channel closes at the end of the input.
+fn read_lines() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => {
+                    println!("Error reading input: {}", err);
+                    return;
+                }
+            };
+            // the console has quit
+            if sender.send(line).is_err() {
+                return;
+            }
+        }
+    });
+    receiver
+}
+
+fn parse_command(line: &str) -> Result<Command, String> {
+    let (word, rest) = match line.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (line, ""),
+    };
+    let no_arguments = |command: Command| {
+        if rest.is_empty() {
+            Ok(command)
+        } else {
+            Err(format!("'{}' takes no arguments", word))
+        }
+    };
+    match word.to_lowercase().as_str() {
+        "undo" => no_arguments(Command::Undo),
+        "new" => no_arguments(Command::New),
+        "fen" => no_arguments(Command::Fen),
+        "load" if rest.is_empty() => Err("Usage: load <fen|file.pgn>".to_string()),
+        "load" => Ok(Command::Load(rest.to_string())),
+        "save" if rest.is_empty() => Err("Usage: save <file.pgn>".to_string()),
+        "save" => Ok(Command::Save(rest.to_string())),
+        "moves" if rest.contains(char::is_whitespace) => Err("Usage: moves [square]".to_string()),
+        "moves" => Ok(Command::Moves((!rest.is_empty()).then(|| rest.to_string()))),
+        "flip" if rest.eq_ignore_ascii_case("auto") => Ok(Command::Flip(true)),
+        "flip" if !rest.is_empty() => Err("Usage: flip [auto]".to_string()),
+        "flip" => Ok(Command::Flip(false)),
+        "resign" => no_arguments(Command::Resign),
+        "draw" => no_arguments(Command::Draw),
+        "hint" => no_arguments(Command::Hint),
+        "history" => no_arguments(Command::History),
+        "help" | "?" => no_arguments(Command::Help),
+        "quit" | "exit" => no_arguments(Command::Quit),
+        // every move has a digit in it or is a castling
+        _ if word.chars().all(|c| c.is_ascii_alphabetic()) => {
+            Err(format!("Unknown command '{}', type 'help' for the list of commands", word))
+        }
+        _ if !rest.is_empty() => Err("Enter one move at a time".to_string()),
+        _ => Ok(Command::Move(word.to_string())),
+    }
+}
+
+impl Console {
+    fn execute(&mut self, command: Command) -> Result<(), String> {
+        match command {
+            Command::Move(san) => {
+                self.check_not_over()?;
+                let mv = self.game.parse_short_notation(&san)?;
+                self.make_timed_move(&mv)?;
+                self.redraw = true;
+                self.play_engine_move();
+            }
+            Command::Undo => {
+                if self.clock.is_some() {
+                    return Err("Moves can't be taken back in a timed game".to_string());
+                }
+                self.game.undo_move().ok_or("Nothing to undo")?;
+                // take back the engine's reply too, so that it's the player's turn again
+                if !matches!(self.opponent, Opponent::Human)
+                    && self.game.turn() == self.engine_color
+                    && self.game.undo_move().is_none()
+                {
+                    self.play_engine_move();
+                }
+                self.outcome = None;
+                self.redraw = true;
+            }
+            Command::New => self.start(Game::new()),
+            Command::Fen => println!("{}", self.game.to_fen()),
+            Command::Load(source) => {
+                self.start(load_game(&source)?);
+            }
+            Command::Save(path) => {
+                let (white, black) = match (&self.opponent, self.engine_color) {
+                    (Opponent::Human, _) => ("Player", "Player"),
+                    (_, White) => ("rust-chess", "Player"),
+                    (_, Black) => ("Player", "rust-chess"),
+                };
+                let tags = [("Event", "Casual game"), ("Site", "rust-chess-cli"), ("White", white), ("Black", black)];
+                let pgn = write_pgn(&self.game, &tags, self.result());
+                std::fs::write(&path, pgn).map_err(|err| format!("Can't write {}: {}", path, err))?;
+                println!("Saved to {}", path);
+            }
+            Command::Moves(square) => {
+                let moves: Vec<Move> = match &square {
+                    Some(square) => {
+                        let pos = Pos::from_notation(&square.to_lowercase())?;
+                        print!("{}", self.board_display(Some(pos)));
+                        self.game.get_moves_from_pos(pos).to_vec()
+                    }
+                    None => self.game.possible_moves().to_vec(),
+                };
+                let mut moves = moves
+                    .iter()
+                    .map(|mv| self.game.to_short_notation(mv))
+                    .collect::<Result<Vec<_>, _>>()?;
+                moves.sort();
+                match (moves.is_empty(), square) {
+                    (true, Some(square)) => println!("No legal moves from {}", square),
+                    (true, None) => println!("No legal moves"),
+                    (false, _) => println!("{}", moves.join(" ")),
+                }
+            }
+            Command::Flip(auto) => {
+                // a plain flip turns around the board as shown and stops following the side to move
+                self.perspective = match auto {
+                    true => self.perspective,
+                    false => self.shown_perspective().opposite(),
+                };
+                self.auto_flip = auto;
+                self.redraw = true;
+            }
+            Command::Resign => {
+                self.check_not_over()?;
+                let loser = self.game.turn();
+                self.outcome = Some(Outcome {
+                    result: if loser == White { "0-1" } else { "1-0" },
+                    message: format!("{} resigns, {} wins", color_name(loser), color_name(loser.opposite())),
+                });
+                self.stop_clock();
+                self.print_game_over();
+            }
+            Command::Draw => {
+                self.check_not_over()?;
+                if !matches!(self.opponent, Opponent::Human) && !self.engine_accepts_draw() {
+                    println!("The engine declines the draw");
+                    return Ok(());
+                }
+                self.outcome = Some(Outcome {
+                    result: "1/2-1/2",
+                    message: "Draw agreed".to_string(),
+                });
+                self.stop_clock();
+                self.print_game_over();
+            }
+            Command::Hint => {
+                self.check_not_over()?;
+                let result = Search::with_limits(SearchLimits::move_time(HINT_TIME)).run(&self.game, |_| {});
+                let hint = result.best_move.ok_or("No legal moves")?;
+                println!("Hint: {} ({})", self.game.to_short_notation(&hint)?, result.score);
+            }
+            Command::History => match self.game.history().moves().is_empty() {
+                true => println!("No moves yet"),
+                false => println!("{}", format_moves(&self.game)),
+            },
+            Command::Help => println!("{}", HELP),
+            Command::Quit => {}
+        }
+        Ok(())
+    }
+
+    fn start(&mut self, game: Game) {
+        self.game = game;
+        self.outcome = None;
+        if let Opponent::Mcts(player) = &mut self.opponent {
+            player.clear();
+        }
+        self.start_clock();
+        self.redraw = true;
+        self.play_engine_move();
+    }
+
+    fn is_over(&self) -> bool {
+        self.game.result().is_some() || self.outcome.is_some()
+    }
+
+    fn check_not_over(&self) -> Result<(), String> {
+        match (self.is_over(), &self.clock) {
+            (false, _) => Ok(()),
+            (true, Some(_)) => Err("The game is over, type 'new' to start a new game".to_string()),
+            (true, None) => {
+                Err("The game is over, type 'new' to start a new game or 'undo' to take back a move".to_string())
+            }
+        }
+    }
+
+    /// Result for the PGN Result tag
+    fn result(&self) -> &'static str {
+        match &self.outcome {
+            Some(outcome) => outcome.result,
+            None => result_tag(&self.game),
+        }
+    }
+
+    /// Prints the result if the game is over, returns true then
+    fn print_game_over(&self) -> bool {
+        let message = match &self.outcome {
+            Some(outcome) => outcome.message.clone(),
+            None => match game_over_message(&self.game) {
+                Some(message) => message,
+                None => return false,
+            },
+        };
+        println!("Game over! {} ({})", message, self.result());
+        true
+    }
+
+    /// Lets the engine move if it's its turn
+    fn play_engine_move(&mut self) {
+        if self.game.turn() != self.engine_color || self.is_over() {
+            return;
+        }
+        let time_manager = engine_time_manager(self.clock.as_ref(), self.engine_color);
+        let reply = match &mut self.opponent {
+            Opponent::Human => return,
+            Opponent::Engine(skill) => engine_move(&self.game, *skill, time_manager, &mut self.random),
+            Opponent::Mcts(player) => {
+                let result = player.search(&self.game, &MctsLimits::move_time(time_manager.soft_limit()));
+                print!("{}", result.format_report(&self.game));
+                result.best_move
+            }
+        };
+        let Some(reply) = reply else {
+            return;
+        };
+        let notation = self.game.to_short_notation(&reply).unwrap_or_else(|_| reply.to_string());
+        self.redraw = true;
+        match self.make_timed_move(&reply) {
+            Ok(()) => println!("Engine plays: {}", notation),
+            Err(err) => println!("{}", err),
+        }
+    }
+
+    /// Makes a legal move and presses the clock. A move made after the time ran out loses on time instead.
+    fn make_timed_move(&mut self, mv: &Move) -> Result<(), String> {
+        if self.check_flag() {
+            self.redraw = true;
+            return Err("Time is up".to_string());
+        }
+        let mover = self.game.turn();
+        self.game.make_move(mv)?;
+        if let Some(clock) = &mut self.clock
+            && clock.press().is_err()
+        {
+            // the time ran out between the check and the press, the move doesn't count
+            self.game.undo_move();
+            self.game.time_out(mover);
+            self.redraw = true;
+            return Err("Time is up".to_string());
+        }
+        Ok(())
+    }
+
+    /// A fresh clock for the current game, running for the side to move
+    fn start_clock(&mut self) {
+        if let Some(clock) = &mut self.clock {
+            *clock = Clock::new(clock.time_control().clone());
+            clock.set_turn(self.game.turn());
+            clock.start();
+        }
+    }
+
+    fn stop_clock(&mut self) {
+        if let Some(clock) = &mut self.clock {
+            clock.stop();
+        }
+    }
+
+    /// Ends the game if the side to move ran out of time, returns true then
+    fn check_flag(&mut self) -> bool {
+        let Some(color) = self.clock.as_ref().and_then(|clock| clock.flagged()) else {
+            return false;
+        };
+        self.stop_clock();
+        self.game.time_out(color);
+        true
+    }
+
+    /// Waits for the next line of input. While a clock runs, the game ends as soon as the time is up.
+    /// None at the end of the input.
+    fn wait_for_input(&mut self, input: &Receiver<String>) -> Option<String> {
+        loop {
+            let until_flag = match &self.clock {
+                Some(clock) if !self.is_over() => clock.time_until_flag(),
+                _ => None,
+            };
+            let Some(until_flag) = until_flag else {
+                return input.recv().ok();
+            };
+            match input.recv_timeout(until_flag) {
+                Ok(line) => return Some(line),
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.check_flag() {
+                        println!();
+                        self.print_clocks();
+                        self.print_game_over();
+                        print!("Your move: ");
+                        io::stdout().flush().unwrap();
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
+
+    /// The board from the side of the player, with the last move and a check highlighted, and the
+    /// destinations of the piece on `selected` marked
+    fn board_display(&self, selected: Option<Pos>) -> String {
+        let mut options = DisplayOptions {
+            perspective: self.shown_perspective(),
+            ..self.display.clone()
+        }
+        .highlight_game(&self.game);
+        if let Some(selected) = selected {
+            options = options.mark_destinations(&self.game, selected);
+        }
+        format!("{}\n", render(self.game.board(), &options))
+    }
+
+    fn shown_perspective(&self) -> PieceColor {
+        if self.auto_flip { self.game.turn() } else { self.perspective }
+    }
+
+    /// Shows both clocks, with a star next to the one running
+    fn print_clocks(&self) {
+        let Some(clock) = &self.clock else {
+            return;
+        };
+        let side = |color: PieceColor| {
+            let running = if clock.is_running() && clock.turn() == color { " *" } else { "" };
+            format!("{} {}{}", color_name(color), format_clock(clock.remaining(color)), running)
+        };
+        println!("{}   {}", side(White), side(Black));
+    }
+
+    /// The engine accepts a draw unless a short search thinks it is better
+    fn engine_accepts_draw(&self) -> bool {
+        let result = Search::with_limits(SearchLimits::depth(DRAW_OFFER_DEPTH)).run(&self.game, |_| {});
+        let sign = match result.score {
+            Score::Centipawns(score) => score.signum(),
+            Score::Mate(moves) => moves.signum(),
+        };
+        let engine_sign = if self.game.turn() == self.engine_color { sign } else { -sign };
+        engine_sign <= 0
+    }
+}
+
+/// PGN result of the game, "*" while it goes on
+pub(crate) fn result_tag(game: &Game) -> &'static str {
+    match game.result() {
+        Some(result) => match result.winner {
+            Some(White) => "1-0",
+            Some(Black) => "0-1",
+            None => "1/2-1/2",
+        },
+        None => "*",
+    }
+}
+
+/// How the game ended, None while it goes on
+pub(crate) fn game_over_message(game: &Game) -> Option<String> {
+    let result = game.result().as_ref()?;
+    let message = match (result.termination, result.winner) {
+        // only the clock of the side to move runs, so it is the one that ran out of time
+        (Termination::Timeout, Some(winner)) => {
+            format!("{} ran out of time, {} wins", color_name(winner.opposite()), color_name(winner))
+        }
+        (Termination::Timeout, None) => format!(
+            "{} ran out of time, but {} can't checkmate, it's a draw",
+            color_name(game.turn()),
+            color_name(game.turn().opposite())
+        ),
+        (_, Some(winner)) => format!("Checkmate, {} wins", color_name(winner)),
+        (_, None) => "Stalemate, it's a draw".to_string(),
+    };
+    Some(message)
+}
+
+/// Thinking time of the engine playing `color`, shared out over the time left on its clock in a timed game
+pub(crate) fn engine_time_manager(clock: Option<&Clock>, color: PieceColor) -> TimeManager {
+    let Some(clock) = clock else {
+        return TimeManager::fixed(ENGINE_MOVE_TIME);
+    };
+    let increment = match clock.bonus(color) {
+        Bonus::None => Duration::ZERO,
+        Bonus::Increment(time) | Bonus::Bronstein(time) | Bonus::SimpleDelay(time) => time,
+    };
+    TimeManager::new(clock.remaining(color), increment, clock.moves_to_go(color), ENGINE_MOVE_OVERHEAD)
+}
+
+/// Searches for the engine's move, weakened according to the skill level
+pub(crate) fn engine_move(game: &Game, skill: SkillLevel, time_manager: TimeManager, random: &mut Random) -> Option<Move> {
+    let limits = skill.limit_search(SearchLimits {
+        time_manager: Some(time_manager),
+        ..Default::default()
+    });
+    let mut search = Search::with_limits(limits);
+    search.set_multi_pv(skill.multi_pv(1));
+    let result = search.run(game, |_| {});
+    skill.choose_move(&result.lines, random).or(result.best_move)
+}
+
+/// A position from a FEN, or the final position of the first game of a PGN file
+pub(crate) fn load_game(source: &str) -> Result<Game, String> {
+    if !source.to_lowercase().ends_with(".pgn") && !Path::new(source).is_file() {
+        return Game::from_fen(source);
+    }
+    let text = std::fs::read_to_string(source).map_err(|err| format!("Can't read {}: {}", source, err))?;
+    let games = parse_pgn(&text);
+    let game = games.first().ok_or_else(|| format!("No games in {}", source))?;
+    game.replay().map_err(|err| format!("{}:{}", source, err))
+}
+
+/// Time on a clock as "m:ss", "h:mm:ss" from an hour, with tenths when little time is left
+pub(crate) fn format_clock(time: Duration) -> String {
+    if time < SHOW_TENTHS_BELOW {
+        return format!("0:{:02}.{}", time.as_secs(), time.subsec_millis() / 100);
+    }
+    let seconds = time.as_secs();
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+pub(crate) fn color_name(color: PieceColor) -> &'static str {
+    match color {
+        White => "White",
+        Black => "Black",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse_command("e4"), Ok(Command::Move("e4".to_string())));
+        assert_eq!(parse_command("O-O"), Ok(Command::Move("O-O".to_string())));
+        assert_eq!(parse_command("UNDO"), Ok(Command::Undo));
+        assert_eq!(parse_command("?"), Ok(Command::Help));
+        assert_eq!(parse_command("exit"), Ok(Command::Quit));
+        assert_eq!(parse_command("load games/my game.pgn"), Ok(Command::Load("games/my game.pgn".to_string())));
+        assert_eq!(parse_command("save out.pgn"), Ok(Command::Save("out.pgn".to_string())));
+        assert_eq!(parse_command("moves"), Ok(Command::Moves(None)));
+        assert_eq!(parse_command("moves e2"), Ok(Command::Moves(Some("e2".to_string()))));
+        assert_eq!(parse_command("flip"), Ok(Command::Flip(false)));
+        assert_eq!(parse_command("flip Auto"), Ok(Command::Flip(true)));
+    }
+
+    #[test]
+    fn rejects_bad_commands() {
+        assert_eq!(parse_command("undo 2"), Err("'undo' takes no arguments".to_string()));
+        assert_eq!(parse_command("load"), Err("Usage: load <fen|file.pgn>".to_string()));
+        assert_eq!(parse_command("save"), Err("Usage: save <file.pgn>".to_string()));
+        assert_eq!(parse_command("moves e2 e4"), Err("Usage: moves [square]".to_string()));
+        assert_eq!(parse_command("flip twice"), Err("Usage: flip [auto]".to_string()));
+        assert_eq!(parse_command("e4 e5"), Err("Enter one move at a time".to_string()));
+        assert_eq!(
+            parse_command("castle"),
+            Err("Unknown command 'castle', type 'help' for the list of commands".to_string())
+        );
+    }
+}
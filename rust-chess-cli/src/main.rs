@@ -1,56 +1,171 @@
-use rust_chess_core::game::Game;
-use std::io;
-use std::io::Write;
+mod batch;
+mod book;
+mod build_book;
+mod build_tablebase;
+mod console;
+mod mate;
+mod play;
+mod problem;
+mod tui;
+mod uci;
+mod validate;
+mod view;
+mod xboard;
+
+use book::OpeningBook;
+use console::Opponent;
+use rust_chess_core::board::PieceColor::Black;
+use rust_chess_core::clock::TimeControl;
+use rust_chess_core::display::{DisplayOptions, PieceStyle};
+use rust_chess_core::mcts::{DEFAULT_ROLLOUT_PLIES, LeafValue, MctsOptions, MctsPlayer};
+use rust_chess_core::skill::SkillLevel;
 
 fn main() {
-    let mut game = Game::new();
-    let mut input = String::new();
-    loop {
-        // read command from the console
-        game.board().print(true);
-        print!("Your move: ");
-        io::stdout().flush().unwrap();
-        
-        input.clear();
-        let input_result = io::stdin().read_line(&mut input);
-        if input_result.is_err() {
-            println!("Error reading input: {}", input_result.err().unwrap());
-            return;
-        }
-        
-        let command = input.trim();
-        let mv = game.parse_short_notation(command);
-        let Ok(mv) = mv else {
-            println!("Error: {}", mv.err().unwrap());
-            continue
-        };
-        
-        let move_result = game.make_move(&mv);
-        if move_result.is_err() {
-            println!("Error: {}", move_result.err().unwrap());
-            continue
-        }
-        
-        if let Some(game_result) = game.result() {
-            println!("Game over!");
-            if let Some(winner_color) = game_result.winner {
-                println!("Winner: {:?}", format!("{}", winner_color));
-            } else {
-                println!("It's a draw!");
+    let args: Vec<String> = std::env::args().collect();
+    let result = match args.get(1).map(String::as_str) {
+        // batch prints its own errors, its exit codes tell them apart
+        Some("batch") => std::process::exit(batch::run(&args[2..])),
+        Some("build-book") => build_book::run(&args[2..]),
+        Some("build-tablebase") => build_tablebase::run(&args[2..]),
+        Some("mate") => mate::run(&args[2..]),
+        Some("play") => play::run(&args[2..]),
+        Some("problem") => problem::run(&args[2..]),
+        Some("tui") => tui::run(&args[2..]),
+        Some("validate") => validate::run(&args[2..]),
+        Some("view") => view::run(&args[2..]),
+        _ => run(&args),
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+/// Without a subcommand, runs the UCI or xboard engine, or a game in the console
+fn run(args: &[String]) -> Result<(), String> {
+    let book = option_value(args, "--book")
+        .map(OpeningBook::open)
+        .transpose()
+        .map_err(|err| format!("Error opening book: {}", err))?;
+    let skill = skill_level(args)?;
+
+    if args.iter().any(|arg| arg == "--uci") {
+        uci::run(book, skill.unwrap_or_default());
+        return Ok(());
+    }
+    if args.iter().any(|arg| arg == "--xboard") {
+        xboard::run(book, skill.unwrap_or_default());
+        return Ok(());
+    }
+
+    // "--mcts [rollout]" lets a Monte Carlo tree search answer the moves instead of the alpha-beta search
+    let opponent = if args.iter().any(|arg| arg == "--mcts") {
+        let leaf_value = if option_value(args, "--mcts") == Some("rollout") {
+            LeafValue::Rollout {
+                max_plies: DEFAULT_ROLLOUT_PLIES,
             }
-            break;
-        }
-    }
-
-    // game.board().print();
-    // game.make_move(Move::new(1, 1, 1, 3)).unwrap();
-    // game.board().print();
-    // game.make_move(Move::new(1, 6, 1, 4)).unwrap();
-    // game.board().print();
-    // game.make_move(Move::new(2, 1, 2, 3)).unwrap();
-    // game.board().print();
-    // game.make_move(Move::new(4, 6, 1, 3)).unwrap();
-    // game.board().print();
-    // game.make_move(Move::from_long_notation("c4b5")).unwrap();
-    // game.board().print();
+        } else {
+            LeafValue::Evaluation
+        };
+        Opponent::Mcts(Box::new(MctsPlayer::new(MctsOptions {
+            leaf_value,
+            ..Default::default()
+        })))
+    } else {
+        // with a strength given, the engine answers the moves as Black
+        skill.map_or(Opponent::Human, Opponent::Engine)
+    };
+    let time_control = time_control(args)?;
+    let display = display_options(args)?;
+    console::run(opponent, Black, time_control, display, auto_flip(args));
+    Ok(())
+}
+
+/// Reads the time control of a console game from "--time <control>", e.g. "5+3", None for an untimed game
+fn time_control(args: &[String]) -> Result<Option<TimeControl>, String> {
+    option_value(args, "--time").map(TimeControl::parse).transpose()
+}
+
+/// Reads how the console draws the board from "--board letters|unicode" and the "--ansi" flag for coloured squares
+fn display_options(args: &[String]) -> Result<DisplayOptions, String> {
+    let style = match option_value(args, "--board") {
+        None | Some("letters") => PieceStyle::Letters,
+        Some("unicode") => PieceStyle::Unicode,
+        Some(style) => return Err(format!("Invalid board style {}, expected letters or unicode", style)),
+    };
+    Ok(DisplayOptions {
+        style,
+        colors: args.iter().any(|arg| arg == "--ansi"),
+        ..Default::default()
+    })
+}
+
+/// "--auto-flip" shows the board from the side to move in the console
+fn auto_flip(args: &[String]) -> bool {
+    args.iter().any(|arg| arg == "--auto-flip")
+}
+
+/// Reads the engine strength from "--skill-level <0-20>" or "--elo <elo>", None if neither is given
+fn skill_level(args: &[String]) -> Result<Option<SkillLevel>, String> {
+    if let Some(level) = option_value(args, "--skill-level") {
+        let level = level.parse().map_err(|_| format!("Invalid skill level {}", level))?;
+        return Ok(Some(SkillLevel::new(level)));
+    }
+    if let Some(elo) = option_value(args, "--elo") {
+        let elo = elo.parse().map_err(|_| format!("Invalid Elo {}", elo))?;
+        return Ok(Some(SkillLevel::from_elo(elo)));
+    }
+    Ok(None)
+}
+
+/// Returns the value following the option name in the arguments, e.g. "book.bin" for "--book book.bin"
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let index = args.iter().position(|arg| arg == name)?;
+    args.get(index + 1).map(|value| value.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn reads_option_values() {
+        assert_eq!(option_value(&args("--book book.bin --uci"), "--book"), Some("book.bin"));
+        assert_eq!(option_value(&args("--uci --book"), "--book"), None);
+        assert_eq!(option_value(&args("--uci"), "--book"), None);
+        assert!(auto_flip(&args("--ansi --auto-flip")));
+        assert!(!auto_flip(&args("--ansi")));
+    }
+
+    #[test]
+    fn reads_time_controls() {
+        assert_eq!(time_control(&args("--ansi")), Ok(None));
+        let control = time_control(&args("--time 5+3")).unwrap().unwrap();
+        assert_eq!(control, TimeControl::parse("5+3").unwrap());
+        assert_eq!(control.stages[0].time, Duration::from_secs(300));
+        assert!(time_control(&args("--time five")).is_err());
+    }
+
+    #[test]
+    fn reads_display_options_and_skill_levels() {
+        let display = display_options(&args("--board unicode --ansi")).unwrap_or_else(|err| panic!("{}", err));
+        assert!(display.style == PieceStyle::Unicode && display.colors);
+        let display = display_options(&args("")).unwrap_or_else(|err| panic!("{}", err));
+        assert!(display.style == PieceStyle::Letters && !display.colors);
+        assert_eq!(
+            display_options(&args("--board ascii")).err(),
+            Some("Invalid board style ascii, expected letters or unicode".to_string())
+        );
+
+        assert_eq!(skill_level(&args("--uci")), Ok(None));
+        assert_eq!(skill_level(&args("--skill-level 5")), Ok(Some(SkillLevel::new(5))));
+        assert_eq!(skill_level(&args("--elo 1500")), Ok(Some(SkillLevel::from_elo(1500))));
+        assert_eq!(skill_level(&args("--elo strong")), Err("Invalid Elo strong".to_string()));
+        assert_eq!(skill_level(&args("--skill-level -1")), Err("Invalid skill level -1".to_string()));
+    }
 }
@@ -0,0 +1,47 @@
+use rust_chess_core::game::Game;
+use rust_chess_core::mate_solver::solve_mate;
+use std::time::Instant;
+
+const USAGE: &str = "Usage: rust-chess-cli mate <fen> <n>";
+
+/// Solves a mate in N problem. `args` are the arguments after "mate", the FEN may be given unquoted.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let [fen @ .., moves] = args else {
+        return Err(USAGE.to_string());
+    };
+    if fen.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    let moves: u32 = match moves.parse() {
+        Ok(moves) if moves > 0 => moves,
+        _ => return Err(format!("Invalid number of moves {}\n{}", moves, USAGE)),
+    };
+    let game = Game::from_fen(&fen.join(" "))?;
+
+    let start = Instant::now();
+    let solution = solve_mate(&game, moves);
+    let elapsed = start.elapsed();
+
+    if !solution.is_mate() {
+        println!("No mate in {}", moves);
+    } else {
+        print!("{}", solution.format_tree(&game));
+        if solution.is_cooked() {
+            let keys = solution
+                .keys
+                .iter()
+                .map(|key| game.to_short_notation(&key.mv).unwrap_or_else(|_| key.mv.to_string()))
+                .collect::<Vec<_>>();
+            println!("Cooked, {} key moves: {}", keys.len(), keys.join(", "));
+        }
+        for dual in &solution.duals {
+            let line = game
+                .moves_to_short_notation(dual)
+                .map(|moves| moves.join(" "))
+                .unwrap_or_default();
+            println!("Dual after {}", line);
+        }
+    }
+    println!("Nodes: {}, time: {} ms", solution.nodes, elapsed.as_millis());
+    Ok(())
+}
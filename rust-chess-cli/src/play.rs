@@ -0,0 +1,107 @@
+use crate::console;
+use crate::console::Opponent;
+use crate::{auto_flip, display_options, option_value, time_control};
+use rust_chess_core::board::PieceColor;
+use rust_chess_core::board::PieceColor::{Black, White};
+use rust_chess_core::clock::TimeControl;
+use rust_chess_core::display::DisplayOptions;
+use rust_chess_core::random::Random;
+use rust_chess_core::skill::{MAX_SKILL_LEVEL, SkillLevel};
+
+const USAGE: &str = "Usage: rust-chess-cli play [--color white|black|random] [--level 0-20] [--time <minutes>[+<increment>]] \
+                     [--board letters|unicode] [--ansi] [--auto-flip]";
+
+// what the arguments of "play" ask for
+struct PlayOptions {
+    // the side the player plays
+    color: PieceColor,
+    skill: SkillLevel,
+    time_control: Option<TimeControl>,
+    display: DisplayOptions,
+    auto_flip: bool,
+}
+
+/// Plays against the engine in the console. `args` are the arguments after "play".
+pub fn run(args: &[String]) -> Result<(), String> {
+    let options = parse_args(args)?;
+    println!("You play {}", if options.color == White { "White" } else { "Black" });
+    console::run(
+        Opponent::Engine(options.skill),
+        options.color.opposite(),
+        options.time_control,
+        options.display,
+        options.auto_flip,
+    );
+    Ok(())
+}
+
+fn parse_args(args: &[String]) -> Result<PlayOptions, String> {
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--color" | "--level" | "--time" | "--board" => {
+                args_iter.next();
+            }
+            "--ansi" | "--auto-flip" => {}
+            _ => return Err(format!("Unknown argument {}\n{}", arg, USAGE)),
+        }
+    }
+
+    let color = match option_value(args, "--color").map(|color| color.to_lowercase()).as_deref() {
+        None | Some("white") => White,
+        Some("black") => Black,
+        Some("random") if Random::from_time().below(2) == 0 => White,
+        Some("random") => Black,
+        Some(color) => return Err(format!("Invalid color {}\n{}", color, USAGE)),
+    };
+    let skill = match option_value(args, "--level") {
+        Some(level) => match level.parse() {
+            Ok(level) if level <= MAX_SKILL_LEVEL => SkillLevel::new(level),
+            _ => return Err(format!("Invalid level {}, expected 0 to {}\n{}", level, MAX_SKILL_LEVEL, USAGE)),
+        },
+        None => SkillLevel::full_strength(),
+    };
+    let time_control = time_control(args).map_err(|err| format!("{}\n{}", err, USAGE))?;
+    let display = display_options(args).map_err(|err| format!("{}\n{}", err, USAGE))?;
+    Ok(PlayOptions { color, skill, time_control, display, auto_flip: auto_flip(args) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_chess_core::display::PieceStyle;
+    use std::time::Duration;
+
+    fn parse(args: &str) -> Result<PlayOptions, String> {
+        parse_args(&args.split_whitespace().map(String::from).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn parses_play_arguments() {
+        let options = parse("").unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(options.color, White);
+        assert_eq!(options.skill, SkillLevel::full_strength());
+        assert_eq!(options.time_control, None);
+        assert!(options.display.style == PieceStyle::Letters && !options.display.colors && !options.auto_flip);
+
+        let options = parse("--color BLACK --level 3 --time 5+3 --board unicode --ansi --auto-flip")
+            .unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(options.color, Black);
+        assert_eq!(options.skill, SkillLevel::new(3));
+        assert_eq!(options.time_control, Some(TimeControl::parse("5+3").unwrap()));
+        assert_eq!(options.time_control.unwrap().stages[0].time, Duration::from_secs(300));
+        assert!(options.display.style == PieceStyle::Unicode && options.display.colors && options.auto_flip);
+        assert!(parse("--color random").is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_play_arguments() {
+        let error = |args| parse(args).err().unwrap_or_default();
+        assert!(error("--depth 3").starts_with("Unknown argument --depth\nUsage: "));
+        assert!(error("--color green").starts_with("Invalid color green\n"));
+        assert!(error("--level 21").starts_with("Invalid level 21, expected 0 to 20\n"));
+        assert!(error("--level x").starts_with("Invalid level x"));
+        assert!(error("--board ascii").starts_with("Invalid board style ascii"));
+        assert!(parse("--time 5+x").is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use rust_chess_core::problem::{TwinChange, parse_problems};
+use std::time::Instant;
+
+const USAGE: &str = "Usage: rust-chess-cli problem <file>";
+
+/// Solves all problems of a file in the Popeye-like format. `args` are the arguments after "problem".
+pub fn run(args: &[String]) -> Result<(), String> {
+    let [path] = args else {
+        return Err(USAGE.to_string());
+    };
+    let text = std::fs::read_to_string(path).map_err(|err| format!("Error reading {}: {}", path, err))?;
+    let problems = parse_problems(&text)?;
+
+    for (i, problem) in problems.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let mut header = format!("Problem {}", i + 1);
+        if let Some(title) = &problem.title {
+            header.push_str(&format!(": {}", title));
+        }
+        if let Some(author) = &problem.author {
+            header.push_str(&format!(" ({})", author));
+        }
+        println!("{}", header);
+
+        let start = Instant::now();
+        let solutions = match problem.solve() {
+            Ok(solutions) => solutions,
+            Err(err) => {
+                println!("  {}", err);
+                continue;
+            }
+        };
+        for solution in solutions {
+            let position = &solution.position;
+            let mut description = vec![position.label.clone()];
+            if !position.changes.iter().any(|change| matches!(change, TwinChange::Stipulation(_))) {
+                description.push(position.stipulation.to_string());
+            }
+            description.extend(position.changes.iter().map(|change| change.to_string()));
+            println!("{}", description.join(" ").trim_start());
+            if solution.solutions.is_empty() {
+                println!("  no solution");
+            }
+            for line in &solution.solutions {
+                println!("  {}", solution.format_solution(line));
+            }
+        }
+        println!("Time: {} ms", start.elapsed().as_millis());
+    }
+    Ok(())
+}
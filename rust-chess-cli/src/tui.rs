@@ -0,0 +1,572 @@
+use crate::console::{
+    color_name, engine_move, engine_time_manager, format_clock, game_over_message, load_game, result_tag,
+};
+use crate::{option_value, time_control};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{event, execute, queue, terminal};
+use rust_chess_core::board::PieceColor::{Black, White};
+use rust_chess_core::board::{Board, PieceColor};
+use rust_chess_core::clock::Clock;
+use rust_chess_core::display::{
+    BLACK_PIECE, CHECK_SQUARE, DARK_SQUARE, DESTINATION_SQUARE, LAST_MOVE_DARK_SQUARE, LAST_MOVE_LIGHT_SQUARE,
+    LIGHT_SQUARE, WHITE_PIECE, glyph,
+};
+use rust_chess_core::game::Game;
+use rust_chess_core::pos::Pos;
+use rust_chess_core::r#move::Move;
+use rust_chess_core::random::Random;
+use rust_chess_core::skill::{MAX_SKILL_LEVEL, SkillLevel};
+use std::io;
+use std::io::{Stdout, Write};
+use std::time::Duration;
+
+const USAGE: &str = "Usage: rust-chess-cli tui [--color white|black|random] [--level 0-20] [--two-players] \
+                     [--time <minutes>[+<increment>]] [--load <fen|file.pgn>]";
+
+// how often the screen is redrawn while a clock runs
+const TICK: Duration = Duration::from_millis(100);
+// backgrounds of the cursor and of the selected piece
+const CURSOR_SQUARE: u8 = 75;
+const SELECTED_SQUARE: u8 = 179;
+// where the panes are drawn
+const BOARD_TOP: u16 = 1;
+const BOARD_LEFT: u16 = 1;
+const SIDE_PANE_LEFT: u16 = 32;
+const STATUS_ROW: u16 = 11;
+// the move list is under the clocks, beside the board
+const MOVE_LIST_TOP: u16 = 3;
+const MOVE_LIST_ROWS: usize = 7;
+
+const KEYS: &str = "arrows: cursor  enter/space: select, move  ,/.: back/forward  home/end: first/last  \
+                    f: flip  u: undo  n: new  q: quit";
+
+/// Puts the terminal in full-screen raw mode, and back when dropped, also on errors
+struct RawScreen {
+    out: Stdout,
+}
+
+impl RawScreen {
+    fn enter() -> io::Result<RawScreen> {
+        terminal::enable_raw_mode()?;
+        let mut out = io::stdout();
+        execute!(out, EnterAlternateScreen, Hide)?;
+        Ok(RawScreen { out })
+    }
+}
+
+impl Drop for RawScreen {
+    fn drop(&mut self) {
+        let _ = execute!(self.out, ResetColor, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+struct Tui {
+    // the whole game, with the moves after the viewed position
+    game: Game,
+    // SAN of the moves of the game
+    san: Vec<String>,
+    // number of moves played in the position shown, less than the moves of the game when reviewing
+    view_ply: usize,
+    // the position shown
+    view: Game,
+    engine: Option<SkillLevel>,
+    engine_color: PieceColor,
+    clock: Option<Clock>,
+    random: Random,
+    perspective: PieceColor,
+    cursor: Pos,
+    selected: Option<Pos>,
+    // shown in the status line until the next key
+    message: String,
+}
+
+/// Plays or reviews a game in a full-screen terminal UI. `args` are the arguments after "tui".
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--color" | "--level" | "--time" | "--load" => {
+                args_iter.next();
+            }
+            "--two-players" => {}
+            _ => return Err(format!("Unknown argument {}\n{}", arg, USAGE)),
+        }
+    }
+    let color = match option_value(args, "--color").map(|color| color.to_lowercase()).as_deref() {
+        None | Some("white") => White,
+        Some("black") => Black,
+        Some("random") if Random::from_time().below(2) == 0 => White,
+        Some("random") => Black,
+        Some(color) => return Err(format!("Invalid color {}\n{}", color, USAGE)),
+    };
+    let engine = match option_value(args, "--level") {
+        _ if args.iter().any(|arg| arg == "--two-players") => None,
+        Some(level) => match level.parse() {
+            Ok(level) if level <= MAX_SKILL_LEVEL => Some(SkillLevel::new(level)),
+            _ => return Err(format!("Invalid level {}, expected 0 to {}\n{}", level, MAX_SKILL_LEVEL, USAGE)),
+        },
+        None => Some(SkillLevel::full_strength()),
+    };
+    let time_control = time_control(args).map_err(|err| format!("{}\n{}", err, USAGE))?;
+    let game = match option_value(args, "--load") {
+        Some(source) => load_game(source)?,
+        None => Game::new(),
+    };
+
+    let mut tui = Tui {
+        view: game.clone(),
+        view_ply: game.history().moves().len(),
+        san: Vec::new(),
+        game,
+        engine,
+        engine_color: color.opposite(),
+        clock: time_control.map(Clock::new),
+        random: Random::from_time(),
+        perspective: color,
+        cursor: Pos::new(4, if color == White { 1 } else { 6 }),
+        selected: None,
+        message: String::new(),
+    };
+    tui.update_san()?;
+    tui.start_clock();
+
+    let mut screen = RawScreen::enter().map_err(|err| format!("Can't set up the terminal: {}", err))?;
+    tui.event_loop(&mut screen.out).map_err(|err| format!("Terminal error: {}", err))
+}
+
+impl Tui {
+    fn event_loop(&mut self, out: &mut Stdout) -> io::Result<()> {
+        loop {
+            self.check_flag();
+            if self.is_engine_turn() {
+                self.message = "The engine is thinking...".to_string();
+                self.draw(out)?;
+                self.message.clear();
+                self.play_engine_move();
+            }
+            self.draw(out)?;
+
+            // wake up regularly while a clock runs, to show it ticking
+            let clock_running = self.clock.as_ref().is_some_and(|clock| clock.is_running());
+            if clock_running && !event::poll(TICK)? {
+                continue;
+            }
+            match event::read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release && !self.handle_key(key) => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    /// Acts on a key, false to quit
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        self.message.clear();
+        // up on the screen is towards the opponent's side
+        let (up, right) = if self.perspective == White { (1, 1) } else { (-1, -1) };
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Char('q') => return false,
+            KeyCode::Esc if self.selected.is_none() => return false,
+            KeyCode::Esc | KeyCode::Backspace => self.selected = None,
+            KeyCode::Up => self.move_cursor(0, up),
+            KeyCode::Down => self.move_cursor(0, -up),
+            KeyCode::Left => self.move_cursor(-right, 0),
+            KeyCode::Right => self.move_cursor(right, 0),
+            KeyCode::Enter | KeyCode::Char(' ') => self.select_or_move(),
+            KeyCode::Char(',') | KeyCode::PageUp => self.show_ply(self.view_ply.saturating_sub(1)),
+            KeyCode::Char('.') | KeyCode::PageDown => self.show_ply(self.view_ply + 1),
+            KeyCode::Home => self.show_ply(0),
+            KeyCode::End => self.show_ply(self.game.history().moves().len()),
+            KeyCode::Char('f') => self.perspective = self.perspective.opposite(),
+            KeyCode::Char('u') => self.undo(),
+            KeyCode::Char('n') => self.new_game(),
+            _ => {}
+        }
+        true
+    }
+
+    fn move_cursor(&mut self, cols: i8, rows: i8) {
+        self.cursor = Pos::new((self.cursor.col() + cols).clamp(0, 7), (self.cursor.row() + rows).clamp(0, 7));
+    }
+
+    /// Selects the piece under the cursor, or moves the selected piece to the cursor
+    fn select_or_move(&mut self) {
+        if !self.is_live() {
+            self.message = "Press end to return to the game before moving".to_string();
+            return;
+        }
+        if self.game.result().is_some() {
+            self.message = "The game is over, press n for a new game".to_string();
+            return;
+        }
+        if self.is_engine_turn() {
+            return;
+        }
+        let (col, row) = self.cursor.tuple();
+        if let Some(from) = self.selected {
+            let mv = self
+                .game
+                .get_moves_from(from.col(), from.row())
+                .iter()
+                .find(|mv| (mv.to_col, mv.to_row) == (col, row))
+                .copied();
+            if let Some(mv) = mv {
+                self.selected = None;
+                self.make_move(&mv);
+                return;
+            }
+        }
+        let square = self.game.board().at(col, row);
+        if self.selected == Some(self.cursor) {
+            self.selected = None;
+        } else if !square.is_occupied_by_color(self.game.turn()) {
+            self.message = match self.selected {
+                Some(_) => "Illegal move".to_string(),
+                None => format!("Select a piece of {}", color_name(self.game.turn())),
+            };
+        } else if self.game.get_moves_from(col, row).is_empty() {
+            self.message = format!("No legal moves from {}", self.cursor);
+        } else {
+            self.selected = Some(self.cursor);
+        }
+    }
+
+    /// Makes a move of the live game and presses the clock. A move made after the time ran out loses on time.
+    fn make_move(&mut self, mv: &Move) {
+        if self.check_flag() {
+            return;
+        }
+        let mover = self.game.turn();
+        if let Err(err) = self.game.make_move(mv) {
+            self.message = err.to_string();
+            return;
+        }
+        if let Some(clock) = &mut self.clock
+            && clock.press().is_err()
+        {
+            self.game.undo_move();
+            self.game.time_out(mover);
+        }
+        if let Some(clock) = &mut self.clock
+            && self.game.result().is_some()
+        {
+            clock.stop();
+        }
+        self.game_changed();
+    }
+
+    fn is_live(&self) -> bool {
+        self.view_ply == self.game.history().moves().len()
+    }
+
+    fn is_engine_turn(&self) -> bool {
+        self.engine.is_some()
+            && self.is_live()
+            && self.game.turn() == self.engine_color
+            && self.game.result().is_none()
+    }
+
+    fn play_engine_move(&mut self) {
+        let Some(skill) = self.engine else {
+            return;
+        };
+        let time_manager = engine_time_manager(self.clock.as_ref(), self.engine_color);
+        if let Some(reply) = engine_move(&self.game, skill, time_manager, &mut self.random) {
+            self.make_move(&reply);
+        }
+    }
+
+    /// Takes back the last move, and the engine's reply before it, in an untimed game
+    fn undo(&mut self) {
+        if self.clock.is_some() {
+            self.message = "Moves can't be taken back in a timed game".to_string();
+            return;
+        }
+        if self.game.undo_move().is_none() {
+            self.message = "Nothing to undo".to_string();
+            return;
+        }
+        if self.engine.is_some() && self.game.turn() == self.engine_color {
+            self.game.undo_move();
+        }
+        self.game_changed();
+    }
+
+    fn new_game(&mut self) {
+        self.game = Game::new();
+        self.start_clock();
+        self.game_changed();
+    }
+
+    /// Shows the live position after the game changed
+    fn game_changed(&mut self) {
+        self.selected = None;
+        if let Err(err) = self.update_san() {
+            self.message = err;
+        }
+        self.show_ply(self.game.history().moves().len());
+    }
+
+    fn update_san(&mut self) -> Result<(), String> {
+        self.san = self.game.initial_game().moves_to_short_notation(self.game.history().moves())?;
+        Ok(())
+    }
+
+    /// Shows the position after `ply` moves of the game
+    fn show_ply(&mut self, ply: usize) {
+        let moves = self.game.history().moves();
+        self.view_ply = ply.min(moves.len());
+        // the live game also knows about a time out, which replaying the moves doesn't
+        self.view = if self.view_ply == moves.len() {
+            self.game.clone()
+        } else {
+            let mut view = self.game.initial_game();
+            for mv in &moves[..self.view_ply] {
+                view.make_move(mv).expect("Moves of the game should be legal");
+            }
+            view
+        };
+        self.selected = None;
+    }
+
+    fn start_clock(&mut self) {
+        if let Some(clock) = &mut self.clock {
+            *clock = Clock::new(clock.time_control().clone());
+            clock.set_turn(self.game.turn());
+            clock.start();
+        }
+    }
+
+    /// Ends the game if the side to move ran out of time, returns true then
+    fn check_flag(&mut self) -> bool {
+        if self.game.result().is_some() {
+            return false;
+        }
+        let Some(color) = self.clock.as_ref().and_then(|clock| clock.flagged()) else {
+            return false;
+        };
+        if let Some(clock) = &mut self.clock {
+            clock.stop();
+        }
+        self.game.time_out(color);
+        self.game_changed();
+        true
+    }
+
+    fn draw(&self, out: &mut Stdout) -> io::Result<()> {
+        queue!(out, Clear(ClearType::All))?;
+        self.draw_board(out)?;
+        self.draw_clocks(out)?;
+        self.draw_move_list(out)?;
+        queue!(
+            out,
+            ResetColor,
+            MoveTo(BOARD_LEFT, STATUS_ROW),
+            Print(self.status()),
+            MoveTo(BOARD_LEFT, STATUS_ROW + 1),
+            SetAttribute(Attribute::Bold),
+            Print(&self.message),
+            SetAttribute(Attribute::Reset),
+            MoveTo(BOARD_LEFT, STATUS_ROW + 3),
+            SetAttribute(Attribute::Dim),
+            Print(KEYS),
+            SetAttribute(Attribute::Reset),
+        )?;
+        out.flush()
+    }
+
+    fn draw_board(&self, out: &mut Stdout) -> io::Result<()> {
+        let board = self.view.board();
+        let last_move = self.game.history().moves()[..self.view_ply].last();
+        let check = match self.view.is_check() {
+            true => board.find_king(self.view.turn()),
+            false => None,
+        };
+        let destinations: Vec<Pos> = match self.selected {
+            Some(from) => (self.game.get_moves_from(from.col(), from.row()).iter())
+                .map(|mv| Pos::new(mv.to_col, mv.to_row))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let (rows, cols) = Board::display_order(self.perspective);
+        for (y, row) in rows.into_iter().enumerate() {
+            queue!(out, ResetColor, MoveTo(BOARD_LEFT, BOARD_TOP + y as u16), Print(format!("{} ", row + 1)))?;
+            for col in cols {
+                let pos = Pos::new(col, row);
+                let light = (row + col) % 2 == 1;
+                let is_last_move = last_move
+                    .is_some_and(|mv| (mv.from_col, mv.from_row) == (col, row) || (mv.to_col, mv.to_row) == (col, row));
+                let background = if pos == self.cursor {
+                    CURSOR_SQUARE
+                } else if self.selected == Some(pos) {
+                    SELECTED_SQUARE
+                } else if check == Some(pos) {
+                    CHECK_SQUARE
+                } else if destinations.contains(&pos) {
+                    DESTINATION_SQUARE
+                } else if is_last_move {
+                    if light { LAST_MOVE_LIGHT_SQUARE } else { LAST_MOVE_DARK_SQUARE }
+                } else if light {
+                    LIGHT_SQUARE
+                } else {
+                    DARK_SQUARE
+                };
+                let (text, foreground) = match board.at(col, row).piece() {
+                    // solid glyphs for both sides, the colour tells them apart
+                    Some((piece_type, color)) => (
+                        format!(" {} ", glyph(piece_type, Black)),
+                        if color == White { WHITE_PIECE } else { BLACK_PIECE },
+                    ),
+                    None => ("   ".to_string(), BLACK_PIECE),
+                };
+                queue!(
+                    out,
+                    SetBackgroundColor(Color::AnsiValue(background)),
+                    SetForegroundColor(Color::AnsiValue(foreground)),
+                    Print(text)
+                )?;
+            }
+        }
+        let files: String = cols.iter().map(|&col| format!(" {} ", (b'a' + col as u8) as char)).collect();
+        queue!(out, ResetColor, MoveTo(BOARD_LEFT, BOARD_TOP + 8), Print(format!("  {}", files)))
+    }
+
+    fn draw_clocks(&self, out: &mut Stdout) -> io::Result<()> {
+        let Some(clock) = &self.clock else {
+            return Ok(());
+        };
+        queue!(out, MoveTo(SIDE_PANE_LEFT, BOARD_TOP))?;
+        for color in [White, Black] {
+            let running = clock.is_running() && clock.turn() == color;
+            let text = format!("{} {}", color_name(color), format_clock(clock.remaining(color)));
+            if running {
+                queue!(out, SetAttribute(Attribute::Reverse), Print(text), SetAttribute(Attribute::Reset))?;
+            } else {
+                queue!(out, Print(text))?;
+            }
+            queue!(out, Print("   "))?;
+        }
+        Ok(())
+    }
+
+    /// Moves in SAN two per row, scrolled so that the shown move is visible and highlighted
+    fn draw_move_list(&self, out: &mut Stdout) -> io::Result<()> {
+        // a game from a position with Black to move starts with "1... e5"
+        let black_first = self.game.initial_game().turn() == Black;
+        let offset = black_first as usize;
+        let current_row = (self.view_ply + offset).saturating_sub(1) / 2;
+        let first_row = (current_row + 1).saturating_sub(MOVE_LIST_ROWS);
+
+        queue!(out, MoveTo(SIDE_PANE_LEFT, MOVE_LIST_TOP), SetAttribute(Attribute::Underlined), Print("Moves"))?;
+        queue!(out, SetAttribute(Attribute::Reset))?;
+        let rows = (self.san.len() + offset).div_ceil(2);
+        for (y, row) in (first_row..rows).take(MOVE_LIST_ROWS).enumerate() {
+            queue!(out, MoveTo(SIDE_PANE_LEFT, MOVE_LIST_TOP + 1 + y as u16), Print(format!("{:>3}. ", row + 1)))?;
+            for side in 0..2 {
+                let ply = (row * 2 + side).wrapping_sub(offset);
+                let text = match self.san.get(ply) {
+                    Some(san) => format!("{:<8}", san),
+                    None if ply == usize::MAX => format!("{:<8}", "..."),
+                    None => continue,
+                };
+                if ply + 1 == self.view_ply {
+                    queue!(out, SetAttribute(Attribute::Reverse), Print(text), SetAttribute(Attribute::Reset))?;
+                } else {
+                    queue!(out, Print(text))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> String {
+        let moves = self.game.history().moves().len();
+        if !self.is_live() {
+            return format!("Reviewing move {} of {}, press end to return to the game", self.view_ply, moves);
+        }
+        if let Some(message) = game_over_message(&self.game) {
+            return format!("Game over! {} ({})", message, result_tag(&self.game));
+        }
+        let check = if self.game.is_check() { "Check! " } else { "" };
+        format!("{}{} to move", check, color_name(self.game.turn()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a game between two players at the keyboard, White at the bottom
+    fn tui() -> Tui {
+        Tui {
+            game: Game::new(),
+            san: Vec::new(),
+            view_ply: 0,
+            view: Game::new(),
+            engine: None,
+            engine_color: Black,
+            clock: None,
+            random: Random::new(1),
+            perspective: White,
+            cursor: Pos::new(4, 1),
+            selected: None,
+            message: String::new(),
+        }
+    }
+
+    fn press(tui: &mut Tui, codes: &[KeyCode]) -> bool {
+        codes.iter().all(|&code| tui.handle_key(KeyEvent::from(code)))
+    }
+
+    #[test]
+    fn moves_pieces_with_the_cursor() {
+        let mut tui = tui();
+        // e2-e4, then d7-d5 with the board flipped
+        assert!(press(&mut tui, &[KeyCode::Enter, KeyCode::Up, KeyCode::Up, KeyCode::Char(' ')]));
+        assert_eq!(tui.san, ["e4"]);
+        assert!(press(&mut tui, &[KeyCode::Char('f'), KeyCode::Down, KeyCode::Down, KeyCode::Down]));
+        assert_eq!(tui.cursor.tuple(), (4, 6));
+        assert!(press(&mut tui, &[KeyCode::Right, KeyCode::Enter, KeyCode::Up, KeyCode::Up, KeyCode::Enter]));
+        assert_eq!(tui.san, ["e4", "d5"]);
+        assert_eq!(tui.view.to_fen(), tui.game.to_fen());
+
+        // the cursor stops at the edge of the board
+        press(&mut tui, &[KeyCode::Right; 10]);
+        assert_eq!(tui.cursor.col(), 0);
+        press(&mut tui, &[KeyCode::Enter]);
+        assert_eq!(tui.message, "Select a piece of White");
+        assert!(tui.selected.is_none());
+    }
+
+    #[test]
+    fn reviews_and_takes_back_moves() {
+        let mut tui = tui();
+        for mv in ["e2e4", "e7e5", "g1f3"] {
+            tui.make_move(&Move::parse_long_notation(mv).unwrap());
+        }
+        press(&mut tui, &[KeyCode::Char(','), KeyCode::Char(',')]);
+        assert_eq!(tui.view_ply, 1);
+        press(&mut tui, &[KeyCode::Enter]);
+        assert_eq!(tui.message, "Press end to return to the game before moving");
+        press(&mut tui, &[KeyCode::Home]);
+        assert_eq!(tui.view.to_fen(), Game::new().to_fen());
+        press(&mut tui, &[KeyCode::End, KeyCode::Char('.')]);
+        assert_eq!(tui.view_ply, 3);
+
+        press(&mut tui, &[KeyCode::Char('u')]);
+        assert_eq!(tui.san, ["e4", "e5"]);
+        press(&mut tui, &[KeyCode::Char('n'), KeyCode::Char('u')]);
+        assert_eq!(tui.message, "Nothing to undo");
+
+        assert!(!press(&mut tui, &[KeyCode::Char('q')]));
+        assert!(!tui.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)));
+        tui.selected = Some(tui.cursor);
+        assert!(press(&mut tui, &[KeyCode::Esc]));
+        assert!(!press(&mut tui, &[KeyCode::Esc]));
+    }
+}
@@ -0,0 +1,557 @@
+use crate::book::OpeningBook;
+use rust_chess_core::board::PieceColor::White;
+use rust_chess_core::game::Game;
+use rust_chess_core::r#move::Move;
+use rust_chess_core::random::Random;
+use rust_chess_core::search::{Ponder, Search, SearchInfo, SearchLimits};
+use rust_chess_core::skill::{MAX_ELO, MAX_SKILL_LEVEL, MIN_ELO, SkillLevel};
+use rust_chess_core::syzygy::Syzygy;
+use rust_chess_core::tablebase::Tablebases;
+use rust_chess_core::time_manager::TimeManager;
+use rust_chess_core::transposition_table::{DEFAULT_HASH_SIZE_MB, TranspositionTable};
+use std::io;
+use std::io::BufRead;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const ENGINE_NAME: &str = "rust-chess";
+const ENGINE_AUTHOR: &str = "pihaizer";
+
+const DEFAULT_MOVE_OVERHEAD_MS: u64 = 10;
+const MAX_THREADS: usize = 256;
+const MAX_HASH_SIZE_MB: usize = 65536;
+const MAX_MULTI_PV: usize = 256;
+const DEFAULT_ELO: u32 = 1500;
+
+/// Runs the engine using the Universal Chess Interface protocol on stdin/stdout until "quit" is received.
+/// If a book is given, the engine plays from it by default. `skill` is the initial strength of the engine,
+/// the GUI can still change it with the options.
+pub fn run(book: Option<OpeningBook>, skill: SkillLevel) {
+    let mut engine = UciEngine::new();
+    engine.own_book = book.is_some();
+    engine.book = book;
+    engine.skill_level = skill.level();
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        if !engine.handle_command(&line) {
+            break;
+        }
+    }
+    engine.stop_search();
+}
+
+struct UciEngine {
+    game: Game,
+    move_overhead: Duration,
+    book: Option<OpeningBook>,
+    own_book: bool,
+    threads: usize,
+    multi_pv: usize,
+    // "Skill Level" option, used unless the strength is limited by Elo
+    skill_level: u32,
+    limit_strength: bool,
+    elo: u32,
+    // kept between searches of the same game
+    tt: Arc<TranspositionTable>,
+    // loaded from the "TablebasePath" option
+    tablebases: Option<Arc<Tablebases>>,
+    // found in the directories of the "SyzygyPath" option
+    syzygy: Option<Arc<Syzygy>>,
+    search_thread: Option<JoinHandle<()>>,
+    // set to stop the current search
+    stop: Arc<AtomicBool>,
+    // set while the search must not report the best move: in "go infinite" and "go ponder" modes
+    wait_for_stop: Arc<AtomicBool>,
+    // set during "go ponder", turns the ponder search into a normal one after "ponderhit"
+    ponder: Option<Ponder>,
+}
+
+#[derive(Default, Debug)]
+struct GoParams {
+    depth: Option<u32>,
+    nodes: Option<u64>,
+    move_time: Option<u64>,
+    white_time: Option<u64>,
+    black_time: Option<u64>,
+    white_increment: Option<u64>,
+    black_increment: Option<u64>,
+    moves_to_go: Option<u64>,
+    // search for a mate in this many moves
+    mate: Option<u32>,
+    // the only root moves to search
+    search_moves: Vec<Move>,
+    infinite: bool,
+    ponder: bool,
+}
+
+impl UciEngine {
+    fn new() -> UciEngine {
+        UciEngine {
+            game: Game::new(),
+            move_overhead: Duration::from_millis(DEFAULT_MOVE_OVERHEAD_MS),
+            book: None,
+            own_book: false,
+            threads: 1,
+            multi_pv: 1,
+            skill_level: MAX_SKILL_LEVEL,
+            limit_strength: false,
+            elo: DEFAULT_ELO,
+            tt: Arc::new(TranspositionTable::default()),
+            tablebases: None,
+            syzygy: None,
+            search_thread: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            wait_for_stop: Arc::new(AtomicBool::new(false)),
+            ponder: None,
+        }
+    }
+
+    /// Returns false if the engine should quit
+    fn handle_command(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = tokens.split_first() else {
+            return true;
+        };
+
+        match command {
+            "uci" => {
+                println!("id name {}", ENGINE_NAME);
+                println!("id author {}", ENGINE_AUTHOR);
+                println!(
+                    "option name Move Overhead type spin default {} min 0 max 5000",
+                    DEFAULT_MOVE_OVERHEAD_MS
+                );
+                println!("option name Threads type spin default 1 min 1 max {}", MAX_THREADS);
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_HASH_SIZE_MB, MAX_HASH_SIZE_MB
+                );
+                println!("option name MultiPV type spin default 1 min 1 max {}", MAX_MULTI_PV);
+                println!(
+                    "option name Skill Level type spin default {} min 0 max {}",
+                    self.skill_level, MAX_SKILL_LEVEL
+                );
+                println!("option name UCI_LimitStrength type check default false");
+                println!("option name UCI_Elo type spin default {} min {} max {}", DEFAULT_ELO, MIN_ELO, MAX_ELO);
+                println!("option name Ponder type check default false");
+                println!("option name OwnBook type check default {}", self.own_book);
+                println!("option name BookFile type string default <empty>");
+                println!("option name TablebasePath type string default <empty>");
+                println!("option name SyzygyPath type string default <empty>");
+                println!("uciok");
+            }
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                self.stop_search();
+                self.game = Game::new();
+                self.tt.clear();
+            }
+            "position" => {
+                self.stop_search();
+                if let Err(err) = self.set_position(args) {
+                    println!("info string {}", err);
+                }
+            }
+            "go" => {
+                self.stop_search();
+                let (params, warnings) = parse_go(args);
+                for warning in warnings {
+                    println!("info string {}", warning);
+                }
+                self.start_search(params);
+            }
+            "stop" => self.stop_search(),
+            "ponderhit" => self.ponder_hit(),
+            "setoption" => {
+                if let Err(err) = self.set_option(args) {
+                    println!("info string {}", err);
+                }
+            }
+            "quit" => return false,
+            _ => println!("info string Unknown command: {}", command),
+        }
+        true
+    }
+
+    fn set_position(&mut self, args: &[&str]) -> Result<(), String> {
+        let moves_index = args.iter().position(|&arg| arg == "moves");
+        let (position, moves) = match moves_index {
+            Some(i) => (&args[..i], &args[i + 1..]),
+            None => (args, &[][..]),
+        };
+
+        let mut game = match position.split_first() {
+            Some((&"startpos", _)) => Game::new(),
+            Some((&"fen", fen)) => Game::from_fen(&fen.join(" "))?,
+            _ => return Err("Expected startpos or fen".to_string()),
+        };
+        for mv in moves {
+            let parsed = Move::parse_long_notation(mv);
+            if let Err(err) = parsed.and_then(|parsed| game.make_move(&parsed)) {
+                // keep the position reached so far, so that the engine can still respond
+                self.game = game;
+                return Err(format!("Invalid move {}: {}", mv, err));
+            }
+        }
+        self.game = game;
+        Ok(())
+    }
+
+    fn set_option(&mut self, args: &[&str]) -> Result<(), String> {
+        // setoption name <id> [value <x>], where both id and x can contain spaces
+        let value_index = args.iter().position(|&arg| arg == "value");
+        let name = match (args.first(), value_index) {
+            (Some(&"name"), Some(i)) => args[1..i].join(" "),
+            (Some(&"name"), None) => args[1..].join(" "),
+            _ => return Err("Expected setoption name <id> [value <x>]".to_string()),
+        };
+        let value = value_index.map(|i| args[i + 1..].join(" ")).unwrap_or_default();
+
+        match name.to_lowercase().as_str() {
+            "move overhead" => {
+                let millis: u64 = value.parse().map_err(|_| format!("Invalid value {}", value))?;
+                self.move_overhead = Duration::from_millis(millis);
+            }
+            "threads" => {
+                let threads: usize = value.parse().map_err(|_| format!("Invalid value {}", value))?;
+                self.threads = threads.clamp(1, MAX_THREADS);
+            }
+            "multipv" => {
+                let multi_pv: usize = value.parse().map_err(|_| format!("Invalid value {}", value))?;
+                self.multi_pv = multi_pv.clamp(1, MAX_MULTI_PV);
+            }
+            "skill level" => {
+                let level: u32 = value.parse().map_err(|_| format!("Invalid value {}", value))?;
+                self.skill_level = level.min(MAX_SKILL_LEVEL);
+            }
+            "uci_limitstrength" => self.limit_strength = value == "true",
+            "uci_elo" => {
+                let elo: u32 = value.parse().map_err(|_| format!("Invalid value {}", value))?;
+                self.elo = elo.clamp(MIN_ELO, MAX_ELO);
+            }
+            "hash" => {
+                let size_mb: usize = value.parse().map_err(|_| format!("Invalid value {}", value))?;
+                self.stop_search();
+                self.tt = Arc::new(TranspositionTable::new(size_mb.clamp(1, MAX_HASH_SIZE_MB)));
+            }
+            // pondering is controlled by the GUI with "go ponder", nothing to configure here
+            "ponder" => {}
+            "ownbook" => self.own_book = value == "true",
+            "bookfile" => {
+                self.book = match value.as_str() {
+                    "" | "<empty>" => None,
+                    path => Some(OpeningBook::open(path).map_err(|err| format!("Can't open book {}: {}", path, err))?),
+                };
+            }
+            "tablebasepath" => {
+                self.tablebases = match value.as_str() {
+                    "" | "<empty>" => None,
+                    path => Some(Arc::new(Tablebases::load_dir(Path::new(path))?)),
+                };
+            }
+            "syzygypath" => {
+                self.syzygy = match value.as_str() {
+                    "" | "<empty>" => None,
+                    path => {
+                        let syzygy = Syzygy::load_path(path)?;
+                        if syzygy.is_empty() {
+                            return Err(format!("No Syzygy tables in {}", path));
+                        }
+                        Some(Arc::new(syzygy))
+                    }
+                };
+            }
+            _ => return Err(format!("Unknown option {}", name)),
+        }
+        Ok(())
+    }
+
+    fn start_search(&mut self, params: GoParams) {
+        // in analysis and ponder modes the GUI expects the engine to think, and the book knows nothing about mates
+        // or the moves the GUI wants searched
+        if self.own_book
+            && !params.infinite
+            && !params.ponder
+            && params.mate.is_none()
+            && params.search_moves.is_empty()
+            && let Some(book_move) = self.book.as_mut().and_then(|book| book.choose_move(&self.game))
+        {
+            println!("info string book move");
+            println!("bestmove {}", book_move);
+            return;
+        }
+
+        // the time limits of a ponder search only start after "ponderhit"
+        self.ponder = if params.ponder { Some(Ponder::new()) } else { None };
+        let skill = self.skill();
+        // a mate in N moves is at most 2N - 1 plies deep
+        let mate_depth = params.mate.map(|moves| (2 * moves).saturating_sub(1).max(1));
+        let limits = skill.limit_search(SearchLimits {
+            depth: params.depth.into_iter().chain(mate_depth).min(),
+            nodes: params.nodes,
+            time_manager: self.time_manager(&params),
+            ponder: self.ponder.clone(),
+        });
+
+        self.stop = Arc::new(AtomicBool::new(false));
+        self.wait_for_stop = Arc::new(AtomicBool::new(params.infinite || params.ponder));
+
+        let game = self.game.clone();
+        let stop = self.stop.clone();
+        let wait_for_stop = self.wait_for_stop.clone();
+        let threads = self.threads;
+        let search_moves = params.search_moves;
+        let multi_pv = skill.multi_pv(self.multi_pv);
+        let tt = self.tt.clone();
+        let tablebases = self.tablebases.clone();
+        let syzygy = self.syzygy.clone();
+        self.search_thread = Some(thread::spawn(move || {
+            // a bug in the search must not take the engine down, the GUI still gets a move
+            let search_stop = stop.clone();
+            let searched = panic::catch_unwind(AssertUnwindSafe(|| {
+                let mut search = Search::new(limits, search_stop);
+                search.set_threads(threads);
+                search.set_multi_pv(multi_pv);
+                search.set_search_moves(search_moves);
+                search.set_transposition_table(tt);
+                if let Some(tablebases) = tablebases {
+                    search.set_tablebases(tablebases);
+                }
+                if let Some(syzygy) = syzygy {
+                    search.set_syzygy(syzygy);
+                }
+                search.run(&game, print_info)
+            }));
+
+            // in infinite and ponder modes the best move can only be sent after "stop" or "ponderhit"
+            while wait_for_stop.load(Ordering::Relaxed) && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            let (best_move, ponder_move) = match searched {
+                // a weakened engine doesn't always play the best line
+                Ok(result) => match skill.choose_line(&result.lines, &mut Random::from_time()) {
+                    Some(line) => (Some(line.mv), line.pv.get(1).copied()),
+                    None => (result.best_move, result.ponder_move),
+                },
+                Err(_) => {
+                    println!("info string The search failed, playing the first legal move");
+                    (game.possible_moves().first().copied(), None)
+                }
+            };
+            match (best_move, ponder_move) {
+                (Some(best_move), Some(ponder_move)) => {
+                    println!("bestmove {} ponder {}", best_move, ponder_move)
+                }
+                (Some(best_move), None) => println!("bestmove {}", best_move),
+                // no legal moves
+                (None, _) => println!("bestmove 0000"),
+            }
+        }));
+    }
+
+    fn skill(&self) -> SkillLevel {
+        if self.limit_strength {
+            SkillLevel::from_elo(self.elo)
+        } else {
+            SkillLevel::new(self.skill_level)
+        }
+    }
+
+    fn stop_search(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(search_thread) = self.search_thread.take()
+            && search_thread.join().is_err()
+        {
+            println!("info string The search thread failed");
+        }
+    }
+
+    /// The opponent played the expected move, so the ponder search continues as a normal search
+    /// with everything it has found so far
+    fn ponder_hit(&mut self) {
+        if let Some(ponder) = self.ponder.take() {
+            ponder.ponder_hit();
+        }
+        self.wait_for_stop.store(false, Ordering::Relaxed);
+    }
+
+    /// Decides how long to think on the current move, None if the search is not limited by time
+    fn time_manager(&self, params: &GoParams) -> Option<TimeManager> {
+        if params.infinite {
+            return None;
+        }
+        if let Some(move_time) = params.move_time {
+            return Some(TimeManager::fixed(
+                Duration::from_millis(move_time).saturating_sub(self.move_overhead),
+            ));
+        }
+
+        let (time, increment) = if self.game.turn() == White {
+            (params.white_time?, params.white_increment.unwrap_or(0))
+        } else {
+            (params.black_time?, params.black_increment.unwrap_or(0))
+        };
+        Some(TimeManager::new(
+            Duration::from_millis(time),
+            Duration::from_millis(increment),
+            params.moves_to_go.map(|moves| moves as u32),
+            self.move_overhead,
+        ))
+    }
+}
+
+/// Parses the parameters of "go". Unknown parameters and invalid values are skipped and returned as warnings,
+/// so that a search is always started and the GUI always gets its "bestmove".
+fn parse_go(args: &[&str]) -> (GoParams, Vec<String>) {
+    let mut params = GoParams::default();
+    let mut warnings = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        let arg = args[i];
+        let value = args.get(i + 1);
+        let mut parse_value = || -> Result<u64, String> {
+            let value = value.ok_or(format!("Missing value for {}", arg))?;
+            i += 1;
+            // the time can be negative if the GUI lets the clock run a bit past zero
+            let value: i64 = value.parse().map_err(|_| format!("Invalid value for {}: {}", arg, value))?;
+            Ok(value.max(0) as u64)
+        };
+        let parsed = match arg {
+            "depth" => parse_value().map(|value| params.depth = Some(value as u32)),
+            "nodes" => parse_value().map(|value| params.nodes = Some(value)),
+            "mate" => parse_value().map(|value| params.mate = Some(value as u32)),
+            "movetime" => parse_value().map(|value| params.move_time = Some(value)),
+            "wtime" => parse_value().map(|value| params.white_time = Some(value)),
+            "btime" => parse_value().map(|value| params.black_time = Some(value)),
+            "winc" => parse_value().map(|value| params.white_increment = Some(value)),
+            "binc" => parse_value().map(|value| params.black_increment = Some(value)),
+            "movestogo" => parse_value().map(|value| params.moves_to_go = Some(value)),
+            "infinite" => {
+                params.infinite = true;
+                Ok(())
+            }
+            "ponder" => {
+                params.ponder = true;
+                Ok(())
+            }
+            // the moves go on until the next token that isn't a move
+            "searchmoves" => {
+                while let Some(mv) = args.get(i + 1).and_then(|mv| Move::parse_long_notation(mv).ok()) {
+                    params.search_moves.push(mv);
+                    i += 1;
+                }
+                Ok(())
+            }
+            _ => Err(format!("Unknown go parameter {}", arg)),
+        };
+        if let Err(warning) = parsed {
+            warnings.push(warning);
+        }
+        i += 1;
+    }
+    (params, warnings)
+}
+
+fn print_info(info: &SearchInfo) {
+    let pv: Vec<String> = info.pv.iter().map(|mv| mv.to_string()).collect();
+    println!(
+        "info depth {} seldepth {} multipv {} score {} nodes {} nps {} hashfull {} time {} pv {}",
+        info.depth,
+        info.seldepth,
+        info.multi_pv,
+        info.score,
+        info.nodes,
+        info.nps(),
+        info.hashfull,
+        info.time.as_millis(),
+        pv.join(" ")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_go_parameters() {
+        let args = ["wtime", "1000", "btime", "-20", "winc", "10", "movestogo", "5", "ponder"];
+        let (params, warnings) = parse_go(&args);
+        assert!(warnings.is_empty());
+        assert_eq!(params.white_time, Some(1000));
+        // the clock may run past zero
+        assert_eq!(params.black_time, Some(0));
+        assert_eq!(params.white_increment, Some(10));
+        assert_eq!(params.moves_to_go, Some(5));
+        assert!(params.ponder && !params.infinite);
+
+        let (params, warnings) = parse_go(&["searchmoves", "e2e4", "d2d4", "mate", "3"]);
+        assert!(warnings.is_empty());
+        assert_eq!(params.search_moves, [Move::from_long_notation("e2e4"), Move::from_long_notation("d2d4")]);
+        assert_eq!(params.mate, Some(3));
+    }
+
+    #[test]
+    fn skips_unknown_go_parameters() {
+        let (params, warnings) = parse_go(&["depth", "x", "bogus", "nodes", "100", "movetime"]);
+        assert_eq!(
+            warnings,
+            ["Invalid value for depth: x", "Unknown go parameter bogus", "Missing value for movetime"]
+        );
+        assert_eq!(params.depth, None);
+        assert_eq!(params.nodes, Some(100));
+    }
+
+    #[test]
+    fn go_always_starts_a_search() {
+        let mut engine = UciEngine::new();
+        for command in ["go mate 1", "go searchmoves e2e4 d2d4 depth 1", "go wtime 100 foo"] {
+            assert!(engine.handle_command(command));
+            assert!(engine.search_thread.is_some(), "{}", command);
+            engine.stop_search();
+        }
+    }
+
+    #[test]
+    fn sets_positions() -> Result<(), String> {
+        let mut engine = UciEngine::new();
+        engine.set_position(&["startpos", "moves", "e2e4", "c7c5", "g1f3"])?;
+        assert_eq!(engine.game.to_fen(), "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1";
+        let mut args: Vec<&str> = fen.split_whitespace().collect();
+        args.insert(0, "fen");
+        engine.set_position(&args)?;
+        assert_eq!(engine.game.to_fen(), fen);
+
+        // the moves up to the invalid one are kept
+        let err = engine.set_position(&["startpos", "moves", "e2e4", "e2e4", "d7d5"]).unwrap_err();
+        assert!(err.starts_with("Invalid move e2e4"), "{}", err);
+        assert_eq!(engine.game.history().moves().len(), 1);
+
+        assert!(engine.set_position(&["fen", "7k/8/6KQ/8/8/8/8/8", "w", "-", "-", "0", "1"]).is_err());
+        assert!(engine.set_position(&["moves", "e2e4"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sets_syzygy_path() -> Result<(), String> {
+        let mut engine = UciEngine::new();
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../rust-chess-core/tests/syzygy");
+        engine.set_option(&["name", "SyzygyPath", "value", dir])?;
+        assert_eq!(engine.syzygy.as_ref().map(|syzygy| syzygy.max_pieces()), Some(4));
+
+        // a directory without tables is an error
+        let empty = concat!(env!("CARGO_MANIFEST_DIR"), "/src");
+        assert!(engine.set_option(&["name", "SyzygyPath", "value", empty]).is_err());
+        engine.set_option(&["name", "SyzygyPath", "value", "<empty>"])?;
+        assert!(engine.syzygy.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use rust_chess_core::pgn::parse_pgn;
+
+const USAGE: &str = "Usage: rust-chess-cli validate <file.pgn>";
+
+/// Checks every game of a PGN file and prints its problems as "file:line:column: game N: message".
+/// `args` are the arguments after "validate". The error is the summary if any problems were found.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let [path] = args else {
+        return Err(USAGE.to_string());
+    };
+    if path.starts_with("--") {
+        return Err(format!("Unknown argument {}\n{}", path, USAGE));
+    }
+    let text = std::fs::read_to_string(path).map_err(|err| format!("Can't read {}: {}", path, err))?;
+    let games = parse_pgn(&text);
+    if games.is_empty() {
+        return Err(format!("No games in {}", path));
+    }
+
+    let mut problems = 0;
+    let mut broken_games = 0;
+    for (i, game) in games.iter().enumerate() {
+        let errors = game.validate();
+        for err in &errors {
+            println!("{}:{}:{}: game {}: {}", path, err.line, err.column, i + 1, err.message);
+        }
+        problems += errors.len();
+        broken_games += usize::from(!errors.is_empty());
+    }
+    match problems {
+        0 => {
+            println!("{}: {} games, no problems found", path, games.len());
+            Ok(())
+        }
+        _ => Err(format!("{}: {} problems in {} of {} games", path, problems, broken_games, games.len())),
+    }
+}
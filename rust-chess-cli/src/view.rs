@@ -0,0 +1,352 @@
+use crate::{display_options, option_value};
+use rust_chess_core::board::PieceColor::{Black, White};
+use rust_chess_core::display::{DisplayOptions, render};
+use rust_chess_core::game::Game;
+use rust_chess_core::pgn::{PgnError, PgnGame, parse_pgn};
+use std::io;
+use std::io::Write;
+
+const USAGE: &str = "Usage: rust-chess-cli view <file.pgn> [--game N] [--board letters|unicode] [--ansi]";
+
+const HELP: &str = "\
+Commands, most of them also work by their first letter:
+  next (or enter)   show the next move
+  back              show the previous move
+  <number>          jump to the position after that many plies, 0 for the start
+  start, end        jump to the start or the end of the game
+  games             list the games of the file
+  game <N>          open the N-th game of the file
+  tags              show all tags of the game
+  flip              turn the board around
+  help              show this help
+  quit              leave the viewer";
+
+struct Viewer {
+    games: Vec<PgnGame>,
+    // index of the open game
+    index: usize,
+    // positions of the open game, from the start up to the last move that could be played
+    positions: Vec<Game>,
+    // why the game couldn't be replayed to the end
+    replay_error: Option<PgnError>,
+    ply: usize,
+    display: DisplayOptions,
+}
+
+// what the arguments of "view" ask for
+struct ViewOptions<'a> {
+    path: &'a str,
+    // 1-based number of the game opened first
+    game: usize,
+    display: DisplayOptions,
+}
+
+/// Steps through the games of a PGN file. `args` are the arguments after "view".
+pub fn run(args: &[String]) -> Result<(), String> {
+    let ViewOptions { path, game: number, display } = parse_args(args)?;
+    let text = std::fs::read_to_string(path).map_err(|err| format!("Can't read {}: {}", path, err))?;
+    let games = parse_pgn(&text);
+    if games.is_empty() {
+        return Err(format!("No games in {}", path));
+    }
+
+    let mut viewer = Viewer {
+        games,
+        index: 0,
+        positions: Vec::new(),
+        replay_error: None,
+        ply: 0,
+        display,
+    };
+    viewer.list_games();
+    viewer.open(number)?;
+    viewer.show();
+
+    let mut input = String::new();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+        input.clear();
+        match io::stdin().read_line(&mut input) {
+            Ok(0) => return Ok(()),
+            Ok(_) => {}
+            Err(err) => return Err(format!("Error reading input: {}", err)),
+        }
+        match viewer.execute(input.trim()) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(err) => println!("Error: {}", err),
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<ViewOptions<'_>, String> {
+    let mut path = None;
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--game" | "--board" => {
+                args_iter.next();
+            }
+            "--ansi" => {}
+            _ if arg.starts_with("--") || path.is_some() => {
+                return Err(format!("Unknown argument {}\n{}", arg, USAGE));
+            }
+            _ => path = Some(arg),
+        }
+    }
+    let path = path.ok_or(USAGE)?;
+    let display = display_options(args).map_err(|err| format!("{}\n{}", err, USAGE))?;
+    let game = match option_value(args, "--game") {
+        Some(number) => number.parse().map_err(|_| format!("Invalid game number {}\n{}", number, USAGE))?,
+        None => 1,
+    };
+    Ok(ViewOptions { path, game, display })
+}
+
+impl Viewer {
+    /// Runs a command, false to quit
+    fn execute(&mut self, line: &str) -> Result<bool, String> {
+        let (word, rest) = match line.split_once(char::is_whitespace) {
+            Some((word, rest)) => (word, rest.trim()),
+            None => (line, ""),
+        };
+        if let Ok(ply) = word.parse::<usize>() {
+            if ply >= self.positions.len() {
+                return Err(format!("The game has positions up to ply {}", self.positions.len() - 1));
+            }
+            self.ply = ply;
+            self.show();
+            return Ok(true);
+        }
+        match word.to_lowercase().as_str() {
+            "" | "n" | "next" => {
+                if self.ply + 1 >= self.positions.len() {
+                    return Err("This is the end of the game".to_string());
+                }
+                self.ply += 1;
+                self.show();
+            }
+            "b" | "back" => {
+                if self.ply == 0 {
+                    return Err("This is the start of the game".to_string());
+                }
+                self.ply -= 1;
+                self.show();
+            }
+            "s" | "start" => {
+                self.ply = 0;
+                self.show();
+            }
+            "e" | "end" => {
+                self.ply = self.positions.len() - 1;
+                self.show();
+            }
+            "l" | "games" => self.list_games(),
+            "g" | "game" => {
+                let number = rest.parse().map_err(|_| "Usage: game <N>".to_string())?;
+                self.open(number)?;
+                self.show();
+            }
+            "t" | "tags" => {
+                for tag in &self.games[self.index].tags {
+                    println!("[{} \"{}\"]", tag.name, tag.value);
+                }
+            }
+            "f" | "flip" => {
+                self.display.perspective = self.display.perspective.opposite();
+                self.show();
+            }
+            "h" | "help" | "?" => println!("{}", HELP),
+            "q" | "quit" | "exit" => return Ok(false),
+            _ => return Err(format!("Unknown command '{}', type 'help' for the list of commands", line)),
+        }
+        Ok(true)
+    }
+
+    fn list_games(&self) {
+        for (i, game) in self.games.iter().enumerate() {
+            let tag = |name: &str| game.tag(name).unwrap_or("?");
+            println!(
+                "{:>4}. {} - {}, {} ({}, {}), {} plies",
+                i + 1,
+                tag("White"),
+                tag("Black"),
+                tag("Result"),
+                tag("Event"),
+                tag("Date"),
+                game.moves.len()
+            );
+        }
+    }
+
+    /// Opens the game with the 1-based `number` at its start, replaying the moves up to the first one that fails
+    fn open(&mut self, number: usize) -> Result<(), String> {
+        if number == 0 || number > self.games.len() {
+            return Err(format!("Invalid game number {}, the file has {} games", number, self.games.len()));
+        }
+        let game = &self.games[number - 1];
+        let mut position = game.initial_game().map_err(|err| format!("Can't open game {}: {}", number, err))?;
+        let mut positions = vec![position.clone()];
+        let mut replay_error = None;
+        for pgn_move in &game.moves {
+            let played = position
+                .parse_short_notation(&pgn_move.san)
+                .and_then(|mv| position.make_move(&mv).map_err(|err| err.to_string()));
+            if let Err(err) = played {
+                replay_error = Some(PgnError {
+                    message: format!("Can't play {}: {}", pgn_move.san, err),
+                    line: pgn_move.line,
+                    column: pgn_move.column,
+                });
+                break;
+            }
+            positions.push(position.clone());
+        }
+
+        self.index = number - 1;
+        self.positions = positions;
+        self.replay_error = replay_error;
+        self.ply = 0;
+        let tag = |name: &str| game.tag(name).unwrap_or("?");
+        println!("\nGame {}: {} - {}, {}", number, tag("White"), tag("Black"), tag("Result"));
+        if let Some(err) = &self.replay_error {
+            println!("The game stops after ply {}, line {}", self.positions.len() - 1, err);
+        }
+        Ok(())
+    }
+
+    /// Prints the board and the move that led to it with its comment
+    fn show(&self) {
+        let game = &self.games[self.index];
+        let position = &self.positions[self.ply];
+        let options = self.display.clone().highlight_game(position);
+        print!("{}", render(position.board(), &options));
+
+        let last_ply = self.positions.len() - 1;
+        let comment = match self.ply {
+            0 => {
+                println!("Start position, {} plies", last_ply);
+                &game.initial_comment
+            }
+            ply => {
+                let before = &self.positions[ply - 1];
+                let number = match before.turn() {
+                    White => format!("{}.", before.fullmove_number()),
+                    Black => format!("{}...", before.fullmove_number()),
+                };
+                let pgn_move = &game.moves[ply - 1];
+                println!("Ply {} of {}: {} {}", ply, last_ply, number, pgn_move.annotated_san());
+                &pgn_move.comment
+            }
+        };
+        if let Some(comment) = comment {
+            println!("{{{}}}", comment);
+        }
+        if self.ply == last_ply {
+            match (&self.replay_error, &game.result) {
+                (Some(_), _) => println!("No further moves can be played"),
+                (None, Some(result)) => println!("End of the game: {}", result),
+                (None, None) => println!("End of the game"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_chess_core::game::Termination;
+
+    const PGN: &str = r#"[White "Anna"]
+[Black "Ben"]
+[Result "1-0"]
+
+{Start} 1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0
+
+[White "Carl"]
+[Result "*"]
+
+1. d4 d5 2. Ke3 *
+"#;
+
+    fn viewer() -> Viewer {
+        let mut viewer = Viewer {
+            games: parse_pgn(PGN),
+            index: 0,
+            positions: Vec::new(),
+            replay_error: None,
+            ply: 0,
+            display: DisplayOptions::default(),
+        };
+        viewer.open(1).unwrap();
+        viewer
+    }
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parses_view_arguments() {
+        let args = args("games.pgn --game 2 --board unicode --ansi");
+        let options = parse_args(&args).unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!((options.path, options.game), ("games.pgn", 2));
+        assert!(options.display.colors);
+        assert_eq!(parse_args(&self::args("games.pgn")).ok().map(|options| options.game), Some(1));
+
+        let error = |line| parse_args(&self::args(line)).err().unwrap_or_default();
+        assert_eq!(error(""), USAGE);
+        assert!(error("a.pgn b.pgn").starts_with("Unknown argument b.pgn\n"));
+        assert!(error("a.pgn --moves").starts_with("Unknown argument --moves\n"));
+        assert!(error("a.pgn --game x").starts_with("Invalid game number x\n"));
+        assert!(error("a.pgn --board ascii").starts_with("Invalid board style ascii"));
+    }
+
+    #[test]
+    fn steps_through_a_game() {
+        let mut viewer = viewer();
+        assert_eq!(viewer.positions.len(), 8);
+        assert_eq!(viewer.execute("b"), Err("This is the start of the game".to_string()));
+        assert_eq!(viewer.execute(""), Ok(true));
+        assert_eq!(viewer.execute("next"), Ok(true));
+        assert_eq!(viewer.ply, 2);
+        assert_eq!(viewer.execute("back"), Ok(true));
+        assert_eq!(viewer.ply, 1);
+        assert_eq!(viewer.execute("End"), Ok(true));
+        assert_eq!(viewer.ply, 7);
+        assert_eq!(viewer.positions[7].result().map(|result| result.termination), Some(Termination::Checkmate));
+        assert_eq!(viewer.execute("n"), Err("This is the end of the game".to_string()));
+        assert_eq!(viewer.execute("3"), Ok(true));
+        assert_eq!(viewer.ply, 3);
+        assert_eq!(viewer.execute("8"), Err("The game has positions up to ply 7".to_string()));
+        assert_eq!(viewer.execute("s"), Ok(true));
+        assert_eq!(viewer.ply, 0);
+
+        assert_eq!(viewer.execute("flip"), Ok(true));
+        assert_eq!(viewer.display.perspective, Black);
+        assert_eq!(viewer.execute("tags"), Ok(true));
+        assert_eq!(viewer.execute("q"), Ok(false));
+        assert_eq!(
+            viewer.execute("jump 3"),
+            Err("Unknown command 'jump 3', type 'help' for the list of commands".to_string())
+        );
+    }
+
+    #[test]
+    fn opens_other_games() {
+        let mut viewer = viewer();
+        viewer.execute("e").unwrap();
+        assert_eq!(viewer.execute("game 2"), Ok(true));
+        assert_eq!((viewer.index, viewer.ply), (1, 0));
+        // the illegal king move stops the replay
+        assert_eq!(viewer.positions.len(), 3);
+        let error = viewer.replay_error.clone().unwrap();
+        assert_eq!((error.line, error.column), (10, 13));
+        assert!(error.message.starts_with("Can't play Ke3"));
+
+        assert_eq!(viewer.execute("game 3"), Err("Invalid game number 3, the file has 2 games".to_string()));
+        assert_eq!(viewer.execute("g"), Err("Usage: game <N>".to_string()));
+        assert_eq!(viewer.index, 1);
+    }
+}
@@ -0,0 +1,457 @@
+use crate::book::OpeningBook;
+use rust_chess_core::board::PieceColor;
+use rust_chess_core::game::{Game, GameResult, Termination};
+use rust_chess_core::r#move::Move;
+use rust_chess_core::random::Random;
+use rust_chess_core::search::{Score, Search, SearchInfo, SearchLimits};
+use rust_chess_core::skill::SkillLevel;
+use rust_chess_core::time_manager::TimeManager;
+use rust_chess_core::transposition_table::TranspositionTable;
+use std::io;
+use std::io::BufRead;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const ENGINE_NAME: &str = "rust-chess";
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+
+// xboard reports mate in N moves as 100000 + N
+const XBOARD_MATE_SCORE: i32 = 100000;
+
+/// Runs the engine using the Chess Engine Communication Protocol (xboard/winboard) on stdin/stdout
+/// until "quit" is received. If a book is given, the engine plays from it while the position is in the book.
+pub fn run(book: Option<OpeningBook>, skill: SkillLevel) {
+    let mut engine = XboardEngine::new();
+    engine.book = book;
+    engine.skill = skill;
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        if !engine.handle_command(&line) {
+            break;
+        }
+    }
+    engine.stop_search(true);
+}
+
+struct XboardEngine {
+    // shared with the search thread, which makes the engine's move once the search is done
+    game: Arc<Mutex<Game>>,
+    // None in force mode, when the engine only tracks the moves of both sides
+    engine_color: Option<PieceColor>,
+    time_control: TimeControl,
+    max_depth: Option<u32>,
+    engine_time: Duration,
+    opponent_time: Duration,
+    post: bool,
+    book: Option<OpeningBook>,
+    threads: usize,
+    skill: SkillLevel,
+    // kept between searches of the same game
+    tt: Arc<TranspositionTable>,
+    search_thread: Option<JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+    // set if the search is interrupted and its move must not be played
+    discard_move: Arc<AtomicBool>,
+}
+
+struct TimeControl {
+    // number of moves per time control session, None if the whole game is played in one session
+    moves_per_session: Option<u32>,
+    increment: Duration,
+    // set by "st", overrides the clock
+    time_per_move: Option<Duration>,
+}
+
+impl XboardEngine {
+    fn new() -> XboardEngine {
+        XboardEngine {
+            game: Arc::new(Mutex::new(Game::new())),
+            engine_color: Some(PieceColor::Black),
+            time_control: TimeControl {
+                moves_per_session: None,
+                increment: Duration::ZERO,
+                time_per_move: None,
+            },
+            max_depth: None,
+            engine_time: Duration::from_secs(300),
+            opponent_time: Duration::from_secs(300),
+            post: false,
+            book: None,
+            threads: 1,
+            skill: SkillLevel::full_strength(),
+            tt: Arc::new(TranspositionTable::default()),
+            search_thread: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            discard_move: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Returns false if the engine should quit
+    fn handle_command(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = tokens.split_first() else {
+            return true;
+        };
+
+        let result = match command {
+            "xboard" => Ok(()),
+            "protover" => {
+                println!(
+                    "feature myname=\"{}\" ping=1 setboard=1 usermove=1 time=1 draw=0 sigint=0 sigterm=0 \
+                     reuse=1 analyze=0 colors=0 san=0 playother=0 smp=1 memory=1 done=1",
+                    ENGINE_NAME
+                );
+                Ok(())
+            }
+            "new" => {
+                self.stop_search(true);
+                *self.game.lock().unwrap() = Game::new();
+                self.engine_color = Some(PieceColor::Black);
+                self.max_depth = None;
+                self.tt.clear();
+                Ok(())
+            }
+            "force" => {
+                self.stop_search(true);
+                self.engine_color = None;
+                Ok(())
+            }
+            "go" => {
+                self.stop_search(true);
+                self.engine_color = Some(self.game.lock().unwrap().turn());
+                self.start_thinking();
+                Ok(())
+            }
+            "usermove" => match args.first() {
+                Some(mv) => self.user_move(mv),
+                None => Err("Missing move".to_string()),
+            },
+            // move now
+            "?" => {
+                self.stop_search(false);
+                Ok(())
+            }
+            "setboard" => {
+                self.stop_search(true);
+                match Game::from_fen(&args.join(" ")) {
+                    Ok(game) => {
+                        *self.game.lock().unwrap() = game;
+                        Ok(())
+                    }
+                    Err(err) => {
+                        println!("tellusererror Illegal position: {}", err);
+                        Ok(())
+                    }
+                }
+            }
+            "undo" => self.take_back(1),
+            "remove" => self.take_back(2),
+            "level" => self.set_level(args),
+            "st" => parse_arg::<u64>(args).map(|seconds| {
+                self.time_control.time_per_move = Some(Duration::from_secs(seconds));
+            }),
+            "sd" => parse_arg::<u32>(args).map(|depth| self.max_depth = Some(depth)),
+            "cores" => parse_arg::<usize>(args).map(|cores| self.threads = cores.max(1)),
+            "memory" => parse_arg::<usize>(args).map(|size_mb| {
+                self.stop_search(true);
+                self.tt = Arc::new(TranspositionTable::new(size_mb));
+            }),
+            "time" => parse_arg::<u64>(args).map(|centis| self.engine_time = Duration::from_millis(centis * 10)),
+            "otim" => parse_arg::<u64>(args).map(|centis| self.opponent_time = Duration::from_millis(centis * 10)),
+            "result" => {
+                self.stop_search(true);
+                self.engine_color = None;
+                Ok(())
+            }
+            "post" => {
+                self.post = true;
+                Ok(())
+            }
+            "nopost" => {
+                self.post = false;
+                Ok(())
+            }
+            "ping" => {
+                println!("pong {}", args.first().unwrap_or(&""));
+                Ok(())
+            }
+            "quit" => return false,
+            // commands that don't affect this engine
+            "accepted" | "rejected" | "random" | "hard" | "easy" | "computer" | "name" | "rating" | "draw"
+            | "white" | "black" => Ok(()),
+            // older interfaces send moves without "usermove"
+            other if Move::parse_long_notation(other).is_ok() => self.user_move(other),
+            _ => {
+                println!("Error (unknown command): {}", command);
+                Ok(())
+            }
+        };
+
+        if let Err(err) = result {
+            println!("Error ({}): {}", err, line);
+        }
+        true
+    }
+
+    fn user_move(&mut self, notation: &str) -> Result<(), String> {
+        self.stop_search(true);
+        let mv = Move::parse_long_notation(notation);
+        let mut game = self.game.lock().unwrap();
+        if mv.and_then(|mv| game.make_move(&mv)).is_err() {
+            println!("Illegal move: {}", notation);
+            return Ok(());
+        }
+        let engine_to_move = self.engine_color == Some(game.turn()) && game.result().is_none();
+        drop(game);
+
+        if engine_to_move {
+            self.start_thinking();
+        }
+        Ok(())
+    }
+
+    fn take_back(&mut self, plies: usize) -> Result<(), String> {
+        self.stop_search(true);
+        let mut game = self.game.lock().unwrap();
+        if game.history().moves().len() < plies {
+            return Err("No moves to take back".to_string());
+        }
+        for _ in 0..plies {
+            game.undo_move();
+        }
+        Ok(())
+    }
+
+    /// level MPS BASE INC, where BASE is in minutes or minutes:seconds and INC is in seconds
+    fn set_level(&mut self, args: &[&str]) -> Result<(), String> {
+        let [moves_per_session, base, increment] = args else {
+            return Err("Expected level MPS BASE INC".to_string());
+        };
+        let moves_per_session: u32 = moves_per_session.parse().map_err(|_| "Invalid MPS")?;
+        let base = match base.split_once(':') {
+            Some((minutes, seconds)) => {
+                let minutes: u64 = minutes.parse().map_err(|_| "Invalid BASE")?;
+                let seconds: u64 = seconds.parse().map_err(|_| "Invalid BASE")?;
+                Duration::from_secs(minutes * 60 + seconds)
+            }
+            None => Duration::from_secs(base.parse::<u64>().map_err(|_| "Invalid BASE")? * 60),
+        };
+        let increment: f64 = increment.parse().map_err(|_| "Invalid INC")?;
+
+        self.time_control = TimeControl {
+            moves_per_session: if moves_per_session == 0 { None } else { Some(moves_per_session) },
+            increment: Duration::from_secs_f64(increment),
+            time_per_move: None,
+        };
+        self.engine_time = base;
+        self.opponent_time = base;
+        Ok(())
+    }
+
+    fn search_limits(&self, game: &Game) -> SearchLimits {
+        let time_manager = match self.time_control.time_per_move {
+            Some(time_per_move) => TimeManager::fixed(time_per_move.saturating_sub(MOVE_OVERHEAD)),
+            None => {
+                let moves_to_go = self.time_control.moves_per_session.map(|moves_per_session| {
+                    moves_per_session - (game.fullmove_number() - 1) % moves_per_session
+                });
+                TimeManager::new(self.engine_time, self.time_control.increment, moves_to_go, MOVE_OVERHEAD)
+            }
+        };
+        self.skill.limit_search(SearchLimits {
+            depth: self.max_depth,
+            nodes: None,
+            time_manager: Some(time_manager),
+            ponder: None,
+        })
+    }
+
+    fn start_thinking(&mut self) {
+        let game = self.game.lock().unwrap().clone();
+        if game.result().is_some() {
+            return;
+        }
+        if let Some(book_move) = self.book.as_mut().and_then(|book| book.choose_move(&game)) {
+            let mut game = self.game.lock().unwrap();
+            game.make_move(&book_move).expect("Book moves are validated");
+            println!("move {}", book_move);
+            return;
+        }
+        let limits = self.search_limits(&game);
+
+        self.stop = Arc::new(AtomicBool::new(false));
+        self.discard_move = Arc::new(AtomicBool::new(false));
+        let stop = self.stop.clone();
+        let discard_move = self.discard_move.clone();
+        let shared_game = self.game.clone();
+        let post = self.post;
+        let threads = self.threads;
+        let skill = self.skill;
+        let tt = self.tt.clone();
+        self.search_thread = Some(thread::spawn(move || {
+            // a bug in the search must not take the engine down, the game goes on without a move
+            let searched = panic::catch_unwind(AssertUnwindSafe(|| {
+                let mut search = Search::new(limits, stop);
+                search.set_threads(threads);
+                search.set_multi_pv(skill.multi_pv(1));
+                search.set_transposition_table(tt);
+                search.run(&game, |info| {
+                    // only the best line, the others are searched for the weakened engine to choose from
+                    if post && info.multi_pv == 1 {
+                        print_thinking(info)
+                    }
+                })
+            }));
+            let Ok(result) = searched else {
+                println!("tellusererror The search failed");
+                return;
+            };
+
+            if discard_move.load(Ordering::Relaxed) {
+                return;
+            }
+            let chosen_move = skill.choose_move(&result.lines, &mut Random::from_time());
+            let Some(best_move) = chosen_move.or(result.best_move) else {
+                return;
+            };
+            let mut game = shared_game.lock().unwrap();
+            game.make_move(&best_move).expect("Search should return a legal move");
+            println!("move {}", best_move);
+            if let Some(result) = game.result() {
+                print_result(result);
+            }
+        }));
+    }
+
+    /// Stops the current search. If `discard_move` is false, the engine still plays the best move found so far.
+    fn stop_search(&mut self, discard_move: bool) {
+        if discard_move {
+            self.discard_move.store(true, Ordering::Relaxed);
+        }
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(search_thread) = self.search_thread.take()
+            && search_thread.join().is_err()
+        {
+            println!("tellusererror The search failed");
+        }
+    }
+}
+
+fn parse_arg<T: std::str::FromStr>(args: &[&str]) -> Result<T, String> {
+    let Some(arg) = args.first() else {
+        return Err("Missing argument".to_string());
+    };
+    arg.parse().map_err(|_| format!("Invalid argument {}", arg))
+}
+
+/// Thinking output: ply, score in centipawns, time in centiseconds, nodes and the principal variation
+fn print_thinking(info: &SearchInfo) {
+    let score = match info.score {
+        Score::Centipawns(cp) => cp,
+        Score::Mate(moves) if moves > 0 => XBOARD_MATE_SCORE + moves,
+        Score::Mate(moves) => -XBOARD_MATE_SCORE + moves,
+    };
+    let pv: Vec<String> = info.pv.iter().map(|mv| mv.to_string()).collect();
+    println!(
+        "{} {} {} {} {}",
+        info.depth,
+        score,
+        info.time.as_millis() / 10,
+        info.nodes,
+        pv.join(" ")
+    );
+}
+
+fn print_result(result: &GameResult) {
+    match (result.winner, result.termination) {
+        (Some(PieceColor::White), Termination::Timeout) => println!("1-0 {{Black forfeits on time}}"),
+        (Some(PieceColor::Black), Termination::Timeout) => println!("0-1 {{White forfeits on time}}"),
+        (Some(PieceColor::White), _) => println!("1-0 {{White mates}}"),
+        (Some(PieceColor::Black), _) => println!("0-1 {{Black mates}}"),
+        (None, Termination::Timeout) => println!("1/2-1/2 {{Time forfeit with insufficient material}}"),
+        (None, _) => println!("1/2-1/2 {{Stalemate}}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_time_commands() {
+        let mut engine = XboardEngine::new();
+        assert!(engine.handle_command("level 40 5 2.5"));
+        assert_eq!(engine.time_control.moves_per_session, Some(40));
+        assert_eq!(engine.time_control.increment, Duration::from_millis(2500));
+        assert_eq!(engine.engine_time, Duration::from_secs(300));
+
+        engine.handle_command("level 0 2:30 0");
+        assert_eq!(engine.time_control.moves_per_session, None);
+        assert_eq!(engine.opponent_time, Duration::from_secs(150));
+        assert!(engine.set_level(&["40", "5"]).is_err());
+        assert!(engine.set_level(&["40", "x:10", "0"]).is_err());
+
+        engine.handle_command("time 1234");
+        engine.handle_command("otim 500");
+        assert_eq!(engine.engine_time, Duration::from_millis(12340));
+        assert_eq!(engine.opponent_time, Duration::from_secs(5));
+        engine.handle_command("st 7");
+        engine.handle_command("sd 4");
+        engine.handle_command("cores 0");
+        assert_eq!(engine.time_control.time_per_move, Some(Duration::from_secs(7)));
+        assert_eq!(engine.max_depth, Some(4));
+        assert_eq!(engine.threads, 1);
+    }
+
+    #[test]
+    fn tracks_moves_in_force_mode() {
+        let mut engine = XboardEngine::new();
+        engine.handle_command("force");
+        assert_eq!(engine.engine_color, None);
+        // with and without "usermove"
+        engine.handle_command("usermove e2e4");
+        engine.handle_command("e7e5");
+        engine.handle_command("e2e4");
+        engine.handle_command("g1f3");
+        assert!(engine.search_thread.is_none());
+        assert_eq!(engine.game.lock().unwrap().history().moves().len(), 3);
+
+        assert!(engine.take_back(2).is_ok());
+        assert_eq!(engine.game.lock().unwrap().history().moves().len(), 1);
+        // taking back more moves than were played leaves the game as it is
+        assert!(engine.take_back(2).is_err());
+        assert_eq!(engine.game.lock().unwrap().history().moves().len(), 1);
+
+        engine.handle_command("setboard 4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        assert_eq!(engine.game.lock().unwrap().to_fen(), "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        // an illegal position leaves the game as it is
+        engine.handle_command("setboard 7k/8/6KQ/8/8/8/8/8 w - - 0 1");
+        assert_eq!(engine.game.lock().unwrap().to_fen(), "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+
+        engine.handle_command("new");
+        assert_eq!(engine.engine_color, Some(PieceColor::Black));
+        assert_eq!(engine.game.lock().unwrap().history().moves().len(), 0);
+        assert!(engine.handle_command("unknown"));
+        assert!(!engine.handle_command("quit"));
+    }
+
+    #[test]
+    fn thinks_when_its_side_is_to_move() {
+        let mut engine = XboardEngine::new();
+        engine.handle_command("sd 1");
+        engine.handle_command("usermove e2e4");
+        engine.stop_search(false);
+        assert_eq!(engine.game.lock().unwrap().history().moves().len(), 2);
+
+        // "go" makes the engine play the side to move
+        engine.handle_command("go");
+        engine.stop_search(false);
+        assert_eq!(engine.engine_color, Some(PieceColor::White));
+        assert_eq!(engine.game.lock().unwrap().history().moves().len(), 3);
+    }
+}
@@ -12,6 +12,7 @@ pub struct Board {
 
 
 const SYMBOLS_ROW: &str = "    a  b  c  d  e  f  g  h\n";
+const SYMBOLS_ROW_FROM_BLACK: &str = "    h  g  f  e  d  c  b  a\n";
 
 impl Board {
     pub fn empty() -> Board {
@@ -69,13 +70,20 @@ impl Board {
     /// Empty squares are represented by "--" for white squares or "::" for black squares.
     /// Rows are separated by newlines. The first row corresponds to row 8, the last row to row 1.
     pub fn from_string(input: &str) -> Result<Board, String> {
+        Self::from_string_for(input, White)
+    }
+
+    /// Same as `from_string` for a board seen from the side of `perspective`, as printed by
+    /// `get_display_str_for`. For Black, the first row is row 1 and the first column is h.
+    pub fn from_string_for(input: &str, perspective: PieceColor) -> Result<Board, String> {
+        let (rows, cols) = Self::display_order(perspective);
         let mut board = Board::empty();
         for (row, line) in input.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).enumerate() {
             if row >= 9 {
                 return Err(String::from("Too many lines in board string"));
             }
             if row == 8 { continue } // don't validate the last line if it's column letters
-            let row = 7 - row;
+            let row = rows[row];
 
             let squares: Vec<_> = line.split_whitespace().enumerate().collect();
             if squares.len() > 9 {
@@ -85,8 +93,8 @@ impl Board {
 
             for (col, square) in squares {
                 if has_row_number && col == 0 { continue };
-                let col = if has_row_number { col - 1} else { col };
-                let pos = Pos::new(col as i8, row as i8);
+                let col = cols[if has_row_number { col - 1} else { col }];
+                let pos = Pos::new(col, row);
                 match square {
                     "::" => {
                         if (col + row) % 2 != 0 {
@@ -113,7 +121,7 @@ impl Board {
                             'K' => King,
                             _other => return Err(format!("Invalid piece type {_other} at {pos}")),
                         };
-                        board.set(col as i8, row as i8, piece, color)
+                        board.set(col, row, piece, color)
                     }
                     _other => return Err(format!("Invalid square format {_other} at {pos}")),
                 }
@@ -177,6 +185,12 @@ impl Board {
     /// ```
     /// If print_col_row_helpers is true, the row numbers and column letters are printed.
     pub fn get_display_str(&self, print_col_row_helpers: bool) -> String {
+        self.get_display_str_for(print_col_row_helpers, White)
+    }
+
+    /// Same as `get_display_str`, but seen from the side of `perspective`:
+    /// for Black, row 1 is on top and column h on the left.
+    pub fn get_display_str_for(&self, print_col_row_helpers: bool, perspective: PieceColor) -> String {
         // When printing row and col helpers, we have 9 rows.
         // Each row contains 2 symbols for number ("1 "), 3 symbols per each square * 8 (" bK"),
         // and closing '\n', resulting in 2 + 3 * 8 + 1 = 27. We also have '\n' at the start of the board.
@@ -190,14 +204,15 @@ impl Board {
         let mut result = String::with_capacity(if print_col_row_helpers { FULL_BOARD_STRING_CAPACITY } else { SHORT_BOARD_STRING_CAPACITY });
         result.push('\n');
 
-        // print the board row-by-row starting from the topmost row which is 8
-        for row in (0..8).rev() {
+        // print the board row-by-row starting from the topmost row, which is 8 from White's side
+        let (rows, cols) = Self::display_order(perspective);
+        for row in rows {
             if print_col_row_helpers {
                 result.push_str((row + 1).to_string().as_str());
                 result.push(' ');
             }
 
-            for col in 0..8 {
+            for col in cols {
                 result.push(' ');
 
                 let square = self.at(col, row);
@@ -211,7 +226,7 @@ impl Board {
         }
 
         if print_col_row_helpers {
-            result.push_str(SYMBOLS_ROW);
+            result.push_str(if perspective == White { SYMBOLS_ROW } else { SYMBOLS_ROW_FROM_BLACK });
         }
 
         result
@@ -221,6 +236,20 @@ impl Board {
         println!("{}", self.get_display_str(print_col_row_helpers));
     }
 
+    pub fn print_for(&self, print_col_row_helpers: bool, perspective: PieceColor) {
+        println!("{}", self.get_display_str_for(print_col_row_helpers, perspective));
+    }
+
+    /// Rows from top to bottom and columns from left to right of the board seen from the side of `perspective`
+    pub fn display_order(perspective: PieceColor) -> ([i8; 8], [i8; 8]) {
+        let ascending = [0, 1, 2, 3, 4, 5, 6, 7];
+        let descending = [7, 6, 5, 4, 3, 2, 1, 0];
+        match perspective {
+            White => (descending, ascending),
+            Black => (ascending, descending),
+        }
+    }
+
     pub fn at(&self, col: i8, row: i8) -> &BoardSquare {
         &self.squares[Self::get_index(col, row)]
     }
@@ -642,4 +671,30 @@ mod tests {
 
         assert_eq!(board_parsed, board_manual);
     }
+
+    #[test]
+    fn parsing_board_str_from_blacks_side_works() {
+        let text = "
+            1  -- :: -- :: wK :: -- ::
+            2  :: -- :: wp :: -- :: --
+            3  -- :: -- :: -- :: -- ::
+            4  :: -- :: -- :: -- :: --
+            5  -- :: -- :: -- :: -- ::
+            6  :: -- :: -- :: -- :: --
+            7  -- :: -- bR -- :: -- ::
+            8  :: -- :: -- bK -- :: --
+                h  g  f  e  d  c  b  a
+        ";
+        let board = Board::from_string_for(text, Black).expect("Failed to parse board string");
+        let mut board_manual = Board::empty();
+        board_manual.set(3, 0, King, White);
+        board_manual.set(4, 1, Pawn, White);
+        board_manual.set(4, 6, Rook, Black);
+        board_manual.set(3, 7, King, Black);
+        assert_eq!(board, board_manual);
+
+        let display = board.get_display_str_for(true, Black);
+        assert_eq!(display.lines().nth(1), Some("1  -- :: -- :: wK :: -- ::"));
+        assert_eq!(Board::from_string_for(&display, Black), Ok(board));
+    }
 }
\ No newline at end of file
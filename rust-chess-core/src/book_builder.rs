@@ -0,0 +1,344 @@
+use crate::board::PieceColor;
+use crate::game::Game;
+use crate::pgn::{PgnError, PgnGame};
+use crate::polyglot::{BookEntry, encode_move, polyglot_hash};
+use crate::r#move::Move;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::io::Write;
+
+#[derive(Clone, Debug)]
+pub struct BookBuilderOptions {
+    // only the first `max_ply` half-moves of each game are added to the book
+    pub max_ply: usize,
+    // if set, only the moves of this player go into the book file, and games they didn't play are skipped
+    pub player: Option<String>,
+    // moves played in fewer games are left out of the book file
+    pub min_games: u32,
+}
+
+impl Default for BookBuilderOptions {
+    fn default() -> Self {
+        BookBuilderOptions {
+            max_ply: 20,
+            player: None,
+            min_games: 1,
+        }
+    }
+}
+
+/// Results of the games a move was played in, from the point of view of the side that made it
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct MoveStats {
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MoveStats {
+    /// The usual Polyglot weighting: two points for a win and one for a draw
+    pub fn weight(&self) -> u32 {
+        2 * self.wins + self.draws
+    }
+
+    /// `winner` is None if the result is unknown and Some(None) for a draw
+    fn add_game(&mut self, winner: Option<Option<PieceColor>>, color: PieceColor) {
+        self.games += 1;
+        match winner {
+            Some(Some(winner)) if winner == color => self.wins += 1,
+            Some(Some(_)) => self.losses += 1,
+            Some(None) => self.draws += 1,
+            None => {}
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct BookMoveStats {
+    mv: Move,
+    raw_move: u16,
+    // all games the move was played in, shown in the tree
+    stats: MoveStats,
+    // only the games that pass the player filter, used for the book file
+    book_stats: MoveStats,
+}
+
+/// Builds a Polyglot opening book from PGN games
+pub struct BookBuilder {
+    options: BookBuilderOptions,
+    // moves played in each position, by Polyglot key
+    positions: HashMap<u64, Vec<BookMoveStats>>,
+    games_added: usize,
+}
+
+impl BookBuilder {
+    pub fn new(options: BookBuilderOptions) -> BookBuilder {
+        BookBuilder {
+            options,
+            positions: HashMap::new(),
+            games_added: 0,
+        }
+    }
+
+    /// Number of games that passed the player filter and were added
+    pub fn games_added(&self) -> usize {
+        self.games_added
+    }
+
+    pub fn position_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Adds the opening moves of a game. Returns false if the game was skipped by the player filter.
+    /// Games with an illegal move within the ply limit are rejected as a whole.
+    pub fn add_game(&mut self, pgn: &PgnGame) -> Result<bool, PgnError> {
+        let counted_color = match &self.options.player {
+            None => None,
+            Some(player) => {
+                let plays = |tag: &str| pgn.tag(tag).is_some_and(|name| name.eq_ignore_ascii_case(player));
+                if plays("White") {
+                    Some(PieceColor::White)
+                } else if plays("Black") {
+                    Some(PieceColor::Black)
+                } else {
+                    return Ok(false);
+                }
+            }
+        };
+        let winner = match pgn.result.as_deref().or(pgn.tag("Result")) {
+            Some("1-0") => Some(Some(PieceColor::White)),
+            Some("0-1") => Some(Some(PieceColor::Black)),
+            Some("1/2-1/2") => Some(None),
+            // unfinished or unknown result, the moves count as played but add no weight
+            _ => None,
+        };
+
+        // replay first, so a broken game doesn't leave half of its moves in the book
+        let mut game = pgn.initial_game()?;
+        let mut played = Vec::new();
+        for pgn_move in pgn.moves.iter().take(self.options.max_ply) {
+            let mv = game.parse_short_notation(&pgn_move.san).map_err(|err| PgnError {
+                message: format!("Can't parse move {}: {}", pgn_move.san, err),
+                line: pgn_move.line,
+                column: pgn_move.column,
+            })?;
+            played.push((polyglot_hash(&game), mv, encode_move(&mv, game.board()), game.turn()));
+            game.make_move(&mv).map_err(|err| PgnError {
+                message: format!("Illegal move {}: {}", pgn_move.san, err),
+                line: pgn_move.line,
+                column: pgn_move.column,
+            })?;
+        }
+
+        for (key, mv, raw_move, color) in played {
+            let moves = self.positions.entry(key).or_default();
+            let index = match moves.iter().position(|book_move| book_move.raw_move == raw_move) {
+                Some(index) => index,
+                None => {
+                    moves.push(BookMoveStats {
+                        mv,
+                        raw_move,
+                        stats: MoveStats::default(),
+                        book_stats: MoveStats::default(),
+                    });
+                    moves.len() - 1
+                }
+            };
+            let book_move = &mut moves[index];
+            book_move.stats.add_game(winner, color);
+            if counted_color.is_none_or(|counted_color| counted_color == color) {
+                book_move.book_stats.add_game(winner, color);
+            }
+        }
+        self.games_added += 1;
+        Ok(true)
+    }
+
+    /// Returns the book entries sorted by key as Polyglot requires, best moves first within a position.
+    /// Moves with zero weight or played in too few games are left out.
+    pub fn entries(&self) -> Vec<BookEntry> {
+        let mut entries = Vec::new();
+        for (&key, moves) in &self.positions {
+            let moves: Vec<&BookMoveStats> = moves
+                .iter()
+                .filter(|book_move| {
+                    book_move.book_stats.games >= self.options.min_games && book_move.book_stats.weight() > 0
+                })
+                .collect();
+            // weights are 16-bit, scale them down in the rare case they don't fit
+            let max_weight = moves.iter().map(|book_move| book_move.book_stats.weight()).max().unwrap_or(0);
+            let scale = |weight: u32| {
+                if max_weight <= u16::MAX as u32 {
+                    weight as u16
+                } else {
+                    (weight as u64 * u16::MAX as u64 / max_weight as u64).max(1) as u16
+                }
+            };
+            entries.extend(moves.iter().map(|book_move| BookEntry {
+                key,
+                raw_move: book_move.raw_move,
+                weight: scale(book_move.book_stats.weight()),
+                learn: 0,
+            }));
+        }
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)));
+        entries
+    }
+
+    /// Writes the book in the Polyglot `.bin` format
+    pub fn write_polyglot(&self, writer: &mut impl Write) -> io::Result<()> {
+        for entry in self.entries() {
+            writer.write_all(&entry.to_bytes())?;
+        }
+        writer.flush()
+    }
+
+    /// Writes the book as an indented move tree starting from the standard initial position,
+    /// with the statistics of every move. The opponent's replies are included even when filtering by player.
+    /// Games starting from a custom FEN are not reachable from it.
+    pub fn write_tree(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut visited = HashSet::new();
+        self.write_subtree(writer, &Game::new(), 0, &mut visited)?;
+        writer.flush()
+    }
+
+    fn write_subtree(
+        &self,
+        writer: &mut impl Write,
+        game: &Game,
+        ply: usize,
+        path: &mut HashSet<u64>,
+    ) -> io::Result<()> {
+        let key = polyglot_hash(game);
+        let Some(moves) = self.positions.get(&key) else {
+            return Ok(());
+        };
+        // a position can be reached again through a repetition, don't loop forever
+        if !path.insert(key) {
+            return Ok(());
+        }
+
+        let mut moves: Vec<&BookMoveStats> = moves.iter().collect();
+        moves.sort_by(|a, b| {
+            b.stats
+                .weight()
+                .cmp(&a.stats.weight())
+                .then(b.stats.games.cmp(&a.stats.games))
+        });
+        for book_move in moves {
+            let notation = game
+                .to_short_notation(&book_move.mv)
+                .unwrap_or_else(|_| book_move.mv.to_string());
+            let number = match game.turn() {
+                PieceColor::White => format!("{}.", game.fullmove_number()),
+                PieceColor::Black => format!("{}...", game.fullmove_number()),
+            };
+            let stats = book_move.stats;
+            writeln!(
+                writer,
+                "{:indent$}{} {}  games: {}  +{} ={} -{}  weight: {}",
+                "",
+                number,
+                notation,
+                stats.games,
+                stats.wins,
+                stats.draws,
+                stats.losses,
+                stats.weight(),
+                indent = ply * 2
+            )?;
+
+            let mut next = game.clone();
+            if next.make_move(&book_move.mv).is_ok() {
+                self.write_subtree(writer, &next, ply + 1, path)?;
+            }
+        }
+
+        path.remove(&key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::parse_pgn;
+    use crate::polyglot::PolyglotBook;
+    use std::io::Cursor;
+
+    const GAMES: &str = r#"
+[White "alice"]
+[Black "bob"]
+[Result "1-0"]
+1. e4 e5 2. Nf3 1-0
+
+[White "bob"]
+[Black "alice"]
+[Result "1/2-1/2"]
+1. e4 c5 1/2-1/2
+
+[White "carol"]
+[Black "dave"]
+[Result "0-1"]
+1. d4 d5 0-1
+"#;
+
+    fn build(options: BookBuilderOptions) -> BookBuilder {
+        let mut builder = BookBuilder::new(options);
+        for game in parse_pgn(GAMES) {
+            builder.add_game(&game).unwrap();
+        }
+        builder
+    }
+
+    #[test]
+    fn weights_moves_by_result() {
+        let builder = build(BookBuilderOptions::default());
+        let mut bytes = Vec::new();
+        builder.write_polyglot(&mut bytes).unwrap();
+
+        let mut book = PolyglotBook::new(Cursor::new(bytes)).unwrap();
+        let moves = book.moves(&Game::new()).unwrap();
+        // e4: a win and a draw, d4: a loss, which leaves it out of the book
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].mv, Move::from_long_notation("e2e4"));
+        assert_eq!(moves[0].weight, 3);
+    }
+
+    #[test]
+    fn player_filter_counts_only_their_moves() {
+        let builder = build(BookBuilderOptions {
+            player: Some("Alice".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(builder.games_added(), 2);
+
+        // only alice's win with e4 counts, bob played it in the drawn game
+        let entries = builder.entries();
+        let first_move = entries.iter().find(|entry| entry.key == polyglot_hash(&Game::new())).unwrap();
+        assert_eq!(first_move.weight, 2);
+        assert_eq!(entries.len(), 3);
+
+        // the tree still shows the opponent's replies
+        let mut tree = Vec::new();
+        builder.write_tree(&mut tree).unwrap();
+        let tree = String::from_utf8(tree).unwrap();
+        assert_eq!(
+            tree,
+            "1. e4  games: 2  +1 =1 -0  weight: 3\n  \
+             1... c5  games: 1  +0 =1 -0  weight: 1\n  \
+             1... e5  games: 1  +0 =0 -1  weight: 0\n    \
+             2. Nf3  games: 1  +1 =0 -0  weight: 2\n"
+        );
+    }
+
+    #[test]
+    fn ply_limit_stops_counting() {
+        let builder = build(BookBuilderOptions {
+            max_ply: 1,
+            ..Default::default()
+        });
+        assert_eq!(builder.position_count(), 1);
+    }
+}
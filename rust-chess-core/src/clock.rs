@@ -0,0 +1,425 @@
+use crate::board::PieceColor;
+use crate::board::PieceColor::White;
+use crate::game::Game;
+use crate::r#move::Move;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Where a clock gets the current time from, so that tests can control it
+pub trait TimeSource {
+    /// Time passed since some fixed moment
+    fn now(&self) -> Duration;
+}
+
+/// The real time
+#[derive(Clone, Debug)]
+pub struct SystemTimeSource {
+    start: Instant,
+}
+
+impl Default for SystemTimeSource {
+    fn default() -> SystemTimeSource {
+        SystemTimeSource { start: Instant::now() }
+    }
+}
+
+impl TimeSource for SystemTimeSource {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Time that only passes when it is advanced, for tests. Clones share the same time.
+#[derive(Clone, Debug, Default)]
+pub struct ManualTimeSource {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl ManualTimeSource {
+    pub fn new() -> ManualTimeSource {
+        Self::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl TimeSource for ManualTimeSource {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// Time a player gets back for each move
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Bonus {
+    None,
+    /// Fischer increment, added after every move
+    Increment(Duration),
+    /// The time used for the move is given back, up to the delay
+    Bronstein(Duration),
+    /// Simple (US) delay: the clock only starts counting down after the delay
+    SimpleDelay(Duration),
+}
+
+/// Part of a time control, e.g. 40 moves in 90 minutes
+#[derive(Clone, PartialEq, Debug)]
+pub struct TimeControlStage {
+    // moves to play in the stage, None for the rest of the game
+    pub moves: Option<u32>,
+    pub time: Duration,
+    pub bonus: Bonus,
+}
+
+/// Time control of a game, one or more stages. The time of a stage is added to what is left when the
+/// moves of the previous stage are played. If the last stage has a number of moves, it repeats.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TimeControl {
+    pub stages: Vec<TimeControlStage>,
+}
+
+impl TimeControl {
+    pub fn sudden_death(time: Duration) -> TimeControl {
+        Self::single_stage(time, Bonus::None)
+    }
+
+    pub fn increment(time: Duration, increment: Duration) -> TimeControl {
+        Self::single_stage(time, Bonus::Increment(increment))
+    }
+
+    pub fn bronstein(time: Duration, delay: Duration) -> TimeControl {
+        Self::single_stage(time, Bonus::Bronstein(delay))
+    }
+
+    pub fn simple_delay(time: Duration, delay: Duration) -> TimeControl {
+        Self::single_stage(time, Bonus::SimpleDelay(delay))
+    }
+
+    fn single_stage(time: Duration, bonus: Bonus) -> TimeControl {
+        TimeControl {
+            stages: vec![TimeControlStage { moves: None, time, bonus }],
+        }
+    }
+
+    /// Parses stages separated by commas, each `[moves/]minutes[+seconds]`. Instead of "+" for an increment,
+    /// "d" gives a simple delay and "b" a Bronstein delay.
+    /// E.g. "5+3", "10d5" or the FIDE control "40/90+30,30+30".
+    pub fn parse(input: &str) -> Result<TimeControl, String> {
+        let stages = input
+            .split(',')
+            .map(|stage| parse_stage(stage.trim()).map_err(|err| format!("Invalid time control {}: {}", input, err)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(TimeControl { stages })
+    }
+
+    fn stage(&self, index: usize) -> &TimeControlStage {
+        &self.stages[index.min(self.stages.len() - 1)]
+    }
+
+    fn next_stage(&self, index: usize) -> usize {
+        (index + 1).min(self.stages.len() - 1)
+    }
+}
+
+fn parse_stage(stage: &str) -> Result<TimeControlStage, &'static str> {
+    let (moves, rest) = match stage.split_once('/') {
+        Some((moves, rest)) => match moves.parse() {
+            Ok(moves) if moves > 0 => (Some(moves), rest),
+            _ => return Err("invalid number of moves"),
+        },
+        None => (None, stage),
+    };
+    let (minutes, bonus) = match rest.find(['+', 'd', 'b']) {
+        Some(index) => {
+            let seconds = parse_duration(&rest[index + 1..], 1.0).ok_or("invalid seconds")?;
+            let bonus = match rest.as_bytes()[index] {
+                b'+' => Bonus::Increment(seconds),
+                b'd' => Bonus::SimpleDelay(seconds),
+                _ => Bonus::Bronstein(seconds),
+            };
+            (&rest[..index], bonus)
+        }
+        None => (rest, Bonus::None),
+    };
+    let time = parse_duration(minutes, 60.0).ok_or("invalid minutes")?;
+    if time.is_zero() {
+        return Err("the time must be positive");
+    }
+    Ok(TimeControlStage { moves, time, bonus })
+}
+
+fn parse_duration(input: &str, unit_seconds: f64) -> Option<Duration> {
+    let value: f64 = input.parse().ok()?;
+    Duration::try_from_secs_f64(value * unit_seconds).ok()
+}
+
+impl Display for TimeControl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, stage) in self.stages.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            if let Some(moves) = stage.moves {
+                write!(f, "{}/", moves)?;
+            }
+            write!(f, "{}", stage.time.as_secs_f64() / 60.0)?;
+            match stage.bonus {
+                Bonus::None => {}
+                Bonus::Increment(seconds) => write!(f, "+{}", seconds.as_secs_f64())?,
+                Bonus::SimpleDelay(seconds) => write!(f, "d{}", seconds.as_secs_f64())?,
+                Bonus::Bronstein(seconds) => write!(f, "b{}", seconds.as_secs_f64())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+struct SideClock {
+    remaining: Duration,
+    stage: usize,
+    moves_in_stage: u32,
+}
+
+/// A chess clock. Only the clock of the side to move runs, `press` ends its turn.
+#[derive(Clone, Debug)]
+pub struct Clock<S: TimeSource = SystemTimeSource> {
+    control: TimeControl,
+    source: S,
+    // indexed by `color_index`
+    sides: [SideClock; 2],
+    turn: PieceColor,
+    // time the side to move used in its turn before the clock was last stopped
+    turn_used: Duration,
+    // time of the source when the clock was started, None while it is stopped
+    started_at: Option<Duration>,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        Self::with_time_source(control, SystemTimeSource::default())
+    }
+}
+
+impl<S: TimeSource> Clock<S> {
+    /// A stopped clock with White to move
+    pub fn with_time_source(control: TimeControl, source: S) -> Clock<S> {
+        let side = SideClock {
+            remaining: control.stages[0].time,
+            stage: 0,
+            moves_in_stage: 0,
+        };
+        Clock {
+            control,
+            source,
+            sides: [side.clone(), side],
+            turn: White,
+            turn_used: Duration::ZERO,
+            started_at: None,
+        }
+    }
+
+    pub fn time_control(&self) -> &TimeControl {
+        &self.control
+    }
+
+    /// The side whose clock runs when the clock is started
+    pub fn turn(&self) -> PieceColor {
+        self.turn
+    }
+
+    /// Sets the side to move, e.g. for a game starting with Black to move. The turn starts anew.
+    pub fn set_turn(&mut self, color: PieceColor) {
+        self.turn = color;
+        self.turn_used = Duration::ZERO;
+        self.started_at = self.started_at.map(|_| self.source.now());
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.started_at.is_some()
+    }
+
+    /// Starts or resumes the clock of the side to move
+    pub fn start(&mut self) {
+        if self.started_at.is_none() {
+            self.started_at = Some(self.source.now());
+        }
+    }
+
+    /// Pauses the clock
+    pub fn stop(&mut self) {
+        self.turn_used = self.turn_time();
+        self.started_at = None;
+    }
+
+    /// Time left for the side, counting the running turn
+    pub fn remaining(&self, color: PieceColor) -> Duration {
+        let side = &self.sides[color_index(color)];
+        if color != self.turn {
+            return side.remaining;
+        }
+        side.remaining.saturating_sub(self.charged_time(self.turn_time()))
+    }
+
+    /// The side to move if its time has run out
+    pub fn flagged(&self) -> Option<PieceColor> {
+        self.remaining(self.turn).is_zero().then_some(self.turn)
+    }
+
+    /// Moves played by the side in the current stage of the time control
+    pub fn moves_in_stage(&self, color: PieceColor) -> u32 {
+        self.sides[color_index(color)].moves_in_stage
+    }
+
+    /// Moves the side has to play until the next stage, None if the current stage lasts for the rest of the game
+    pub fn moves_to_go(&self, color: PieceColor) -> Option<u32> {
+        let side = &self.sides[color_index(color)];
+        self.control.stage(side.stage).moves.map(|moves| moves - side.moves_in_stage)
+    }
+
+    /// Bonus of the current stage of the side
+    pub fn bonus(&self, color: PieceColor) -> Bonus {
+        self.control.stage(self.sides[color_index(color)].stage).bonus
+    }
+
+    /// How long until the flag of the side to move falls, None while the clock is stopped
+    pub fn time_until_flag(&self) -> Option<Duration> {
+        self.started_at?;
+        let delay_left = match self.bonus(self.turn) {
+            Bonus::SimpleDelay(delay) => delay.saturating_sub(self.turn_time()),
+            _ => Duration::ZERO,
+        };
+        Some(self.remaining(self.turn) + delay_left)
+    }
+
+    /// Ends the turn of the side to move after its move: the bonus is applied, the next stage starts if the moves
+    /// of this one are played, and the clock of the opponent starts. Fails if the time ran out before the press.
+    pub fn press(&mut self) -> Result<(), &'static str> {
+        let used = self.turn_time();
+        let charged = self.charged_time(used);
+        let control = &self.control;
+        let side = &mut self.sides[color_index(self.turn)];
+        if charged >= side.remaining {
+            side.remaining = Duration::ZERO;
+            self.stop();
+            return Err("Time is up");
+        }
+
+        let stage = control.stage(side.stage);
+        side.remaining -= charged;
+        match stage.bonus {
+            Bonus::Increment(increment) => side.remaining += increment,
+            Bonus::Bronstein(delay) => side.remaining += used.min(delay),
+            Bonus::None | Bonus::SimpleDelay(_) => {}
+        }
+        side.moves_in_stage += 1;
+        if stage.moves.is_some_and(|moves| side.moves_in_stage >= moves) {
+            side.stage = control.next_stage(side.stage);
+            side.moves_in_stage = 0;
+            side.remaining += control.stage(side.stage).time;
+        }
+
+        let running = self.is_running();
+        self.set_turn(self.turn.opposite());
+        if running {
+            self.started_at = Some(self.source.now());
+        }
+        Ok(())
+    }
+
+    // time the side to move has used in this turn
+    fn turn_time(&self) -> Duration {
+        let running = self.started_at.map_or(Duration::ZERO, |started_at| self.source.now().saturating_sub(started_at));
+        self.turn_used + running
+    }
+
+    // part of the turn time that counts against the side to move
+    fn charged_time(&self, used: Duration) -> Duration {
+        let side = &self.sides[color_index(self.turn)];
+        match self.control.stage(side.stage).bonus {
+            Bonus::SimpleDelay(delay) => used.saturating_sub(delay),
+            _ => used,
+        }
+    }
+}
+
+fn color_index(color: PieceColor) -> usize {
+    if color == White { 0 } else { 1 }
+}
+
+/// A game played with a clock. The clock of the side to move runs, and a player whose time runs out loses,
+/// or draws if the opponent can't checkmate.
+#[derive(Clone)]
+pub struct TimedGame<S: TimeSource = SystemTimeSource> {
+    game: Game,
+    clock: Clock<S>,
+}
+
+impl TimedGame {
+    pub fn new(game: Game, control: TimeControl) -> TimedGame {
+        Self::with_time_source(game, control, SystemTimeSource::default())
+    }
+}
+
+impl<S: TimeSource> TimedGame<S> {
+    /// The clock is stopped until `start`
+    pub fn with_time_source(game: Game, control: TimeControl, source: S) -> TimedGame<S> {
+        let mut clock = Clock::with_time_source(control, source);
+        clock.set_turn(game.turn());
+        TimedGame { game, clock }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn clock(&self) -> &Clock<S> {
+        &self.clock
+    }
+
+    /// Starts or resumes the clock
+    pub fn start(&mut self) {
+        if self.game.result().is_none() {
+            self.clock.start();
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.clock.stop();
+    }
+
+    /// Makes the move and presses the clock. If the time of the side to move has run out, the move is not made
+    /// and the game ends on time.
+    pub fn make_move(&mut self, mv: &Move) -> Result<(), &'static str> {
+        if self.check_flag() {
+            return Err("Time is up");
+        }
+        let mut game = self.game.clone();
+        game.make_move(mv)?;
+        let mover = self.game.turn();
+        if let Err(err) = self.clock.press() {
+            self.game.time_out(mover);
+            return Err(err);
+        }
+        self.game = game;
+        if self.game.result().is_some() {
+            self.clock.stop();
+        }
+        Ok(())
+    }
+
+    /// Ends the game on time if the side to move has run out of it. Returns true then.
+    pub fn check_flag(&mut self) -> bool {
+        if self.game.result().is_some() {
+            return false;
+        }
+        let Some(color) = self.clock.flagged() else {
+            return false;
+        };
+        self.clock.stop();
+        self.game.time_out(color);
+        true
+    }
+}
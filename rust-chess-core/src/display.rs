@@ -0,0 +1,175 @@
+use crate::board::PieceColor::{Black, White};
+use crate::board::PieceType::{Bishop, King, Knight, Pawn, Queen, Rook};
+use crate::board::{Board, PieceColor, PieceType};
+use crate::game::Game;
+use crate::pos::Pos;
+use crate::r#move::Move;
+
+const ANSI_RESET: &str = "\x1b[0m";
+// 256-colour backgrounds of the squares
+pub const LIGHT_SQUARE: u8 = 180;
+pub const DARK_SQUARE: u8 = 137;
+pub const LAST_MOVE_LIGHT_SQUARE: u8 = 187;
+pub const LAST_MOVE_DARK_SQUARE: u8 = 143;
+pub const CHECK_SQUARE: u8 = 167;
+pub const DESTINATION_SQUARE: u8 = 108;
+// foregrounds of the pieces
+pub const WHITE_PIECE: u8 = 231;
+pub const BLACK_PIECE: u8 = 16;
+
+/// How the pieces are drawn
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PieceStyle {
+    /// Colour and type letters as in `Board::get_display_str`, e.g. "wK" and "bp"
+    Letters,
+    /// Chess glyphs, e.g. "♔" and "♟"
+    Unicode,
+}
+
+/// What `render` draws besides the pieces
+#[derive(Clone, PartialEq)]
+pub struct DisplayOptions {
+    pub style: PieceStyle,
+    // light and dark squares as ANSI background colours, for terminals that support 256 colours
+    pub colors: bool,
+    // rank numbers and file letters around the board
+    pub coordinates: bool,
+    // the side at the bottom of the board
+    pub perspective: PieceColor,
+    // squares of the move highlighted as the last one
+    pub last_move: Option<Move>,
+    // square of a king in check
+    pub check: Option<Pos>,
+    // squares marked as legal destinations of a selected piece
+    pub destinations: Vec<Pos>,
+}
+
+impl Default for DisplayOptions {
+    fn default() -> DisplayOptions {
+        DisplayOptions {
+            style: PieceStyle::Letters,
+            colors: false,
+            coordinates: true,
+            perspective: White,
+            last_move: None,
+            check: None,
+            destinations: Vec::new(),
+        }
+    }
+}
+
+impl DisplayOptions {
+    /// Highlights the last move of the game and the king of the side to move if it is in check
+    pub fn highlight_game(mut self, game: &Game) -> DisplayOptions {
+        let board = game.board();
+        self.last_move = game.history().moves().last().copied();
+        self.check = match board.is_check(game.turn()) {
+            true => board.find_king(game.turn()),
+            false => None,
+        };
+        self
+    }
+
+    /// Marks the squares the piece on `from` can legally move to
+    pub fn mark_destinations(mut self, game: &Game, from: Pos) -> DisplayOptions {
+        self.destinations = game
+            .get_moves_from_pos(from)
+            .iter()
+            .map(|mv| Pos::new(mv.to_col, mv.to_row))
+            .collect();
+        self
+    }
+}
+
+/// Draws the board as text. Without colours, the last move and the check aren't shown, and the legal
+/// destinations are marked on the empty squares only ("<>" with letters, "•" with glyphs).
+/// With the letter style and no highlights, the result is the same as `Board::get_display_str_for`.
+pub fn render(board: &Board, options: &DisplayOptions) -> String {
+    let (rows, cols) = Board::display_order(options.perspective);
+    let mut result = String::from("\n");
+    for row in rows {
+        if options.coordinates {
+            result.push_str(&format!("{} ", row + 1));
+        }
+        for col in cols {
+            result.push_str(&render_square(board, options, Pos::new(col, row)));
+        }
+        if options.colors {
+            result.push_str(ANSI_RESET);
+        }
+        result.push('\n');
+    }
+    if options.coordinates {
+        // the letters are under the piece types, or under the glyphs in the middle of the squares
+        result.push_str(match options.style {
+            PieceStyle::Letters => "  ",
+            PieceStyle::Unicode => " ",
+        });
+        for col in cols {
+            result.push_str(&format!("  {}", (b'a' + col as u8) as char));
+        }
+        result.push('\n');
+    }
+    result
+}
+
+fn render_square(board: &Board, options: &DisplayOptions, pos: Pos) -> String {
+    let (col, row) = pos.tuple();
+    let light = (row + col) % 2 == 1;
+    let piece = board.at(col, row).piece();
+    let is_destination = options.destinations.contains(&pos);
+    let text = match (options.style, piece) {
+        (PieceStyle::Letters, Some((piece_type, color))) => format!(" {}{}", color, piece_type),
+        // solid glyphs read best on coloured squares, the text colour tells the sides apart
+        (PieceStyle::Unicode, Some((piece_type, color))) => {
+            format!(" {} ", glyph(piece_type, if options.colors { Black } else { color }))
+        }
+        (PieceStyle::Letters, None) if is_destination && !options.colors => " <>".to_string(),
+        (PieceStyle::Unicode, None) if is_destination && !options.colors => " • ".to_string(),
+        (PieceStyle::Letters, None) if options.colors => "   ".to_string(),
+        (PieceStyle::Letters, None) => format!(" {}", if light { "--" } else { "::" }),
+        (PieceStyle::Unicode, None) if options.colors => "   ".to_string(),
+        (PieceStyle::Unicode, None) => format!(" {} ", if light { '·' } else { ' ' }),
+    };
+    if !options.colors {
+        return text;
+    }
+
+    let is_last_move = options
+        .last_move
+        .is_some_and(|mv| (mv.from_col, mv.from_row) == (col, row) || (mv.to_col, mv.to_row) == (col, row));
+    let background = if options.check == Some(pos) {
+        CHECK_SQUARE
+    } else if is_destination {
+        DESTINATION_SQUARE
+    } else if is_last_move {
+        if light { LAST_MOVE_LIGHT_SQUARE } else { LAST_MOVE_DARK_SQUARE }
+    } else if light {
+        LIGHT_SQUARE
+    } else {
+        DARK_SQUARE
+    };
+    let foreground = match piece {
+        Some((_, White)) => WHITE_PIECE,
+        _ => BLACK_PIECE,
+    };
+    format!("\x1b[48;5;{}m\x1b[38;5;{}m{}", background, foreground, text)
+}
+
+/// Unicode chess symbol of the piece, outlined for White and solid for Black
+pub fn glyph(piece_type: PieceType, color: PieceColor) -> char {
+    match (color, piece_type) {
+        (White, King) => '♔',
+        (White, Queen) => '♕',
+        (White, Rook) => '♖',
+        (White, Bishop) => '♗',
+        (White, Knight) => '♘',
+        (White, Pawn) => '♙',
+        (Black, King) => '♚',
+        (Black, Queen) => '♛',
+        (Black, Rook) => '♜',
+        (Black, Bishop) => '♝',
+        (Black, Knight) => '♞',
+        (Black, Pawn) => '♟',
+    }
+}
@@ -0,0 +1,161 @@
+use crate::board::PieceColor::White;
+use crate::board::PieceType::{Bishop, King, Knight, Pawn, Queen, Rook};
+use crate::board::{Board, PieceColor, PieceType};
+
+pub const PAWN_VALUE: i32 = 100;
+pub const KNIGHT_VALUE: i32 = 320;
+pub const BISHOP_VALUE: i32 = 330;
+pub const ROOK_VALUE: i32 = 500;
+pub const QUEEN_VALUE: i32 = 900;
+
+pub fn piece_value(piece: PieceType) -> i32 {
+    match piece {
+        Pawn => PAWN_VALUE,
+        Knight => KNIGHT_VALUE,
+        Bishop => BISHOP_VALUE,
+        Rook => ROOK_VALUE,
+        Queen => QUEEN_VALUE,
+        King => 0,
+    }
+}
+
+// Piece-square tables are written from White's point of view with a8 in the top left corner,
+// so that they look like the board printed by Board::print. For Black they are mirrored vertically.
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KING_MIDDLEGAME_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+// Non-pawn material of both sides below which the king is evaluated with the endgame table
+const ENDGAME_MATERIAL: i32 = 2 * ROOK_VALUE + 2 * BISHOP_VALUE;
+
+/// Static evaluation of the board in centipawns from White's point of view
+pub fn evaluate(board: &Board) -> i32 {
+    let mut non_pawn_material = 0;
+    for col in 0..8 {
+        for row in 0..8 {
+            if let Some((piece, _)) = board.at(col, row).piece()
+                && piece != Pawn
+            {
+                non_pawn_material += piece_value(piece);
+            }
+        }
+    }
+    let is_endgame = non_pawn_material <= ENDGAME_MATERIAL;
+
+    let mut score = 0;
+    for col in 0..8 {
+        for row in 0..8 {
+            let Some((piece, color)) = board.at(col, row).piece() else {
+                continue;
+            };
+            let value = piece_value(piece) + square_value(piece, color, col, row, is_endgame);
+            if color == White {
+                score += value;
+            } else {
+                score -= value;
+            }
+        }
+    }
+    score
+}
+
+/// Static evaluation of the board in centipawns from the point of view of the given side
+pub fn evaluate_for(board: &Board, color: PieceColor) -> i32 {
+    let score = evaluate(board);
+    if color == White { score } else { -score }
+}
+
+fn square_value(piece: PieceType, color: PieceColor, col: i8, row: i8, is_endgame: bool) -> i32 {
+    // tables start with row 8, so White's rows are flipped and Black's are used as is
+    let table_row = if color == White { 7 - row } else { row };
+    let index = (table_row * 8 + col) as usize;
+    match piece {
+        Pawn => PAWN_TABLE[index],
+        Knight => KNIGHT_TABLE[index],
+        Bishop => BISHOP_TABLE[index],
+        Rook => ROOK_TABLE[index],
+        Queen => QUEEN_TABLE[index],
+        King if is_endgame => KING_ENDGAME_TABLE[index],
+        King => KING_MIDDLEGAME_TABLE[index],
+    }
+}
@@ -0,0 +1,272 @@
+use crate::board::PieceColor::{Black, White};
+use crate::board::PieceType::{Bishop, King, Knight, Pawn, Queen, Rook};
+use crate::board::{Board, PieceColor, PieceType};
+use crate::game::{CastlingRights, Game, GameHistory};
+use crate::pos::Pos;
+
+pub const STARTING_POSITION_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+impl Game {
+    /// Creates a game from a position in Forsyth-Edwards Notation, e.g.
+    /// `rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1`.
+    /// The halfmove clock and fullmove number can be omitted. Positions where the side not to move is in check
+    /// are rejected, since they can't come from a legal game.
+    pub fn from_fen(fen: &str) -> Result<Game, String> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 4 || fields.len() > 6 {
+            return Err(format!("Expected 4 to 6 FEN fields, got {}", fields.len()));
+        }
+
+        let board = parse_board(fields[0])?;
+        let turn = match fields[1] {
+            "w" => White,
+            "b" => Black,
+            other => return Err(format!("Invalid side to move {other}")),
+        };
+        let castling_rights = parse_castling_rights(fields[2])?;
+        let en_passant = match fields[3] {
+            "-" => None,
+            s => Some(Pos::from_notation(s)?),
+        };
+        let halfmove_clock = match fields.get(4) {
+            Some(s) => s.parse().map_err(|_| format!("Invalid halfmove clock {s}"))?,
+            None => 0,
+        };
+        let fullmove_number = match fields.get(5) {
+            Some(s) => match s.parse() {
+                Ok(number) if number > 0 => number,
+                _ => return Err(format!("Invalid fullmove number {s}")),
+            },
+            None => 1,
+        };
+
+        for color in [White, Black] {
+            let kings = (0..8)
+                .flat_map(|col| (0..8).map(move |row| (col, row)))
+                .filter(|(col, row)| board.at(*col, *row).piece() == Some((King, color)))
+                .count();
+            if kings != 1 {
+                return Err(format!("Expected exactly one {color:?} king, found {kings}"));
+            }
+        }
+        if board.is_check(turn.opposite()) {
+            return Err("The side not to move is in check".to_string());
+        }
+
+        let history = GameHistory::with_initial_state(
+            board,
+            turn,
+            castling_rights,
+            en_passant,
+            halfmove_clock,
+            fullmove_number,
+        );
+        Ok(Game::from_board_with_history(board, turn, history))
+    }
+
+    /// Returns the current position in Forsyth-Edwards Notation
+    pub fn to_fen(&self) -> String {
+        let mut fen = board_to_fen(self.board());
+
+        fen.push_str(if self.turn() == White { " w " } else { " b " });
+
+        let rights = self.castling_rights();
+        let board = self.board();
+        let mut castling = String::new();
+        // only report the rights that can still be used, i.e. the king and rook are in place
+        let in_place = |col: i8, row: i8, piece: PieceType, color: PieceColor| {
+            board.at(col, row).piece() == Some((piece, color))
+        };
+        if rights.white_kingside && in_place(4, 0, King, White) && in_place(7, 0, Rook, White) {
+            castling.push('K');
+        }
+        if rights.white_queenside && in_place(4, 0, King, White) && in_place(0, 0, Rook, White) {
+            castling.push('Q');
+        }
+        if rights.black_kingside && in_place(4, 7, King, Black) && in_place(7, 7, Rook, Black) {
+            castling.push('k');
+        }
+        if rights.black_queenside && in_place(4, 7, King, Black) && in_place(0, 7, Rook, Black) {
+            castling.push('q');
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        fen.push_str(&castling);
+
+        match self.en_passant_target() {
+            Some(pos) => fen.push_str(&format!(" {pos}")),
+            None => fen.push_str(" -"),
+        }
+
+        fen.push_str(&format!(" {} {}", self.halfmove_clock(), self.fullmove_number()));
+        fen
+    }
+}
+
+/// Returns only the piece placement field of a FEN string
+pub fn board_to_fen(board: &Board) -> String {
+    let mut result = String::with_capacity(64 + 7);
+    for row in (0..8).rev() {
+        let mut empty_count = 0;
+        for col in 0..8 {
+            let Some((piece, color)) = board.at(col, row).piece() else {
+                empty_count += 1;
+                continue;
+            };
+            if empty_count > 0 {
+                result.push_str(&empty_count.to_string());
+                empty_count = 0;
+            }
+            result.push(piece_to_fen_char(piece, color));
+        }
+        if empty_count > 0 {
+            result.push_str(&empty_count.to_string());
+        }
+        if row > 0 {
+            result.push('/');
+        }
+    }
+    result
+}
+
+pub fn piece_to_fen_char(piece: PieceType, color: PieceColor) -> char {
+    let c = match piece {
+        Pawn => 'p',
+        Knight => 'n',
+        Bishop => 'b',
+        Rook => 'r',
+        Queen => 'q',
+        King => 'k',
+    };
+    if color == White { c.to_ascii_uppercase() } else { c }
+}
+
+pub fn piece_from_fen_char(c: char) -> Option<(PieceType, PieceColor)> {
+    let color = if c.is_ascii_uppercase() { White } else { Black };
+    let piece = match c.to_ascii_lowercase() {
+        'p' => Pawn,
+        'n' => Knight,
+        'b' => Bishop,
+        'r' => Rook,
+        'q' => Queen,
+        'k' => King,
+        _ => return None,
+    };
+    Some((piece, color))
+}
+
+fn parse_board(placement: &str) -> Result<Board, String> {
+    let mut board = Board::empty();
+    let rows: Vec<&str> = placement.split('/').collect();
+    if rows.len() != 8 {
+        return Err(format!("Expected 8 rows in FEN, got {}", rows.len()));
+    }
+    for (i, fen_row) in rows.iter().enumerate() {
+        let row = 7 - i as i8;
+        let mut col: i8 = 0;
+        for c in fen_row.chars() {
+            if let Some(skip) = c.to_digit(10) {
+                if skip == 0 || col + skip as i8 > 8 {
+                    return Err(format!("Too many squares in FEN row {}", row + 1));
+                }
+                col += skip as i8;
+            } else {
+                let Some((piece, color)) = piece_from_fen_char(c) else {
+                    return Err(format!("Invalid piece {c} in FEN"));
+                };
+                if col > 7 {
+                    return Err(format!("Too many squares in FEN row {}", row + 1));
+                }
+                if piece == Pawn && (row == 0 || row == 7) {
+                    return Err(format!("Pawn on the last row at {}", Pos::new(col, row)));
+                }
+                board.set(col, row, piece, color);
+                col += 1;
+            }
+        }
+        if col != 8 {
+            return Err(format!("Expected 8 squares in FEN row {}, got {col}", row + 1));
+        }
+    }
+    Ok(board)
+}
+
+fn parse_castling_rights(s: &str) -> Result<CastlingRights, String> {
+    let mut rights = CastlingRights::none();
+    if s == "-" {
+        return Ok(rights);
+    }
+    for c in s.chars() {
+        match c {
+            'K' => rights.white_kingside = true,
+            'Q' => rights.white_queenside = true,
+            'k' => rights.black_kingside = true,
+            'q' => rights.black_queenside = true,
+            other => return Err(format!("Invalid castling right {other}")),
+        }
+    }
+    Ok(rights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::r#move::Move;
+
+    #[test]
+    fn fen_round_trip() -> Result<(), String> {
+        let fens = [
+            STARTING_POSITION_FEN,
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ];
+        for fen in fens {
+            assert_eq!(Game::from_fen(fen)?.to_fen(), fen);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn fen_tracks_moves() -> Result<(), String> {
+        let mut game = Game::new();
+        for mv in ["e2e4", "c7c5", "g1f3"] {
+            game.make_move(&Move::from_long_notation(mv))?;
+        }
+        assert_eq!(
+            game.to_fen(),
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fen_rejects_invalid_positions() {
+        for fen in [
+            // the side not to move is in check
+            "7k/8/6KQ/8/8/8/8/8 w - - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 0",
+            "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8p/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/99999999999999999999/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/080/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        ] {
+            assert!(Game::from_fen(fen).is_err(), "{fen}");
+        }
+        assert!(Game::from_fen("7k/8/6KQ/8/8/8/8/8 b - - 0 1").is_ok());
+    }
+
+    #[test]
+    fn fen_castling_and_en_passant_rights() -> Result<(), String> {
+        let game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1")?;
+        assert!(game.validate_move(&Move::from_long_notation("e1g1")).is_ok());
+        assert!(game.validate_move(&Move::from_long_notation("e1c1")).is_err());
+
+        let game = Game::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2")?;
+        assert!(game.validate_move(&Move::from_long_notation("e5d6")).is_ok());
+        let game = Game::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 2")?;
+        assert!(game.validate_move(&Move::from_long_notation("e5d6")).is_err());
+        Ok(())
+    }
+}
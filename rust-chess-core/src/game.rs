@@ -6,6 +6,7 @@ use crate::pos::Pos;
 use regex::Regex;
 use std::sync::LazyLock;
 
+#[derive(Clone)]
 pub struct Game {
     board: Board,
     turn: PieceColor,
@@ -13,6 +14,8 @@ pub struct Game {
     is_check: bool,
     history: GameHistory,
     result: Option<GameResult>,
+    // number of half-moves since the last capture or pawn move
+    halfmove_clock: u32,
 }
 
 impl Game {
@@ -24,19 +27,24 @@ impl Game {
             is_check: false,
             turn: White,
             result: None,
+            halfmove_clock: 0,
         };
         game.collect_possible_moves();
         game
     }
 
     pub fn from_board(board: Board, turn: PieceColor) -> Game {
+        let mut history = GameHistory::new();
+        history.initial_state = Some(board);
+        history.initial_turn = Some(turn);
         let mut game = Game {
-            history: GameHistory::new(),
+            history,
             board,
             possible_moves: Vec::new(),
             is_check: false,
             turn,
             result: None,
+            halfmove_clock: 0,
         };
         game.collect_game_state();
         game
@@ -50,7 +58,9 @@ impl Game {
             is_check: false,
             turn,
             result: None,
+            halfmove_clock: 0,
         };
+        game.halfmove_clock = game.history.initial_halfmove_clock;
         game.collect_game_state();
         game
     }
@@ -69,6 +79,65 @@ impl Game {
 
     pub fn is_check(&self) -> bool { self.is_check }
 
+    pub fn turn(&self) -> PieceColor {
+        self.turn
+    }
+
+    /// All legal moves in the current position. Promotions are only listed as queen promotions.
+    pub fn possible_moves(&self) -> &[Move] {
+        &self.possible_moves
+    }
+
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    /// Number of the current full move, starting at 1 and incremented after Black's move
+    pub fn fullmove_number(&self) -> u32 {
+        let initial_turn = self.history.initial_turn.unwrap_or(White);
+        let plies = self.history.moves.len() as u32 + if initial_turn == Black { 1 } else { 0 };
+        self.history.initial_fullmove_number + plies / 2
+    }
+
+    /// Castling rights that are still available, i.e. neither the king nor the corresponding rook has moved
+    /// or been captured. Doesn't check if castling is possible in the current position.
+    pub fn castling_rights(&self) -> CastlingRights {
+        let mut rights = self.history.initial_castling_rights;
+        for mv in &self.history.moves {
+            for (col, row) in [mv.from(), mv.to()] {
+                match (col, row) {
+                    (4, 0) => {
+                        rights.white_kingside = false;
+                        rights.white_queenside = false;
+                    }
+                    (4, 7) => {
+                        rights.black_kingside = false;
+                        rights.black_queenside = false;
+                    }
+                    (7, 0) => rights.white_kingside = false,
+                    (0, 0) => rights.white_queenside = false,
+                    (7, 7) => rights.black_kingside = false,
+                    (0, 7) => rights.black_queenside = false,
+                    _ => {}
+                }
+            }
+        }
+        rights
+    }
+
+    /// Returns the square that can be captured onto en passant, if the last move was a two-step pawn move.
+    /// Doesn't check if there is a pawn that can actually make the capture.
+    pub fn en_passant_target(&self) -> Option<Pos> {
+        let Some(last_move) = self.history.moves.last() else {
+            return self.history.initial_en_passant;
+        };
+        let (piece_type, _) = self.board.at(last_move.to_col, last_move.to_row).piece()?;
+        if piece_type != PieceType::Pawn || last_move.from_row.abs_diff(last_move.to_row) != 2 {
+            return None;
+        }
+        Some(Pos::new(last_move.to_col, (last_move.from_row + last_move.to_row) / 2))
+    }
+
     pub fn make_move(&mut self, mv: &Move) -> Result<(), &'static str> {
         if self.result.is_some() {
             return Err("Game is over");
@@ -77,6 +146,14 @@ impl Game {
         // Validate the move
         self.validate_move(&mv)?;
 
+        let is_capture = self.board.at(mv.to_col, mv.to_row).is_occupied();
+        let is_pawn_move = self.board.at(mv.from_col, mv.from_row).piece_type() == Some(PieceType::Pawn);
+        if is_capture || is_pawn_move {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+
         // Update the board
         self.board.make_move(mv);
 
@@ -92,6 +169,31 @@ impl Game {
         Ok(())
     }
 
+    /// Takes back the last move by replaying the history from the initial position.
+    /// Returns the move that was taken back, or None if there is nothing to take back.
+    pub fn undo_move(&mut self) -> Option<Move> {
+        let mut moves = self.history.moves.clone();
+        let last_move = moves.pop()?;
+        let mut game = self.initial_game();
+        for mv in &moves {
+            // can fail if the history doesn't start from the initial position, e.g. when it was only partially provided
+            game.make_move(mv).ok()?;
+        }
+        *self = game;
+        Some(last_move)
+    }
+
+    /// Returns a game in the position this game started from, without any moves made
+    pub fn initial_game(&self) -> Game {
+        let Some(board) = self.history.initial_state else {
+            return Game::new();
+        };
+        let turn = self.history.initial_turn.unwrap_or(White);
+        let mut history = self.history.clone();
+        history.moves.clear();
+        Game::from_board_with_history(board, turn, history)
+    }
+
     pub fn validate_move(&self, mv: &Move) -> Result<(), &'static str> {
         if mv.from_col > 7 || mv.from_row > 7 || mv.to_col > 7 || mv.to_row > 7 {
             return Err("Out of bounds");
@@ -311,6 +413,78 @@ impl Game {
         }
     }
 
+    /// Formats a legal move in short algebraic notation, e.g. "Nbd7", "exd5", "e8=Q+" or "O-O-O#"
+    pub fn to_short_notation(&self, mv: &Move) -> Result<String, &'static str> {
+        self.validate_move(mv)?;
+        let Some((piece_type, _)) = self.board.at(mv.from_col, mv.from_row).piece() else {
+            return Err("No piece to move");
+        };
+        let is_capture = self.board.at(mv.to_col, mv.to_row).is_occupied()
+            || self.board.is_en_passant_move(mv).is_some();
+        let to = Pos::new(mv.to_col, mv.to_row);
+
+        let mut notation = if piece_type == PieceType::King && self.board.is_castle_move(mv).is_some() {
+            if mv.to_col == 6 { "O-O".to_string() } else { "O-O-O".to_string() }
+        } else if piece_type == PieceType::Pawn {
+            let mut notation = String::new();
+            if is_capture {
+                notation.push((b'a' + mv.from_col as u8) as char);
+                notation.push('x');
+            }
+            notation.push_str(&to.to_string());
+            if let Some(promotion) = mv.promotion_to {
+                notation.push_str(&format!("={}", promotion));
+            }
+            notation
+        } else {
+            // other pieces of the same type that can move to the same square
+            let mut same_col = false;
+            let mut same_row = false;
+            let mut ambiguous = false;
+            for col in 0..8 {
+                for row in 0..8 {
+                    if (col, row) == (mv.from_col, mv.from_row)
+                        || self.board.at(col, row).piece() != Some((piece_type, self.turn))
+                        || self.validate_move(&Move::new(col, row, mv.to_col, mv.to_row)).is_err()
+                    {
+                        continue;
+                    }
+                    ambiguous = true;
+                    same_col |= col == mv.from_col;
+                    same_row |= row == mv.from_row;
+                }
+            }
+            let from = Pos::new(mv.from_col, mv.from_row).to_string();
+            let disambiguation = match (ambiguous, same_col, same_row) {
+                (false, _, _) => "",
+                (true, false, _) => &from[0..1],
+                (true, true, false) => &from[1..2],
+                (true, true, true) => &from,
+            };
+            format!("{}{}{}{}", piece_type, disambiguation, if is_capture { "x" } else { "" }, to)
+        };
+
+        let mut after = self.clone();
+        after.make_move(mv)?;
+        if after.result.as_ref().is_some_and(|result| result.winner.is_some()) {
+            notation.push('#');
+        } else if after.is_check {
+            notation.push('+');
+        }
+        Ok(notation)
+    }
+
+    /// Formats a sequence of moves starting from this position, e.g. a principal variation
+    pub fn moves_to_short_notation(&self, moves: &[Move]) -> Result<Vec<String>, &'static str> {
+        let mut game = self.clone();
+        let mut notations = Vec::with_capacity(moves.len());
+        for mv in moves {
+            notations.push(game.to_short_notation(mv)?);
+            game.make_move(mv)?;
+        }
+        Ok(notations)
+    }
+
     pub fn get_moves_from(&self, col: i8, row: i8) -> &[Move] {
         let mut from = 0;
         while from < self.possible_moves.len()
@@ -329,6 +503,52 @@ impl Game {
         &self.possible_moves[from..to]
     }
 
+    /// Ends the game because `color` ran out of time. The opponent wins, unless they can't checkmate
+    /// with their material, then it is a draw. Does nothing if the game is already over.
+    pub fn time_out(&mut self, color: PieceColor) {
+        if self.result.is_some() {
+            return;
+        }
+        let opponent = color.opposite();
+        self.result = Some(GameResult {
+            winner: if self.has_insufficient_material(opponent) { None } else { Some(opponent) },
+            termination: Termination::Timeout,
+        });
+    }
+
+    /// True if `color` can't checkmate by any series of legal moves (FIDE rule 6.9), judged by the material only.
+    /// Pawns, rooks and queens can always mate, and so can two minor pieces unless they are bishops of one shade.
+    /// That leaves three cases where mate is impossible:
+    /// - a lone king;
+    /// - a single knight, when the opponent has nothing but queens. The knight needs the opponent's own pieces
+    ///   to take the flight squares of their cornered king, and a queen there always guards the squares
+    ///   the knight would check from;
+    /// - bishops all on squares of one shade, when the opponent has nothing but bishops of that shade,
+    ///   so nothing can block a flight square of the other shade.
+    pub fn has_insufficient_material(&self, color: PieceColor) -> bool {
+        // pieces other than the kings, with the shade of their square
+        let mut own = Vec::new();
+        let mut opponent = Vec::new();
+        for col in 0..8 {
+            for row in 0..8 {
+                match self.board.at(col, row).piece() {
+                    Some((PieceType::King, _)) | None => {}
+                    Some((piece, piece_color)) if piece_color == color => own.push((piece, (col + row) % 2)),
+                    Some((piece, _)) => opponent.push((piece, (col + row) % 2)),
+                }
+            }
+        }
+        match own.as_slice() {
+            [] => true,
+            [(PieceType::Knight, _)] => opponent.iter().all(|&(piece, _)| piece == PieceType::Queen),
+            [(PieceType::Bishop, shade), ..] => own
+                .iter()
+                .chain(&opponent)
+                .all(|&(piece, other_shade)| piece == PieceType::Bishop && other_shade == *shade),
+            _ => false,
+        }
+    }
+
     pub fn get_moves_from_pos(&self, pos: Pos) -> &[Move] {
         self.get_moves_from(pos.col(), pos.row())
     }
@@ -342,9 +562,13 @@ impl Game {
             if self.is_check {
                 self.result = Some(GameResult {
                     winner: Some(self.turn.opposite()),
+                    termination: Termination::Checkmate,
                 })
             } else {
-                self.result = Some(GameResult { winner: None })
+                self.result = Some(GameResult {
+                    winner: None,
+                    termination: Termination::Stalemate,
+                })
             }
         }
     }
@@ -377,8 +601,18 @@ impl Game {
                 } else {
                     (mv.to_col, 1)
                 };
-                let Some(last_move) = self.history.moves.last() else {
+                // the captured pawn must have just made a double step, a king step to the same square doesn't count
+                let passed_sq = self.board.at(en_passant_pos.col(), en_passant_pos.row());
+                let en_passant_row = if color == White { 4 } else { 3 };
+                if mv.from_row != en_passant_row || passed_sq.piece() != Some((PieceType::Pawn, color.opposite())) {
                     return Err("Invalid move");
+                }
+                let Some(last_move) = self.history.moves.last() else {
+                    // the game may start from a position where en passant is possible
+                    return match self.history.initial_en_passant {
+                        Some(target) if target.tuple() == mv.to() => Ok(false),
+                        _ => Err("Invalid move"),
+                    };
                 };
                 if last_move.from() != en_passant_move_from
                     || last_move.to() != en_passant_pos.tuple()
@@ -412,16 +646,13 @@ impl Game {
         }
 
         // Validate that rook or king haven't moved
-        let had_moves_from_rook_or_king = self.history.moves.iter().any(|h_mv| {
-            // on the same row as king and either king col or rook col
-            h_mv.from_row == mv.from_row
-                && (h_mv.from_col == mv.from_col || h_mv.from_col == old_rook_pos.col())
-        });
-        if had_moves_from_rook_or_king {
-            return false;
+        let rights = self.castling_rights();
+        match (self.turn, old_rook_pos.col()) {
+            (White, 7) => rights.white_kingside,
+            (White, _) => rights.white_queenside,
+            (Black, 7) => rights.black_kingside,
+            (Black, _) => rights.black_queenside,
         }
-
-        true
     }
 
     fn collect_possible_moves(&mut self) {
@@ -447,35 +678,100 @@ impl Game {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct GameResult {
     // None in case of a draw
     pub winner: Option<PieceColor>,
+    pub termination: Termination,
+}
+
+/// How the game ended
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    // a player ran out of time, see `Game::time_out`
+    Timeout,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CastlingRights {
+    pub white_kingside: bool,
+    pub white_queenside: bool,
+    pub black_kingside: bool,
+    pub black_queenside: bool,
+}
+
+impl CastlingRights {
+    pub fn all() -> CastlingRights {
+        CastlingRights {
+            white_kingside: true,
+            white_queenside: true,
+            black_kingside: true,
+            black_queenside: true,
+        }
+    }
+
+    pub fn none() -> CastlingRights {
+        CastlingRights {
+            white_kingside: false,
+            white_queenside: false,
+            black_kingside: false,
+            black_queenside: false,
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct GameHistory {
     // Only set if the initial state is not the standard chess starting position
     initial_state: Option<Board>,
     initial_turn: Option<PieceColor>,
+    // The rest of the initial state, only differs from the defaults for games started from a FEN
+    initial_castling_rights: CastlingRights,
+    initial_en_passant: Option<Pos>,
+    initial_halfmove_clock: u32,
+    initial_fullmove_number: u32,
     moves: Vec<Move>,
 }
 
 impl GameHistory {
     pub fn new() -> GameHistory {
-        GameHistory {
-            initial_state: None,
-            initial_turn: None,
-            moves: Vec::new(),
-        }
+        Self::with_moves(Vec::new())
     }
 
     pub fn with_moves(moves: Vec<Move>) -> GameHistory {
         GameHistory {
             initial_state: None,
             initial_turn: None,
+            initial_castling_rights: CastlingRights::all(),
+            initial_en_passant: None,
+            initial_halfmove_clock: 0,
+            initial_fullmove_number: 1,
             moves,
         }
     }
 
+    /// History of a game started from an arbitrary position with no moves made yet
+    pub fn with_initial_state(
+        board: Board,
+        turn: PieceColor,
+        castling_rights: CastlingRights,
+        en_passant: Option<Pos>,
+        halfmove_clock: u32,
+        fullmove_number: u32,
+    ) -> GameHistory {
+        GameHistory {
+            initial_state: Some(board),
+            initial_turn: Some(turn),
+            initial_castling_rights: castling_rights,
+            initial_en_passant: en_passant,
+            initial_halfmove_clock: halfmove_clock,
+            initial_fullmove_number: fullmove_number,
+            moves: Vec::new(),
+        }
+    }
+
     pub fn moves(&self) -> &Vec<Move> {
         &self.moves
     }
@@ -487,4 +783,20 @@ impl GameHistory {
     pub fn initial_turn(&self) -> &Option<PieceColor> {
         &self.initial_turn
     }
+
+    pub fn initial_castling_rights(&self) -> CastlingRights {
+        self.initial_castling_rights
+    }
+
+    pub fn initial_en_passant(&self) -> Option<Pos> {
+        self.initial_en_passant
+    }
+
+    pub fn initial_halfmove_clock(&self) -> u32 {
+        self.initial_halfmove_clock
+    }
+
+    pub fn initial_fullmove_number(&self) -> u32 {
+        self.initial_fullmove_number
+    }
 }
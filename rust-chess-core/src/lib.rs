@@ -1,5 +1,23 @@
 pub mod board;
+pub mod book_builder;
+pub mod clock;
+pub mod display;
+pub mod eval;
+pub mod fen;
 pub mod game;
+pub mod mate_solver;
+pub mod mcts;
 mod piece_moves_iterator;
 pub mod r#move;
+pub mod pgn;
+pub mod polyglot;
+mod polyglot_keys;
 pub mod pos;
+pub mod problem;
+pub mod random;
+pub mod search;
+pub mod skill;
+pub mod syzygy;
+pub mod tablebase;
+pub mod time_manager;
+pub mod transposition_table;
@@ -0,0 +1,230 @@
+use crate::board::{PieceColor, PieceType};
+use crate::game::Game;
+use crate::r#move::Move;
+use std::fmt::Write;
+
+/// A move of the side that gives mate. If `defences` is empty, the move mates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttackerMove {
+    pub mv: Move,
+    pub defences: Vec<DefenceMove>,
+}
+
+/// A reply of the defending side, with every attacker move that still mates in time
+#[derive(Clone, Debug, PartialEq)]
+pub struct DefenceMove {
+    pub mv: Move,
+    pub continuations: Vec<AttackerMove>,
+}
+
+/// Result of a mate-in-N search. `keys` lists all first moves that force mate in at most N moves,
+/// so an empty list proves there is no such mate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MateSolution {
+    pub moves: u32,
+    pub keys: Vec<AttackerMove>,
+    // move sequences after which the attacker has more than one way to continue
+    pub duals: Vec<Vec<Move>>,
+    pub nodes: u64,
+}
+
+impl MateSolution {
+    pub fn is_mate(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// A problem is cooked if there is more than one key move
+    pub fn is_cooked(&self) -> bool {
+        self.keys.len() > 1
+    }
+
+    /// Formats the solution tree in short algebraic notation, one variation per line, e.g.
+    /// ```text
+    /// 1. Qg7+!
+    ///   1... Kxg7 2. Rh7#
+    /// ```
+    /// `game` must be the position that was solved.
+    pub fn format_tree(&self, game: &Game) -> String {
+        let mut output = String::new();
+        for key in &self.keys {
+            format_attacker_move(&mut output, game, key, 0, true);
+        }
+        output
+    }
+}
+
+/// Proves a forced mate in at most `moves` moves for the side to move, or proves there is none.
+/// All moves are tried, including underpromotions, so the search is exhaustive and only practical for small N.
+pub fn solve_mate(game: &Game, moves: u32) -> MateSolution {
+    let mut solver = MateSolver { nodes: 0, duals: Vec::new() };
+    let mut path = Vec::new();
+    let keys = solver.mating_moves(game, moves, &mut path);
+    MateSolution {
+        moves,
+        keys,
+        duals: solver.duals,
+        nodes: solver.nodes,
+    }
+}
+
+/// All legal moves, including the underpromotions `Game::possible_moves` leaves out
+pub fn all_legal_moves(game: &Game) -> Vec<Move> {
+    let mut moves = Vec::with_capacity(game.possible_moves().len());
+    for mv in game.possible_moves() {
+        moves.push(*mv);
+        if mv.promotion_to.is_some() {
+            for piece in [PieceType::Rook, PieceType::Bishop, PieceType::Knight] {
+                moves.push(Move {
+                    promotion_to: Some(piece),
+                    ..*mv
+                });
+            }
+        }
+    }
+    moves
+}
+
+/// The side to move is checkmated
+pub fn is_checkmate(game: &Game) -> bool {
+    game.result().as_ref().is_some_and(|result| result.winner.is_some())
+}
+
+struct MateSolver {
+    nodes: u64,
+    duals: Vec<Vec<Move>>,
+}
+
+impl MateSolver {
+    /// All attacker moves that mate in at most `moves` moves, with their full solution trees
+    fn mating_moves(&mut self, game: &Game, moves: u32, path: &mut Vec<Move>) -> Vec<AttackerMove> {
+        let mut result = Vec::new();
+        for (mv, child) in self.attacker_children(game) {
+            if is_checkmate(&child) {
+                result.push(AttackerMove { mv, defences: Vec::new() });
+                continue;
+            }
+            if moves <= 1 || child.result().is_some() || !self.all_defences_lose(&child, moves - 1) {
+                continue;
+            }
+
+            // the move works, now collect the whole tree below it
+            path.push(mv);
+            let mut defences = Vec::new();
+            for defence in all_legal_moves(&child) {
+                let grandchild = self.play(&child, &defence);
+                path.push(defence);
+                let continuations = self.mating_moves(&grandchild, moves - 1, path);
+                if continuations.len() > 1 {
+                    self.duals.push(path.clone());
+                }
+                path.pop();
+                defences.push(DefenceMove {
+                    mv: defence,
+                    continuations,
+                });
+            }
+            path.pop();
+            result.push(AttackerMove { mv, defences });
+        }
+        result
+    }
+
+    /// Checks that the attacker, to move, can mate in at most `moves` moves. Stops at the first mating move.
+    fn can_mate(&mut self, game: &Game, moves: u32) -> bool {
+        let children = self.attacker_children(game);
+        if children.iter().any(|(_, child)| is_checkmate(child)) {
+            return true;
+        }
+        moves > 1
+            && children
+                .iter()
+                .any(|(_, child)| child.result().is_none() && self.all_defences_lose(child, moves - 1))
+    }
+
+    /// Checks that every defence allows a mate in at most `moves` moves. Stops at the first refutation.
+    fn all_defences_lose(&mut self, game: &Game, moves: u32) -> bool {
+        all_legal_moves(game).iter().all(|defence| {
+            let child = self.play(game, defence);
+            self.can_mate(&child, moves)
+        })
+    }
+
+    // Checks first, they are the most likely to mate and restrict the defence the most
+    fn attacker_children(&mut self, game: &Game) -> Vec<(Move, Game)> {
+        let mut children = all_legal_moves(game)
+            .into_iter()
+            .map(|mv| (mv, self.play(game, &mv)))
+            .collect::<Vec<_>>();
+        children.sort_by_key(|(_, child)| !child.is_check());
+        children
+    }
+
+    fn play(&mut self, game: &Game, mv: &Move) -> Game {
+        self.nodes += 1;
+        let mut child = game.clone();
+        child.make_move(mv).expect("Solver should only make legal moves");
+        child
+    }
+}
+
+fn format_attacker_move(output: &mut String, game: &Game, attacker: &AttackerMove, indent: usize, is_key: bool) {
+    let notation = game.to_short_notation(&attacker.mv).unwrap_or_else(|_| attacker.mv.to_string());
+    let _ = write!(output, "{}{}", move_number(game), notation);
+    if is_key {
+        output.push('!');
+    }
+    if attacker.defences.is_empty() {
+        output.push('\n');
+        return;
+    }
+
+    let mut after = game.clone();
+    after.make_move(&attacker.mv).expect("Solution moves are legal");
+    output.push('\n');
+    for defence in &attacker.defences {
+        let notation = after.to_short_notation(&defence.mv).unwrap_or_else(|_| defence.mv.to_string());
+        let _ = write!(output, "{:indent$}{}{}", "", move_number(&after), notation, indent = indent + 2);
+        let mut after_defence = after.clone();
+        after_defence.make_move(&defence.mv).expect("Solution moves are legal");
+        for (i, continuation) in defence.continuations.iter().enumerate() {
+            // duals are listed under each other
+            if i > 0 {
+                let _ = write!(output, "{:indent$}", "", indent = indent + 2);
+                output.push_str("  ... ");
+            } else {
+                output.push(' ');
+            }
+            format_attacker_move(output, &after_defence, continuation, indent + 2, false);
+        }
+    }
+}
+
+fn move_number(game: &Game) -> String {
+    match game.turn() {
+        PieceColor::White => format!("{}. ", game.fullmove_number()),
+        PieceColor::Black => format!("{}... ", game.fullmove_number()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proves_there_is_no_mate() -> Result<(), String> {
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1")?;
+        let solution = solve_mate(&game, 1);
+        assert!(!solution.is_mate());
+        Ok(())
+    }
+
+    #[test]
+    fn finds_underpromotion_mates() -> Result<(), String> {
+        // only the knight promotion mates
+        let game = Game::from_fen("6br/5Ppk/7p/5P2/8/8/8/K7 w - - 0 1")?;
+        let solution = solve_mate(&game, 1);
+        assert_eq!(solution.keys.len(), 1);
+        assert_eq!(solution.keys[0].mv, Move::from_long_notation("f7f8n"));
+        Ok(())
+    }
+}
@@ -0,0 +1,398 @@
+use crate::eval::evaluate_for;
+use crate::game::Game;
+use crate::r#move::Move;
+use crate::random::Random;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_ITERATIONS: u64 = 10_000;
+pub const DEFAULT_ROLLOUT_PLIES: usize = 40;
+
+// Centipawns of evaluation that map to about 0.46 in the [-1, 1] value range
+const EVAL_SCALE: f64 = 400.0;
+// Centipawns by which a move must be better to be about e times as likely in the PUCT priors
+const PRIOR_TEMPERATURE: f64 = 100.0;
+// How often (in iterations) the clock is checked
+const TIME_CHECK_INTERVAL: u64 = 64;
+
+/// How the tree descends to a leaf
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Selection {
+    /// UCB1 applied to trees, every move is tried once before any is tried again
+    Uct { exploration: f64 },
+    /// AlphaZero-style selection guided by move priors, here derived from the static evaluation after the move
+    Puct { exploration: f64 },
+}
+
+impl Selection {
+    pub fn uct() -> Selection {
+        Selection::Uct {
+            exploration: std::f64::consts::SQRT_2,
+        }
+    }
+
+    pub fn puct() -> Selection {
+        Selection::Puct { exploration: 1.5 }
+    }
+}
+
+/// How a new leaf is valued
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LeafValue {
+    /// Plays random moves until the game ends or for at most `max_plies`, then evaluates the position reached
+    Rollout { max_plies: usize },
+    /// Static evaluation of the leaf
+    Evaluation,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct MctsOptions {
+    pub selection: Selection,
+    pub leaf_value: LeafValue,
+}
+
+impl Default for MctsOptions {
+    fn default() -> MctsOptions {
+        MctsOptions {
+            selection: Selection::puct(),
+            leaf_value: LeafValue::Evaluation,
+        }
+    }
+}
+
+/// Limits of a single search. If neither is set, DEFAULT_ITERATIONS are run.
+#[derive(Clone, Default, Debug)]
+pub struct MctsLimits {
+    pub iterations: Option<u64>,
+    pub move_time: Option<Duration>,
+}
+
+impl MctsLimits {
+    pub fn iterations(iterations: u64) -> MctsLimits {
+        MctsLimits {
+            iterations: Some(iterations),
+            ..Default::default()
+        }
+    }
+
+    pub fn move_time(move_time: Duration) -> MctsLimits {
+        MctsLimits {
+            move_time: Some(move_time),
+            ..Default::default()
+        }
+    }
+}
+
+/// What the search found out about a move at the root
+#[derive(Clone, Debug, PartialEq)]
+pub struct RootMoveStats {
+    pub mv: Move,
+    pub visits: u32,
+    // average value for the side to move at the root, from -1 (lost) to 1 (won)
+    pub value: f64,
+    // probability the selection gave the move before searching it, uniform with UCT
+    pub prior: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct MctsResult {
+    // the most visited move, None if there are no legal moves
+    pub best_move: Option<Move>,
+    // most visited first
+    pub root_moves: Vec<RootMoveStats>,
+    pub iterations: u64,
+    // visits of the root kept from the previous search
+    pub reused_visits: u32,
+    pub time: Duration,
+}
+
+impl MctsResult {
+    /// One line per root move with its visits, share of the visits, value and prior.
+    /// `game` must be the position the search was started from.
+    pub fn format_report(&self, game: &Game) -> String {
+        let total: u32 = self.root_moves.iter().map(|stats| stats.visits).sum();
+        let mut report = String::new();
+        for stats in &self.root_moves {
+            let notation = game.to_short_notation(&stats.mv).unwrap_or_else(|_| stats.mv.to_string());
+            let share = if total > 0 { 100.0 * stats.visits as f64 / total as f64 } else { 0.0 };
+            let _ = writeln!(
+                report,
+                "{:<8} visits {:>7} ({:>5.1}%)  value {:+.3}  prior {:.3}",
+                notation, stats.visits, share, stats.value, stats.prior
+            );
+        }
+        report
+    }
+}
+
+struct Node {
+    // None for the root
+    mv: Option<Move>,
+    prior: f64,
+    visits: u32,
+    // sum of the values from the point of view of the side that played `mv`
+    value_sum: f64,
+    children: Vec<usize>,
+    expanded: bool,
+}
+
+impl Node {
+    fn new(mv: Option<Move>, prior: f64) -> Node {
+        Node {
+            mv,
+            prior,
+            visits: 0,
+            value_sum: 0.0,
+            children: Vec::new(),
+            expanded: false,
+        }
+    }
+
+    fn average_value(&self) -> f64 {
+        if self.visits == 0 { 0.0 } else { self.value_sum / self.visits as f64 }
+    }
+}
+
+/// Plays moves chosen by Monte Carlo tree search, as an alternative to the alpha-beta `Search`.
+///
+/// The tree is kept between searches: when the next position follows from the previous root by moves
+/// that were already in the tree, the search continues from that subtree.
+pub struct MctsPlayer {
+    options: MctsOptions,
+    random: Random,
+    // the root is at index 0
+    tree: Vec<Node>,
+    // position of the root, None before the first search
+    root_game: Option<Game>,
+}
+
+impl MctsPlayer {
+    pub fn new(options: MctsOptions) -> MctsPlayer {
+        MctsPlayer {
+            options,
+            random: Random::from_time(),
+            tree: Vec::new(),
+            root_game: None,
+        }
+    }
+
+    /// Makes the searches repeatable
+    pub fn set_seed(&mut self, seed: u64) {
+        self.random = Random::new(seed);
+    }
+
+    /// Forgets the tree, e.g. for a new game
+    pub fn clear(&mut self) {
+        self.tree.clear();
+        self.root_game = None;
+    }
+
+    pub fn search(&mut self, game: &Game, limits: &MctsLimits) -> MctsResult {
+        let start = Instant::now();
+        self.reuse_tree(game);
+        let reused_visits = self.tree[0].visits;
+
+        let max_iterations = match limits {
+            MctsLimits { iterations: None, move_time: None } => Some(DEFAULT_ITERATIONS),
+            _ => limits.iterations,
+        };
+        let mut iterations = 0;
+        while game.result().is_none() && max_iterations.is_none_or(|max| iterations < max) {
+            if iterations % TIME_CHECK_INTERVAL == 0
+                && iterations > 0
+                && limits.move_time.is_some_and(|move_time| start.elapsed() >= move_time)
+            {
+                break;
+            }
+            self.iterate(game);
+            iterations += 1;
+        }
+
+        let mut root_moves: Vec<RootMoveStats> = self.tree[0]
+            .children
+            .iter()
+            .map(|&child| {
+                let node = &self.tree[child];
+                RootMoveStats {
+                    mv: node.mv.expect("Only the root has no move"),
+                    visits: node.visits,
+                    value: node.average_value(),
+                    prior: node.prior,
+                }
+            })
+            .collect();
+        root_moves.sort_by(|a, b| b.visits.cmp(&a.visits).then(b.value.total_cmp(&a.value)));
+        MctsResult {
+            best_move: root_moves.first().map(|stats| stats.mv),
+            root_moves,
+            iterations,
+            reused_visits,
+            time: start.elapsed(),
+        }
+    }
+
+    /// Keeps the subtree of the new position if it follows from the previous root, otherwise starts a new tree
+    fn reuse_tree(&mut self, game: &Game) {
+        let new_moves = self.root_game.as_ref().and_then(|root_game| {
+            let old_moves = root_game.history().moves();
+            let moves = game.history().moves();
+            let same_start = moves.starts_with(old_moves)
+                && root_game.initial_game().to_fen() == game.initial_game().to_fen();
+            same_start.then(|| moves[old_moves.len()..].to_vec())
+        });
+        let root = new_moves.and_then(|new_moves| {
+            new_moves.iter().try_fold(0, |node, mv| {
+                self.tree[node].children.iter().copied().find(|&child| self.tree[child].mv == Some(*mv))
+            })
+        });
+
+        match root {
+            Some(0) => {}
+            Some(root) => {
+                let mut tree = Vec::new();
+                self.copy_subtree(root, &mut tree);
+                tree[0].mv = None;
+                self.tree = tree;
+            }
+            None => self.tree = vec![Node::new(None, 1.0)],
+        }
+        self.root_game = Some(game.clone());
+    }
+
+    fn copy_subtree(&mut self, node: usize, tree: &mut Vec<Node>) -> usize {
+        let index = tree.len();
+        let children = std::mem::take(&mut self.tree[node].children);
+        let old = &self.tree[node];
+        tree.push(Node {
+            mv: old.mv,
+            prior: old.prior,
+            visits: old.visits,
+            value_sum: old.value_sum,
+            children: Vec::new(),
+            expanded: old.expanded,
+        });
+        for child in children {
+            let copied = self.copy_subtree(child, tree);
+            tree[index].children.push(copied);
+        }
+        index
+    }
+
+    /// Selects a path down to a leaf, expands it, values it and backs the value up
+    fn iterate(&mut self, root_game: &Game) {
+        let mut game = root_game.clone();
+        let mut path = vec![0];
+        let mut node = 0;
+        let value = loop {
+            if let Some(value) = terminal_value(&game) {
+                break value;
+            }
+            if !self.tree[node].expanded {
+                self.expand(node, &game);
+                break self.leaf_value(&game);
+            }
+            node = self.select(node);
+            let mv = self.tree[node].mv.expect("Only the root has no move");
+            game.make_move(&mv).expect("Tree moves should be legal");
+            path.push(node);
+        };
+
+        // `value` is for the side to move at the leaf, each node keeps it for the side that moved into it
+        let mut value = -value;
+        for &node in path.iter().rev() {
+            let node = &mut self.tree[node];
+            node.visits += 1;
+            node.value_sum += value;
+            value = -value;
+        }
+    }
+
+    fn expand(&mut self, node: usize, game: &Game) {
+        let moves = game.possible_moves();
+        let priors = match self.options.selection {
+            Selection::Uct { .. } => vec![1.0 / moves.len() as f64; moves.len()],
+            Selection::Puct { .. } => move_priors(game),
+        };
+        for (mv, prior) in moves.iter().zip(priors) {
+            let child = self.tree.len();
+            self.tree.push(Node::new(Some(*mv), prior));
+            self.tree[node].children.push(child);
+        }
+        self.tree[node].expanded = true;
+    }
+
+    fn select(&self, node: usize) -> usize {
+        let parent = &self.tree[node];
+        let parent_visits = parent.visits.max(1) as f64;
+        let score = |child: &Node| match self.options.selection {
+            Selection::Uct { exploration } => {
+                if child.visits == 0 {
+                    return f64::INFINITY;
+                }
+                child.average_value() + exploration * (parent_visits.ln() / child.visits as f64).sqrt()
+            }
+            Selection::Puct { exploration } => {
+                child.average_value() + exploration * child.prior * parent_visits.sqrt() / (1.0 + child.visits as f64)
+            }
+        };
+        parent
+            .children
+            .iter()
+            .copied()
+            .max_by(|&a, &b| score(&self.tree[a]).total_cmp(&score(&self.tree[b])))
+            .expect("An expanded node that is not terminal has children")
+    }
+
+    /// Value of the position for the side to move, from -1 to 1
+    fn leaf_value(&mut self, game: &Game) -> f64 {
+        match self.options.leaf_value {
+            LeafValue::Evaluation => evaluation_value(game),
+            LeafValue::Rollout { max_plies } => {
+                let mut rollout = game.clone();
+                for _ in 0..max_plies {
+                    if terminal_value(&rollout).is_some() {
+                        break;
+                    }
+                    let moves = rollout.possible_moves();
+                    let mv = moves[self.random.below(moves.len() as u64) as usize];
+                    rollout.make_move(&mv).expect("Possible moves should be legal");
+                }
+                let value = terminal_value(&rollout).unwrap_or_else(|| evaluation_value(&rollout));
+                if rollout.turn() == game.turn() { value } else { -value }
+            }
+        }
+    }
+}
+
+/// Value of a finished game for the side to move: a mated side has lost, everything else is a draw
+fn terminal_value(game: &Game) -> Option<f64> {
+    match game.result() {
+        Some(result) if result.winner.is_some() => Some(-1.0),
+        Some(_) => Some(0.0),
+        None if game.halfmove_clock() >= 100 => Some(0.0),
+        None => None,
+    }
+}
+
+/// Static evaluation for the side to move, squashed into -1..1
+fn evaluation_value(game: &Game) -> f64 {
+    let score = evaluate_for(game.board(), game.turn()) as f64;
+    2.0 / (1.0 + (-score / EVAL_SCALE).exp()) - 1.0
+}
+
+/// Softmax of the static evaluation after each move
+fn move_priors(game: &Game) -> Vec<f64> {
+    let scores: Vec<f64> = game
+        .possible_moves()
+        .iter()
+        .map(|mv| {
+            let mut board = *game.board();
+            board.make_move(mv);
+            evaluate_for(&board, game.turn()) as f64 / PRIOR_TEMPERATURE
+        })
+        .collect();
+    let max = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let weights: Vec<f64> = scores.iter().map(|score| (score - max).exp()).collect();
+    let sum: f64 = weights.iter().sum();
+    weights.iter().map(|weight| weight / sum).collect()
+}
@@ -61,27 +61,36 @@ impl Move {
     }
 
     pub fn from_long_notation(s: &str) -> Move {
-        if s.len() < 4 {
-            panic!("Invalid move notation");
+        match Self::parse_long_notation(s) {
+            Ok(mv) => mv,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Parses a move in long algebraic notation, e.g. "e2e4" or "e7e8q", as used by UCI.
+    /// Unlike `from_long_notation`, returns an error instead of panicking on invalid input.
+    pub fn parse_long_notation(s: &str) -> Result<Move, &'static str> {
+        if s.len() != 4 && s.len() != 5 {
+            return Err("Invalid move notation");
+        }
+        if !s.is_ascii() {
+            return Err("Invalid move notation");
         }
 
-        let from_col = s.chars().nth(0).unwrap() as i8 - 'a' as i8;
-        let from_row = s.chars().nth(1).unwrap() as i8 - '1' as i8;
-        let to_col = s.chars().nth(2).unwrap() as i8 - 'a' as i8;
-        let to_row = s.chars().nth(3).unwrap() as i8 - '1' as i8;
+        let from = Pos::from_notation(&s[0..2])?;
+        let to = Pos::from_notation(&s[2..4])?;
 
         if s.len() == 5 {
-            let promotion_char = s.chars().nth(4).unwrap();
-            let promotion_to = match promotion_char {
-                'q' | 'Q' => PieceType::Queen,
-                'r' | 'R' => PieceType::Rook,
-                'b' | 'B' => PieceType::Bishop,
-                'n' | 'N' => PieceType::Knight,
-                _ => panic!("Invalid promotion piece type"),
+            let promotion_to = match s.as_bytes()[4] {
+                b'q' | b'Q' => PieceType::Queen,
+                b'r' | b'R' => PieceType::Rook,
+                b'b' | b'B' => PieceType::Bishop,
+                b'n' | b'N' => PieceType::Knight,
+                _ => return Err("Invalid promotion piece type"),
             };
-            Move::with_promotion(from_col, from_row, to_col, to_row, promotion_to)
+            Ok(Move::with_promotion_from_pos(&from, &to, promotion_to))
         } else {
-            Move::new(from_col, from_row, to_col, to_row)
+            Ok(Move::from_pos(&from, &to))
         }
     }
 
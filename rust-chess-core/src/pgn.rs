@@ -0,0 +1,770 @@
+use crate::board::PieceColor::White;
+use crate::game::{Game, Termination};
+use std::fmt::{Display, Formatter};
+
+// Written movetext is wrapped at this width, as the PGN standard recommends
+const PGN_LINE_WIDTH: usize = 80;
+
+// Tags every game should have, the "Seven Tag Roster" of the PGN standard
+const REQUIRED_TAGS: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// A single game read from a PGN file. Only the main line is kept, variations are skipped.
+#[derive(Clone, Debug, Default)]
+pub struct PgnGame {
+    pub tags: Vec<PgnTag>,
+    // comment before the first move
+    pub initial_comment: Option<String>,
+    pub moves: Vec<PgnMove>,
+    // game termination marker from the movetext: "1-0", "0-1", "1/2-1/2" or "*"
+    pub result: Option<String>,
+    // position of the termination marker, 0 if there is none
+    pub result_line: usize,
+    pub result_column: usize,
+    // line where the game starts
+    pub line: usize,
+    // problems in the game text the parser had to skip
+    pub syntax_errors: Vec<PgnError>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnTag {
+    pub name: String,
+    pub value: String,
+    // position of the opening '['
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnMove {
+    // the move in short algebraic notation, without annotations like "!?"
+    pub san: String,
+    pub comment: Option<String>,
+    // numeric annotation glyphs, e.g. 1 for "$1" or "!"
+    pub nags: Vec<u8>,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl PgnMove {
+    /// The move with its annotations, e.g. "Nf3!?" or "e4 $14". The NAGs of "!", "?", "!!", "??", "!?"
+    /// and "?!" are written as those suffixes, the others as "$n".
+    pub fn annotated_san(&self) -> String {
+        let mut text = self.san.clone();
+        for nag in &self.nags {
+            match nag {
+                1 => text.push('!'),
+                2 => text.push('?'),
+                3 => text.push_str("!!"),
+                4 => text.push_str("??"),
+                5 => text.push_str("!?"),
+                6 => text.push_str("?!"),
+                _ => text.push_str(&format!(" ${}", nag)),
+            }
+        }
+        text
+    }
+}
+
+impl Display for PgnError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|tag| tag.name == name)
+            .map(|tag| tag.value.as_str())
+    }
+
+    /// Returns the game in the starting position, which is set by the FEN tag if present
+    pub fn initial_game(&self) -> Result<Game, PgnError> {
+        match self.tag("FEN") {
+            Some(fen) => Game::from_fen(fen).map_err(|err| PgnError {
+                message: format!("Invalid FEN tag: {}", err),
+                line: self.line,
+                column: 1,
+            }),
+            None => Ok(Game::new()),
+        }
+    }
+
+    /// Plays all the moves of the game and returns the final position.
+    /// The error points to the move that couldn't be played.
+    pub fn replay(&self) -> Result<Game, PgnError> {
+        self.replay_with(|_, _, _| {})
+    }
+
+    /// Same as `replay`, but calls `on_move` with the position before each move, the move and its PGN data
+    pub fn replay_with(
+        &self,
+        mut on_move: impl FnMut(&Game, &crate::r#move::Move, &PgnMove),
+    ) -> Result<Game, PgnError> {
+        let mut game = self.initial_game()?;
+        for pgn_move in &self.moves {
+            let error = |message: String| PgnError {
+                message,
+                line: pgn_move.line,
+                column: pgn_move.column,
+            };
+            let mv = game
+                .parse_short_notation(&pgn_move.san)
+                .map_err(|err| error(format!("Can't parse move {}: {}", pgn_move.san, err)))?;
+            on_move(&game, &mv, pgn_move);
+            game.make_move(&mv)
+                .map_err(|err| error(format!("Illegal move {}: {}", pgn_move.san, err)))?;
+        }
+        Ok(game)
+    }
+
+    /// Looks for everything wrong with the game: syntax errors, missing or malformed tags, moves that can't be
+    /// played, check and checkmate suffixes that don't match the position, and a result that contradicts
+    /// the final position or the Result tag. A missing tag is reported where it would be added, on the line
+    /// after the last tag.
+    pub fn validate(&self) -> Vec<PgnError> {
+        let mut errors = self.syntax_errors.clone();
+        let tag_error = |message: String, tag: &PgnTag| PgnError {
+            message,
+            line: tag.line,
+            column: tag.column,
+        };
+
+        let after_tags = self.tags.last().map_or(self.line, |tag| tag.line + 1);
+        for name in REQUIRED_TAGS {
+            if self.tag(name).is_none() {
+                errors.push(PgnError {
+                    message: format!("Missing tag {}", name),
+                    line: after_tags,
+                    column: 1,
+                });
+            }
+        }
+        for (i, tag) in self.tags.iter().enumerate() {
+            if self.tags[..i].iter().any(|other| other.name == tag.name) {
+                errors.push(tag_error(format!("Duplicate tag {}", tag.name), tag));
+            }
+            match tag.name.as_str() {
+                "Date" if !is_valid_date(&tag.value) => errors.push(tag_error(
+                    format!("Date tag {} isn't in the format YYYY.MM.DD", tag.value),
+                    tag,
+                )),
+                "Result" if !RESULTS.contains(&tag.value.as_str()) => {
+                    errors.push(tag_error(format!("Invalid Result tag {}", tag.value), tag))
+                }
+                _ => {}
+            }
+        }
+        let tag_result = self.tag("Result");
+
+        let result_error = |message: String| {
+            let (line, column) = match self.moves.last() {
+                _ if self.result_line > 0 => (self.result_line, self.result_column),
+                Some(last_move) => (last_move.line, last_move.column),
+                None => (self.line, 1),
+            };
+            PgnError { message, line, column }
+        };
+        match (&self.result, tag_result) {
+            (None, _) => errors.push(result_error("Missing game termination marker".to_string())),
+            (Some(result), Some(tag_result)) if result != tag_result => errors.push(result_error(format!(
+                "Termination marker {} doesn't match the Result tag {}",
+                result, tag_result
+            ))),
+            _ => {}
+        }
+
+        let replayed = self.replay_with(|game, mv, pgn_move| {
+            let mut after = game.clone();
+            if after.make_move(mv).is_ok()
+                && let Some(err) = suffix_error(&after, pgn_move)
+            {
+                errors.push(err);
+            }
+        });
+        let game = match replayed {
+            Ok(game) => game,
+            Err(err) => {
+                errors.push(err);
+                return errors;
+            }
+        };
+        let expected = match game.result() {
+            Some(result) if result.termination == Termination::Checkmate => match result.winner {
+                Some(White) => Some(("1-0", "checkmate")),
+                _ => Some(("0-1", "checkmate")),
+            },
+            Some(result) if result.termination == Termination::Stalemate => Some(("1/2-1/2", "stalemate")),
+            _ => None,
+        };
+        if let Some((expected, termination)) = expected
+            && let Some(declared) = self.result.as_deref().or(tag_result)
+            && declared != expected
+        {
+            errors.push(result_error(format!(
+                "The game ends in {}, the result should be {} instead of {}",
+                termination, expected, declared
+            )));
+        }
+        errors
+    }
+}
+
+/// Reports a '+' or '#' suffix of the move that doesn't match the position `after` it
+fn suffix_error(after: &Game, pgn_move: &PgnMove) -> Option<PgnError> {
+    let san = &pgn_move.san;
+    let is_mate = after.result().as_ref().is_some_and(|result| result.termination == Termination::Checkmate);
+    let written = match san.chars().last() {
+        Some('#') => "#",
+        Some('+') => "+",
+        _ => "",
+    };
+    let message = match (written, is_mate, after.is_check()) {
+        ("#", true, _) | ("+", false, true) | ("", false, false) => return None,
+        (_, true, _) => format!("{} is checkmate and should end with '#'", san),
+        ("#", false, true) => format!("{} is marked as checkmate but only gives check", san),
+        (_, false, true) => format!("{} gives check and should end with '+'", san),
+        (_, false, false) => format!("{} is marked with '{}' but doesn't give check", san, written),
+    };
+    Some(PgnError {
+        message,
+        line: pgn_move.line,
+        column: pgn_move.column,
+    })
+}
+
+/// True for dates like "2024.03.17", with '?' for unknown digits
+fn is_valid_date(date: &str) -> bool {
+    date.len() == 10
+        && date.char_indices().all(|(i, c)| match i {
+            4 | 7 => c == '.',
+            _ => c.is_ascii_digit() || c == '?',
+        })
+}
+
+/// The moves of the game with move numbers, e.g. "1. e4 e5 2. Nf3". A game started with Black to move begins with "1...".
+pub fn format_moves(game: &Game) -> String {
+    move_tokens(game).join(" ")
+}
+
+/// Writes the game as PGN with the given tags and the result ("1-0", "0-1", "1/2-1/2" or "*").
+/// The Result tag is taken from `result`, and FEN and SetUp tags are added if the game didn't start
+/// from the initial position.
+pub fn write_pgn(game: &Game, tags: &[(&str, &str)], result: &str) -> String {
+    let mut text = String::new();
+    let mut write_tag = |name: &str, value: &str| {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        text.push_str(&format!("[{} \"{}\"]\n", name, value));
+    };
+    for (name, value) in tags {
+        if !matches!(*name, "Result" | "SetUp" | "FEN") {
+            write_tag(name, value);
+        }
+    }
+    write_tag("Result", result);
+    let initial_fen = game.initial_game().to_fen();
+    if initial_fen != Game::new().to_fen() {
+        write_tag("SetUp", "1");
+        write_tag("FEN", &initial_fen);
+    }
+
+    text.push('\n');
+    let mut line = String::new();
+    for token in move_tokens(game).into_iter().chain([result.to_string()]) {
+        if !line.is_empty() && line.len() + 1 + token.len() > PGN_LINE_WIDTH {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    text.push_str(&line);
+    text.push('\n');
+    text
+}
+
+// move numbers and moves in short algebraic notation
+fn move_tokens(game: &Game) -> Vec<String> {
+    let mut replay = game.initial_game();
+    let mut tokens = Vec::new();
+    for mv in game.history().moves() {
+        if replay.turn() == White {
+            tokens.push(format!("{}.", replay.fullmove_number()));
+        } else if tokens.is_empty() {
+            tokens.push(format!("{}...", replay.fullmove_number()));
+        }
+        tokens.push(replay.to_short_notation(mv).unwrap_or_else(|_| mv.to_string()));
+        if replay.make_move(mv).is_err() {
+            break;
+        }
+    }
+    tokens
+}
+
+/// Parses all games in a PGN text. The parser is lenient: it skips what it can't understand,
+/// recording the problems in `PgnGame::syntax_errors`, so that one broken game doesn't hide the rest of the file.
+pub fn parse_pgn(text: &str) -> Vec<PgnGame> {
+    PgnParser::new(text).parse()
+}
+
+struct PgnParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+    games: Vec<PgnGame>,
+    current: Option<PgnGame>,
+}
+
+impl<'a> PgnParser<'a> {
+    fn new(text: &'a str) -> PgnParser<'a> {
+        PgnParser {
+            chars: text.chars().peekable(),
+            line: 1,
+            column: 1,
+            games: Vec::new(),
+            current: None,
+        }
+    }
+
+    fn parse(mut self) -> Vec<PgnGame> {
+        while let Some(&c) = self.chars.peek() {
+            let (line, column) = (self.line, self.column);
+            match c {
+                c if c.is_whitespace() => {
+                    self.next();
+                }
+                // escape mechanism, the rest of the line is ignored
+                '%' if column == 1 => self.skip_line(),
+                '[' => {
+                    // tags after the movetext start a new game
+                    if self.current.as_ref().is_some_and(|game| !game.moves.is_empty()) {
+                        self.finish_game();
+                    }
+                    self.parse_tag(line, column);
+                }
+                '{' => {
+                    let comment = self.parse_brace_comment(line, column);
+                    self.add_comment(comment, line);
+                }
+                ';' => {
+                    self.next();
+                    let comment = self.take_while(|c| c != '\n');
+                    self.add_comment(comment, line);
+                }
+                '(' => self.skip_variation(line, column),
+                ')' => {
+                    self.next();
+                    self.error("Unexpected ')'", line, column);
+                }
+                '$' => {
+                    self.next();
+                    let nag = self.take_while(|c| c.is_ascii_digit());
+                    match nag.parse() {
+                        Ok(nag) => self.add_nag(nag, line),
+                        Err(_) => self.error("Invalid NAG", line, column),
+                    }
+                }
+                _ => {
+                    let token = self.take_while(|c| !c.is_whitespace() && !"{}()[];$".contains(c));
+                    if token.is_empty() {
+                        // a lone closing bracket or brace
+                        self.next();
+                        self.error(&format!("Unexpected '{}'", c), line, column);
+                    } else {
+                        self.parse_movetext_token(&token, line, column);
+                    }
+                }
+            }
+        }
+        if self.current.is_some() {
+            self.finish_game();
+        }
+        self.games
+    }
+
+    fn parse_tag(&mut self, line: usize, column: usize) {
+        self.next();
+        self.skip_spaces();
+        let name = self.take_while(|c| !c.is_whitespace() && c != ']' && c != '"');
+        self.skip_spaces();
+        match (name.is_empty(), self.chars.peek()) {
+            (false, Some('"')) => {}
+            (true, _) | (false, Some(']' | '\n') | None) => {
+                self.error("Tag must have a name and a value", line, column);
+                self.skip_tag_rest();
+                return;
+            }
+            _ => {
+                self.error("Tag value must be in quotes", line, column);
+                self.skip_tag_rest();
+                return;
+            }
+        }
+
+        // the value is read up to its closing quote, so that it can contain ']'
+        self.next();
+        let mut value = String::new();
+        loop {
+            match self.chars.peek().copied() {
+                Some('"') => {
+                    self.next();
+                    break;
+                }
+                Some('\\') => {
+                    self.next();
+                    match self.chars.peek().copied() {
+                        Some(c @ ('"' | '\\')) => {
+                            self.next();
+                            value.push(c);
+                        }
+                        _ => value.push('\\'),
+                    }
+                }
+                Some('\n') | None => {
+                    self.error("Tag value is not closed with '\"'", line, column);
+                    return;
+                }
+                Some(c) => {
+                    self.next();
+                    value.push(c);
+                }
+            }
+        }
+        self.skip_spaces();
+        if self.chars.peek() == Some(&']') {
+            self.next();
+        } else {
+            self.error("Tag is not closed with ']'", line, column);
+            self.skip_tag_rest();
+        }
+        self.game(line).tags.push(PgnTag {
+            name,
+            value,
+            line,
+            column,
+        });
+    }
+
+    /// Skips the rest of a broken tag, up to its ']' on the same line
+    fn skip_tag_rest(&mut self) {
+        self.take_while(|c| c != ']' && c != '\n');
+        if self.chars.peek() == Some(&']') {
+            self.next();
+        }
+    }
+
+    fn parse_brace_comment(&mut self, line: usize, column: usize) -> String {
+        self.next();
+        let comment = self.take_while(|c| c != '}');
+        if self.chars.peek() == Some(&'}') {
+            self.next();
+        } else {
+            self.error("Comment is not closed with '}'", line, column);
+        }
+        comment
+    }
+
+    fn skip_variation(&mut self, line: usize, column: usize) {
+        let mut depth = 0;
+        while let Some(c) = self.next() {
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                // parentheses inside comments don't count
+                '{' => {
+                    self.take_while(|c| c != '}');
+                    self.next();
+                }
+                _ => {}
+            }
+        }
+        self.error("Variation is not closed with ')'", line, column);
+    }
+
+    fn parse_movetext_token(&mut self, token: &str, line: usize, column: usize) {
+        if RESULTS.contains(&token) {
+            let game = self.game(line);
+            game.result = Some(token.to_string());
+            game.result_line = line;
+            game.result_column = column;
+            self.finish_game();
+            return;
+        }
+
+        // move numbers like "12." or "12...", possibly glued to the move as in "12.e4"
+        let without_number = token.trim_start_matches(|c: char| c.is_ascii_digit());
+        let san = if without_number.len() < token.len() && without_number.starts_with('.') {
+            without_number.trim_start_matches('.')
+        } else {
+            token
+        };
+        if san.is_empty() {
+            return;
+        }
+        let column = column + (token.len() - san.len());
+
+        // suffix annotations are stored as the corresponding NAGs
+        let annotation_start = san.find(['!', '?']).unwrap_or(san.len());
+        let (san, annotation) = san.split_at(annotation_start);
+        let nag = match annotation {
+            "" => None,
+            "!" => Some(1),
+            "?" => Some(2),
+            "!!" => Some(3),
+            "??" => Some(4),
+            "!?" => Some(5),
+            "?!" => Some(6),
+            _ => {
+                self.error(&format!("Unknown annotation {}", annotation), line, column);
+                None
+            }
+        };
+        if san.is_empty() || !san.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            self.error(&format!("Unexpected token {}", token), line, column);
+            return;
+        }
+
+        self.game(line).moves.push(PgnMove {
+            san: san.to_string(),
+            comment: None,
+            nags: nag.into_iter().collect(),
+            line,
+            column,
+        });
+    }
+
+    fn add_comment(&mut self, comment: String, line: usize) {
+        let comment = comment.split_whitespace().collect::<Vec<_>>().join(" ");
+        let game = self.game(line);
+        let target = match game.moves.last_mut() {
+            Some(last_move) => &mut last_move.comment,
+            None => &mut game.initial_comment,
+        };
+        match target {
+            Some(existing) => {
+                existing.push(' ');
+                existing.push_str(&comment);
+            }
+            None => *target = Some(comment),
+        }
+    }
+
+    fn add_nag(&mut self, nag: u8, line: usize) {
+        if let Some(last_move) = self.game(line).moves.last_mut() {
+            last_move.nags.push(nag);
+        }
+    }
+
+    /// Returns the game being parsed, starting a new one if needed
+    fn game(&mut self, line: usize) -> &mut PgnGame {
+        self.current.get_or_insert_with(|| PgnGame {
+            line,
+            ..Default::default()
+        })
+    }
+
+    fn finish_game(&mut self) {
+        if let Some(game) = self.current.take() {
+            self.games.push(game);
+        }
+    }
+
+    fn error(&mut self, message: &str, line: usize, column: usize) {
+        self.game(line).syntax_errors.push(PgnError {
+            message: message.to_string(),
+            line,
+            column,
+        });
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut result = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !predicate(c) {
+                break;
+            }
+            result.push(c);
+            self.next();
+        }
+        result
+    }
+
+    fn skip_spaces(&mut self) {
+        self.take_while(|c| c == ' ' || c == '\t');
+    }
+
+    fn skip_line(&mut self) {
+        self.take_while(|c| c != '\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PGN: &str = r#"[Event "Test"]
+[White "Alice \"A\""]
+[Black "Bob"]
+[Result "1-0"]
+
+{Opening comment} 1. e4 e5 2.Nf3!? Nc6 (2... d6 {Philidor (old)} 3. d4) 3. Bb5 $1 a6?
+; rest of line comment
+4. Ba4 1-0
+
+[Event "Second"]
+
+1. d4 d5 *
+"#;
+
+    #[test]
+    fn parses_tags_moves_and_comments() {
+        let games = parse_pgn(PGN);
+        assert_eq!(games.len(), 2);
+
+        let game = &games[0];
+        assert_eq!(game.tag("White"), Some("Alice \"A\""));
+        assert_eq!(game.result.as_deref(), Some("1-0"));
+        assert_eq!(game.initial_comment.as_deref(), Some("Opening comment"));
+        let sans: Vec<&str> = game.moves.iter().map(|mv| mv.san.as_str()).collect();
+        assert_eq!(sans, ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4"]);
+        assert_eq!(game.moves[2].nags, vec![5]);
+        assert_eq!(game.moves[2].annotated_san(), "Nf3!?");
+        assert_eq!(game.moves[4].nags, vec![1]);
+        assert_eq!(game.moves[5].comment.as_deref(), Some("rest of line comment"));
+        assert_eq!((game.moves[2].line, game.moves[2].column), (6, 30));
+        assert!(game.syntax_errors.is_empty());
+
+        assert_eq!(games[1].tag("Event"), Some("Second"));
+        assert_eq!(games[1].moves.len(), 2);
+        assert_eq!(games[1].result.as_deref(), Some("*"));
+    }
+
+    #[test]
+    fn replays_and_reports_illegal_moves() {
+        let games = parse_pgn("1. e4 e5 2. Nf3 Nc6 *");
+        let game = games[0].replay().unwrap();
+        assert_eq!(game.history().moves().len(), 4);
+
+        let games = parse_pgn("1. e4 e5\n2. Ke3 *");
+        let Err(error) = games[0].replay() else { panic!("Ke3 is illegal") };
+        assert_eq!((error.line, error.column), (2, 4));
+    }
+
+    #[test]
+    fn writes_games_that_parse_back() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K2R b K - 0 12").unwrap();
+        for san in ["Kd7", "O-O", "Ke6", "e4"] {
+            let mv = game.parse_short_notation(san).unwrap();
+            game.make_move(&mv).unwrap();
+        }
+        assert_eq!(format_moves(&game), "12... Kd7 13. O-O Ke6 14. e4");
+
+        let text = write_pgn(&game, &[("White", "Alice \"A\""), ("Result", "1-0")], "*");
+        assert!(text.ends_with("\n\n12... Kd7 13. O-O Ke6 14. e4 *\n"));
+        let parsed = &parse_pgn(&text)[0];
+        assert_eq!(parsed.tag("White"), Some("Alice \"A\""));
+        assert_eq!(parsed.tag("Result"), Some("*"));
+        assert_eq!(parsed.tag("FEN"), Some("4k3/8/8/8/8/8/4P3/4K2R b K - 0 12"));
+        assert_eq!(parsed.replay().unwrap().to_fen(), game.to_fen());
+
+        // long games are wrapped
+        let mut game = Game::new();
+        for _ in 0..20 {
+            for san in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+                let mv = game.parse_short_notation(san).unwrap();
+                game.make_move(&mv).unwrap();
+            }
+        }
+        let text = write_pgn(&game, &[], "1/2-1/2");
+        assert!(text.lines().all(|line| line.len() <= PGN_LINE_WIDTH));
+        assert_eq!(parse_pgn(&text)[0].moves.len(), 80);
+    }
+
+    #[test]
+    fn validates_tags_suffixes_and_results() {
+        let tags = "[Event \"?\"]\n[Site \"?\"]\n[Date \"2024.??.??\"]\n[Round \"1\"]\n[White \"A\"]\n[Black \"B\"]\n";
+        let games = parse_pgn(&format!("{}[Result \"0-1\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n", tags));
+        assert!(games[0].validate().is_empty());
+
+        let text = format!(
+            "{}[Result \"1-0\"]\n\n1. f3 e5 2. g4+ Qh4+ 1-0\n\n{}\n\n1. e4 e5 2. Ke3 *",
+            tags, "[Event \"Second\"]\n  [Date \"2024.01.01\"] [Date \"May 1\"]"
+        );
+        let games = parse_pgn(&text);
+        let messages = |game: &PgnGame| -> Vec<(usize, usize, String)> {
+            game.validate().into_iter().map(|err| (err.line, err.column, err.message)).collect()
+        };
+        assert_eq!(
+            messages(&games[0]),
+            [
+                (9, 13, "g4+ is marked with '+' but doesn't give check".to_string()),
+                (9, 17, "Qh4+ is checkmate and should end with '#'".to_string()),
+                (9, 22, "The game ends in checkmate, the result should be 0-1 instead of 1-0".to_string()),
+            ]
+        );
+        let errors = messages(&games[1]);
+        assert_eq!(errors.len(), 8);
+        // missing tags belong after the last one
+        assert_eq!(errors[0], (13, 1, "Missing tag Site".to_string()));
+        assert_eq!(errors[5], (12, 23, "Duplicate tag Date".to_string()));
+        assert_eq!(errors[6], (12, 23, "Date tag May 1 isn't in the format YYYY.MM.DD".to_string()));
+        assert_eq!(errors[7], (14, 13, "Can't parse move Ke3: No valid move found".to_string()));
+    }
+
+    #[test]
+    fn reads_tag_values_with_brackets_and_quotes() {
+        let games = parse_pgn("[Event \"Blitz [rated]\"]\n[White \"A \\\"]\\\" B\"] [Black \"C:\\\\\"]\n\n1. e4 *");
+        let game = &games[0];
+        assert_eq!(game.tag("Event"), Some("Blitz [rated]"));
+        assert_eq!(game.tag("White"), Some("A \"]\" B"));
+        assert_eq!(game.tag("Black"), Some("C:\\"));
+        assert_eq!((game.tags[2].line, game.tags[2].column), (2, 21));
+        assert!(game.syntax_errors.is_empty());
+
+        let games = parse_pgn("[Event \"Open\n[Site Nowhere]\n[Round \"1\" x]\n1. e4 *");
+        let errors: Vec<&str> = games[0].syntax_errors.iter().map(|err| err.message.as_str()).collect();
+        assert_eq!(
+            errors,
+            ["Tag value is not closed with '\"'", "Tag value must be in quotes", "Tag is not closed with ']'"]
+        );
+        assert_eq!(games[0].tag("Round"), Some("1"));
+        assert_eq!(games[0].moves.len(), 1);
+    }
+
+    #[test]
+    fn recovers_from_syntax_errors() {
+        let games = parse_pgn("[Event \"Broken\"]\n\n1. e4 ) e5 {unclosed");
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].moves.len(), 2);
+        assert_eq!(games[0].syntax_errors.len(), 2);
+    }
+}
@@ -0,0 +1,349 @@
+use crate::board::PieceColor::{Black, White};
+use crate::board::PieceType::{Bishop, King, Knight, Pawn, Queen, Rook};
+use crate::board::{Board, PieceColor, PieceType};
+use crate::game::Game;
+use crate::polyglot_keys::POLYGLOT_RANDOM;
+use crate::r#move::Move;
+use crate::random::Random;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const ENTRY_SIZE: u64 = 16;
+const CASTLING_OFFSET: usize = 768;
+const EN_PASSANT_OFFSET: usize = 772;
+const TURN_OFFSET: usize = 780;
+
+/// Computes the hash of the position as defined by the Polyglot book format
+pub fn polyglot_hash(game: &Game) -> u64 {
+    let board = game.board();
+    let mut hash = 0;
+    for row in 0..8 {
+        for col in 0..8 {
+            if let Some((piece, color)) = board.at(col, row).piece() {
+                hash ^= POLYGLOT_RANDOM[64 * piece_kind(piece, color) + 8 * row as usize + col as usize];
+            }
+        }
+    }
+
+    let rights = game.castling_rights();
+    let in_place = |col: i8, row: i8, piece: PieceType, color: PieceColor| {
+        board.at(col, row).piece() == Some((piece, color))
+    };
+    let castling = [
+        rights.white_kingside && in_place(4, 0, King, White) && in_place(7, 0, Rook, White),
+        rights.white_queenside && in_place(4, 0, King, White) && in_place(0, 0, Rook, White),
+        rights.black_kingside && in_place(4, 7, King, Black) && in_place(7, 7, Rook, Black),
+        rights.black_queenside && in_place(4, 7, King, Black) && in_place(0, 7, Rook, Black),
+    ];
+    for (i, has_right) in castling.into_iter().enumerate() {
+        if has_right {
+            hash ^= POLYGLOT_RANDOM[CASTLING_OFFSET + i];
+        }
+    }
+
+    // en passant only counts if a pawn of the side to move stands next to the pawn that just made a two-step move
+    if let Some(target) = game.en_passant_target() {
+        let pawn_row = if game.turn() == White { 4 } else { 3 };
+        let can_capture = [target.col() - 1, target.col() + 1].into_iter().any(|col| {
+            (0..8).contains(&col) && board.at(col, pawn_row).piece() == Some((Pawn, game.turn()))
+        });
+        if can_capture {
+            hash ^= POLYGLOT_RANDOM[EN_PASSANT_OFFSET + target.col() as usize];
+        }
+    }
+
+    if game.turn() == White {
+        hash ^= POLYGLOT_RANDOM[TURN_OFFSET];
+    }
+    hash
+}
+
+// Polyglot orders pieces as black pawn, white pawn, black knight, white knight, ..., black king, white king
+fn piece_kind(piece: PieceType, color: PieceColor) -> usize {
+    let piece_index = match piece {
+        Pawn => 0,
+        Knight => 1,
+        Bishop => 2,
+        Rook => 3,
+        Queen => 4,
+        King => 5,
+    };
+    2 * piece_index + if color == White { 1 } else { 0 }
+}
+
+/// A raw entry of a Polyglot book
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BookEntry {
+    pub key: u64,
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    pub fn from_bytes(bytes: &[u8; ENTRY_SIZE as usize]) -> BookEntry {
+        BookEntry {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; ENTRY_SIZE as usize] {
+        let mut bytes = [0; ENTRY_SIZE as usize];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+/// Decodes a Polyglot move on the given board. Polyglot encodes castling as the king capturing its own rook,
+/// so such moves are converted to the usual king moves.
+pub fn decode_move(raw_move: u16, board: &Board) -> Move {
+    let to_col = (raw_move & 0x7) as i8;
+    let to_row = ((raw_move >> 3) & 0x7) as i8;
+    let from_col = ((raw_move >> 6) & 0x7) as i8;
+    let from_row = ((raw_move >> 9) & 0x7) as i8;
+    let promotion = match (raw_move >> 12) & 0x7 {
+        1 => Some(Knight),
+        2 => Some(Bishop),
+        3 => Some(Rook),
+        4 => Some(Queen),
+        _ => None,
+    };
+
+    if let Some(promotion) = promotion {
+        return Move::with_promotion(from_col, from_row, to_col, to_row, promotion);
+    }
+
+    let is_king = board.at(from_col, from_row).piece_type() == Some(King);
+    let to_col = match (is_king, from_col, to_col) {
+        (true, 4, 7) => 6,
+        (true, 4, 0) => 2,
+        _ => to_col,
+    };
+    Move::new(from_col, from_row, to_col, to_row)
+}
+
+/// Encodes a move the way Polyglot does, see `decode_move`
+pub fn encode_move(mv: &Move, board: &Board) -> u16 {
+    let to_col = if board.is_castle_move(mv).is_some() {
+        if mv.to_col == 6 { 7 } else { 0 }
+    } else {
+        mv.to_col
+    };
+    let promotion = match mv.promotion_to {
+        Some(Knight) => 1,
+        Some(Bishop) => 2,
+        Some(Rook) => 3,
+        Some(Queen) => 4,
+        _ => 0,
+    };
+    (to_col as u16) | (mv.to_row as u16) << 3 | (mv.from_col as u16) << 6 | (mv.from_row as u16) << 9 | promotion << 12
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BookMove {
+    pub mv: Move,
+    pub weight: u16,
+}
+
+/// How to choose a move when the book has several for a position
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BookSelection {
+    /// Always the move with the highest weight
+    Best,
+    /// A random move with probability proportional to its weight
+    WeightedRandom,
+}
+
+/// Reader of opening books in the Polyglot `.bin` format. Entries are sorted by key,
+/// so they are looked up by binary search directly in the file instead of loading the whole book.
+pub struct PolyglotBook<R: Read + Seek> {
+    reader: R,
+    entry_count: u64,
+}
+
+impl PolyglotBook<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<PolyglotBook<BufReader<File>>> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> PolyglotBook<R> {
+    pub fn new(mut reader: R) -> io::Result<PolyglotBook<R>> {
+        let size = reader.seek(SeekFrom::End(0))?;
+        if size % ENTRY_SIZE != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Polyglot book size must be a multiple of 16 bytes",
+            ));
+        }
+        Ok(PolyglotBook {
+            reader,
+            entry_count: size / ENTRY_SIZE,
+        })
+    }
+
+    pub fn entry_count(&self) -> u64 {
+        self.entry_count
+    }
+
+    /// Returns all entries with the given key
+    pub fn entries(&mut self, key: u64) -> io::Result<Vec<BookEntry>> {
+        // find the first entry with a key that is not less than the given one
+        let mut low = 0;
+        let mut high = self.entry_count;
+        while low < high {
+            let middle = (low + high) / 2;
+            if self.read_entry(middle)?.key < key {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        let mut entries = Vec::new();
+        for index in low..self.entry_count {
+            let entry = self.read_entry(index)?;
+            if entry.key != key {
+                break;
+            }
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    /// Returns the legal book moves for the position, sorted by weight, highest first
+    pub fn moves(&mut self, game: &Game) -> io::Result<Vec<BookMove>> {
+        let mut moves: Vec<BookMove> = self
+            .entries(polyglot_hash(game))?
+            .into_iter()
+            .map(|entry| BookMove {
+                mv: decode_move(entry.raw_move, game.board()),
+                weight: entry.weight,
+            })
+            // protects against hash collisions and broken books
+            .filter(|book_move| game.validate_move(&book_move.mv).is_ok())
+            .collect();
+        moves.sort_by_key(|book_move| std::cmp::Reverse(book_move.weight));
+        Ok(moves)
+    }
+
+    /// Chooses a book move for the position, None if the position is not in the book
+    pub fn choose_move(
+        &mut self,
+        game: &Game,
+        selection: BookSelection,
+        random: &mut Random,
+    ) -> io::Result<Option<Move>> {
+        let moves = self.moves(game)?;
+        let chosen = match selection {
+            BookSelection::Best => moves.first(),
+            BookSelection::WeightedRandom => {
+                let total: u64 = moves.iter().map(|book_move| book_move.weight as u64).sum();
+                if total == 0 {
+                    // all weights are zero, so every move is equally good
+                    moves.get(random.below(moves.len().max(1) as u64) as usize)
+                } else {
+                    let mut pick = random.below(total);
+                    moves.iter().find(|book_move| {
+                        if pick < book_move.weight as u64 {
+                            return true;
+                        }
+                        pick -= book_move.weight as u64;
+                        false
+                    })
+                }
+            }
+        };
+        Ok(chosen.map(|book_move| book_move.mv))
+    }
+
+    fn read_entry(&mut self, index: u64) -> io::Result<BookEntry> {
+        let mut bytes = [0; ENTRY_SIZE as usize];
+        self.reader.seek(SeekFrom::Start(index * ENTRY_SIZE))?;
+        self.reader.read_exact(&mut bytes)?;
+        Ok(BookEntry::from_bytes(&bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn game_after(moves: &[&str]) -> Game {
+        let mut game = Game::new();
+        for mv in moves {
+            game.make_move(&Move::from_long_notation(mv)).unwrap();
+        }
+        game
+    }
+
+    // test positions from the specification of the format
+    #[test]
+    fn hashes_match_the_specification() {
+        let cases: [(&[&str], u64); 9] = [
+            (&[], 0x463b96181691fc9c),
+            (&["e2e4"], 0x823c9b50fd114196),
+            (&["e2e4", "d7d5"], 0x0756b94461c50fb0),
+            (&["e2e4", "d7d5", "e4e5"], 0x662fafb965db29d4),
+            (&["e2e4", "d7d5", "e4e5", "f7f5"], 0x22a48b5a8e47ff78),
+            (&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2"], 0x652a607ca3f242c1),
+            (&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"], 0x00fdd303c946bdd9),
+            (&["a2a4", "b7b5", "h2h4", "b5b4", "c2c4"], 0x3c8123ea7b067637),
+            (&["a2a4", "b7b5", "h2h4", "b5b4", "c2c4", "b4c3", "a1a3"], 0x5c3f9b829b279560),
+        ];
+        for (moves, expected) in cases {
+            assert_eq!(polyglot_hash(&game_after(moves)), expected, "after {:?}", moves);
+        }
+    }
+
+    #[test]
+    fn decodes_castling_as_king_move() {
+        let game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        // e1h1: to col 7, from col 4, both rows are 0
+        let raw = 7 | 4 << 6;
+        let mv = decode_move(raw, game.board());
+        assert_eq!(mv, Move::from_long_notation("e1g1"));
+        assert_eq!(encode_move(&mv, game.board()), raw);
+    }
+
+    #[test]
+    fn reads_moves_from_book() -> io::Result<()> {
+        let start = Game::new();
+        let board = start.board();
+        let key = polyglot_hash(&start);
+        let mut entries = [
+            BookEntry { key: key - 1, raw_move: 0, weight: 1, learn: 0 },
+            BookEntry { key, raw_move: encode_move(&Move::from_long_notation("d2d4"), board), weight: 10, learn: 0 },
+            BookEntry { key, raw_move: encode_move(&Move::from_long_notation("e2e4"), board), weight: 30, learn: 0 },
+            BookEntry { key: key + 1, raw_move: 0, weight: 1, learn: 0 },
+        ];
+        entries.sort_by_key(|entry| entry.key);
+        let bytes: Vec<u8> = entries.iter().flat_map(|entry| entry.to_bytes()).collect();
+
+        let mut book = PolyglotBook::new(Cursor::new(bytes))?;
+        let moves = book.moves(&start)?;
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0].mv, Move::from_long_notation("e2e4"));
+
+        let mut random = Random::new(1);
+        assert_eq!(
+            book.choose_move(&start, BookSelection::Best, &mut random)?,
+            Some(Move::from_long_notation("e2e4"))
+        );
+        for _ in 0..10 {
+            let mv = book.choose_move(&start, BookSelection::WeightedRandom, &mut random)?;
+            assert!(moves.iter().any(|book_move| Some(book_move.mv) == mv));
+        }
+        assert_eq!(book.moves(&game_after(&["e2e4"]))?, vec![]);
+        Ok(())
+    }
+}
@@ -0,0 +1,578 @@
+use crate::board::PieceColor::{Black, White};
+use crate::board::PieceType::{Bishop, King, Knight, Pawn, Queen, Rook};
+use crate::board::{Board, PieceColor, PieceType};
+use crate::fen::board_to_fen;
+use crate::game::Game;
+use crate::mate_solver::{all_legal_moves, is_checkmate, solve_mate};
+use crate::pos::Pos;
+use crate::r#move::Move;
+use std::fmt::{Display, Formatter};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum StipulationKind {
+    // #n: White mates in n moves against any defence
+    Direct,
+    // h#n: Black starts and both sides cooperate so that White mates in n moves
+    Help,
+    // s#n: White forces Black to mate White in n moves
+    SelfMate,
+    // r#n: like a selfmate, but both sides must give mate in one move whenever they can
+    Reflex,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Stipulation {
+    pub kind: StipulationKind,
+    pub moves: u32,
+    // h#n.5: White starts the helpmate, which adds half a move
+    pub half_move: bool,
+}
+
+impl Stipulation {
+    /// Parses stipulations like "#2", "h#3", "h#2.5", "s#2" or "r#2"
+    pub fn parse(s: &str) -> Result<Stipulation, String> {
+        let lower = s.to_ascii_lowercase();
+        let (kind, rest) = match lower.split_once('#') {
+            Some(("", rest)) => (StipulationKind::Direct, rest),
+            Some(("h", rest)) => (StipulationKind::Help, rest),
+            Some(("s", rest)) => (StipulationKind::SelfMate, rest),
+            Some(("r", rest)) => (StipulationKind::Reflex, rest),
+            _ => return Err(format!("Unsupported stipulation {}", s)),
+        };
+        let (moves, half_move) = match rest.strip_suffix(".5") {
+            Some(moves) if kind == StipulationKind::Help => (moves, true),
+            Some(_) => return Err(format!("Half moves are only supported in helpmates: {}", s)),
+            None => (rest, false),
+        };
+        let moves: u32 = moves.parse().map_err(|_| format!("Invalid number of moves in stipulation {}", s))?;
+        if moves == 0 && !half_move {
+            return Err(format!("Invalid number of moves in stipulation {}", s));
+        }
+        Ok(Stipulation { kind, moves, half_move })
+    }
+
+    /// Side that makes the first move
+    pub fn first_to_move(&self) -> PieceColor {
+        if self.kind == StipulationKind::Help && !self.half_move { Black } else { White }
+    }
+}
+
+impl Display for Stipulation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let prefix = match self.kind {
+            StipulationKind::Direct => "",
+            StipulationKind::Help => "h",
+            StipulationKind::SelfMate => "s",
+            StipulationKind::Reflex => "r",
+        };
+        let half = if self.half_move { ".5" } else { "" };
+        write!(f, "{}#{}{}", prefix, self.moves, half)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct PlacedPiece {
+    pub piece: PieceType,
+    pub color: PieceColor,
+    pub pos: Pos,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum TwinChange {
+    Move(Pos, Pos),
+    Remove(Pos),
+    Add(PlacedPiece),
+    Exchange(Pos, Pos),
+    Stipulation(Stipulation),
+}
+
+impl Display for TwinChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TwinChange::Move(from, to) => write!(f, "{}-->{}", from, to),
+            TwinChange::Remove(pos) => write!(f, "-{}", pos),
+            TwinChange::Add(placed) => write!(f, "+{:?} {}{}", placed.color, placed.piece, placed.pos),
+            TwinChange::Exchange(a, b) => write!(f, "{}<-->{}", a, b),
+            TwinChange::Stipulation(stipulation) => write!(f, "stipulation {}", stipulation),
+        }
+    }
+}
+
+/// Variation of a problem, either derived from the original position or, if `continued`, from the previous twin
+#[derive(Clone, PartialEq)]
+pub struct Twin {
+    pub continued: bool,
+    pub changes: Vec<TwinChange>,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Problem {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub pieces: Vec<PlacedPiece>,
+    pub stipulation: Stipulation,
+    pub twins: Vec<Twin>,
+}
+
+/// The original position of a problem or one of its twins
+pub struct ProblemPosition {
+    // "a)", "b)", ... if the problem has twins, empty otherwise
+    pub label: String,
+    pub changes: Vec<TwinChange>,
+    pub stipulation: Stipulation,
+    pub game: Game,
+}
+
+/// All solutions of a problem position.
+/// For helpmates each solution is the full line, otherwise it is the key move.
+pub struct ProblemSolution {
+    pub position: ProblemPosition,
+    pub solutions: Vec<Vec<Move>>,
+}
+
+impl ProblemSolution {
+    /// Formats a solution in short algebraic notation, e.g. "1.Kd5 Qe4+ 2.Kd6 Qe6#" or "1.Ra6!"
+    pub fn format_solution(&self, solution: &[Move]) -> String {
+        let mut game = self.position.game.clone();
+        let mut output = String::new();
+        for (i, mv) in solution.iter().enumerate() {
+            if i % 2 == 0 {
+                if i > 0 {
+                    output.push(' ');
+                }
+                output.push_str(&format!("{}.", i / 2 + 1));
+            } else {
+                output.push(' ');
+            }
+            output.push_str(&game.to_short_notation(mv).unwrap_or_else(|_| mv.to_string()));
+            if game.make_move(mv).is_err() {
+                break;
+            }
+        }
+        if self.position.stipulation.kind != StipulationKind::Help {
+            output.push('!');
+        }
+        output
+    }
+}
+
+impl Problem {
+    /// Builds the positions of the original problem and of all twins
+    pub fn positions(&self) -> Result<Vec<ProblemPosition>, String> {
+        let mut positions = Vec::new();
+        let label = |i: usize| {
+            if self.twins.is_empty() { String::new() } else { format!("{})", (b'a' + i as u8) as char) }
+        };
+        positions.push(ProblemPosition {
+            label: label(0),
+            changes: Vec::new(),
+            stipulation: self.stipulation,
+            game: position_game(&self.pieces, self.stipulation)?,
+        });
+
+        let mut previous = (self.pieces.clone(), self.stipulation);
+        for (i, twin) in self.twins.iter().enumerate() {
+            let (mut pieces, mut stipulation) = if twin.continued {
+                previous.clone()
+            } else {
+                (self.pieces.clone(), self.stipulation)
+            };
+            for change in &twin.changes {
+                apply_change(&mut pieces, &mut stipulation, change)?;
+            }
+            positions.push(ProblemPosition {
+                label: label(i + 1),
+                changes: twin.changes.clone(),
+                stipulation,
+                game: position_game(&pieces, stipulation)?,
+            });
+            previous = (pieces, stipulation);
+        }
+        Ok(positions)
+    }
+
+    /// Solves the original problem and all twins
+    pub fn solve(&self) -> Result<Vec<ProblemSolution>, String> {
+        let positions = self.positions()?;
+        Ok(positions
+            .into_iter()
+            .map(|position| ProblemSolution {
+                solutions: solve_stipulation(&position.game, position.stipulation),
+                position,
+            })
+            .collect())
+    }
+}
+
+/// Finds all solutions of the position for the stipulation. The side to move must match `Stipulation::first_to_move`.
+pub fn solve_stipulation(game: &Game, stipulation: Stipulation) -> Vec<Vec<Move>> {
+    match stipulation.kind {
+        StipulationKind::Direct => solve_mate(game, stipulation.moves)
+            .keys
+            .into_iter()
+            .map(|key| vec![key.mv])
+            .collect(),
+        StipulationKind::Help => {
+            let plies = stipulation.moves * 2 + stipulation.half_move as u32;
+            let mut solutions = Vec::new();
+            help_mates(game, plies, &mut Vec::new(), &mut solutions);
+            solutions
+        }
+        StipulationKind::SelfMate => keys(game, |child| self_mate_defences(child, stipulation.moves)),
+        // White has to mate if it can, which fails the stipulation
+        StipulationKind::Reflex if can_mate_in_one(game) => Vec::new(),
+        StipulationKind::Reflex => keys(game, |child| reflex_defences(child, stipulation.moves)),
+    }
+}
+
+// All moves that don't end the game and after which `works` holds
+fn keys(game: &Game, works: impl Fn(&Game) -> bool) -> Vec<Vec<Move>> {
+    all_legal_moves(game)
+        .into_iter()
+        .filter(|mv| {
+            let child = play(game, mv);
+            child.result().is_none() && works(&child)
+        })
+        .map(|mv| vec![mv])
+        .collect()
+}
+
+fn help_mates(game: &Game, plies: u32, line: &mut Vec<Move>, solutions: &mut Vec<Vec<Move>>) {
+    for mv in all_legal_moves(game) {
+        // the last move has to give check, which is cheap to test on the board alone
+        if plies == 1 && !gives_check(game, &mv) {
+            continue;
+        }
+        let child = play(game, &mv);
+        line.push(mv);
+        if plies == 1 {
+            if is_checkmate(&child) {
+                solutions.push(line.clone());
+            }
+        } else if child.result().is_none() {
+            help_mates(&child, plies - 1, line, solutions);
+        }
+        line.pop();
+    }
+}
+
+// White to move, it can force Black to mate in `moves`
+fn self_mate(game: &Game, moves: u32) -> bool {
+    all_legal_moves(game).iter().any(|mv| {
+        let child = play(game, mv);
+        child.result().is_none() && self_mate_defences(&child, moves)
+    })
+}
+
+// Black to move, every move mates White or leads to a self mate in `moves - 1`
+fn self_mate_defences(game: &Game, moves: u32) -> bool {
+    all_legal_moves(game).iter().all(|mv| {
+        let child = play(game, mv);
+        if is_checkmate(&child) {
+            return true;
+        }
+        moves > 1 && child.result().is_none() && self_mate(&child, moves - 1)
+    })
+}
+
+// White to move, same as `self_mate` but White must not be able to mate in one
+fn reflex_mate(game: &Game, moves: u32) -> bool {
+    if can_mate_in_one(game) {
+        return false;
+    }
+    all_legal_moves(game).iter().any(|mv| {
+        let child = play(game, mv);
+        child.result().is_none() && reflex_defences(&child, moves)
+    })
+}
+
+// Black to move, it has to mate in one if it can
+fn reflex_defences(game: &Game, moves: u32) -> bool {
+    if can_mate_in_one(game) {
+        return true;
+    }
+    moves > 1
+        && all_legal_moves(game).iter().all(|mv| {
+            let child = play(game, mv);
+            child.result().is_none() && reflex_mate(&child, moves - 1)
+        })
+}
+
+fn can_mate_in_one(game: &Game) -> bool {
+    all_legal_moves(game)
+        .iter()
+        .any(|mv| gives_check(game, mv) && is_checkmate(&play(game, mv)))
+}
+
+fn play(game: &Game, mv: &Move) -> Game {
+    let mut child = game.clone();
+    child.make_move(mv).expect("Solver should only make legal moves");
+    child
+}
+
+fn gives_check(game: &Game, mv: &Move) -> bool {
+    let mut board = *game.board();
+    board.make_move(mv);
+    board.is_check(game.turn().opposite())
+}
+
+fn apply_change(pieces: &mut Vec<PlacedPiece>, stipulation: &mut Stipulation, change: &TwinChange) -> Result<(), String> {
+    let index_at = |pieces: &[PlacedPiece], pos: Pos| {
+        pieces
+            .iter()
+            .position(|placed| placed.pos == pos)
+            .ok_or_else(|| format!("Twin: no piece at {}", pos))
+    };
+    match *change {
+        TwinChange::Move(from, to) => {
+            pieces.retain(|placed| placed.pos != to);
+            let i = index_at(pieces, from)?;
+            pieces[i].pos = to;
+        }
+        TwinChange::Remove(pos) => {
+            let i = index_at(pieces, pos)?;
+            pieces.remove(i);
+        }
+        TwinChange::Add(placed) => {
+            pieces.retain(|other| other.pos != placed.pos);
+            pieces.push(placed);
+        }
+        TwinChange::Exchange(a, b) => {
+            let (i, j) = (index_at(pieces, a)?, index_at(pieces, b)?);
+            pieces[i].pos = b;
+            pieces[j].pos = a;
+        }
+        TwinChange::Stipulation(new_stipulation) => *stipulation = new_stipulation,
+    }
+    Ok(())
+}
+
+// Castling is allowed if king and rook stand on their initial squares, as in Popeye
+fn position_game(pieces: &[PlacedPiece], stipulation: Stipulation) -> Result<Game, String> {
+    let mut board = Board::empty();
+    for placed in pieces {
+        board.set_at_pos(&placed.pos, placed.piece, placed.color);
+    }
+    let in_place = |col: i8, row: i8, piece: PieceType, color: PieceColor| {
+        board.at(col, row).piece() == Some((piece, color))
+    };
+    let mut castling = String::new();
+    for (color, row, letters) in [(White, 0, ['K', 'Q']), (Black, 7, ['k', 'q'])] {
+        if in_place(4, row, King, color) {
+            if in_place(7, row, Rook, color) {
+                castling.push(letters[0]);
+            }
+            if in_place(0, row, Rook, color) {
+                castling.push(letters[1]);
+            }
+        }
+    }
+    if castling.is_empty() {
+        castling.push('-');
+    }
+    let turn = if stipulation.first_to_move() == White { "w" } else { "b" };
+    Game::from_fen(&format!("{} {} {} - 0 1", board_to_fen(&board), turn, castling))
+}
+
+/// Parses problems in a simple Popeye-like format. Keywords are case-insensitive, e.g.
+/// ```text
+/// BeginProblem
+/// Title Morphy
+/// Pieces
+/// White Kc8 Ra1 Pb6
+/// Black Ka8 Bb8 Pa7 Pb7
+/// Stipulation #2
+/// Twin Move a1 a2
+/// Twin Cont Add White Qd1
+/// NextProblem
+/// Pieces White Ke1 Black Ke8 Rh8b8
+/// Stipulation h#2
+/// EndProblem
+/// ```
+/// A piece is given by its letter (K, Q, R, B, S or N, P) followed by one or more squares.
+pub fn parse_problems(text: &str) -> Result<Vec<Problem>, String> {
+    let mut problems = Vec::new();
+    let mut parser = ProblemParser::default();
+    for (line_index, line) in text.lines().enumerate() {
+        parser
+            .parse_line(line, &mut problems)
+            .map_err(|err| format!("line {}: {}", line_index + 1, err))?;
+    }
+    parser.finish(&mut problems)?;
+    Ok(problems)
+}
+
+#[derive(Default)]
+enum Section {
+    #[default]
+    None,
+    Pieces(Option<PieceColor>),
+    Twin,
+}
+
+#[derive(Default)]
+struct ProblemParser {
+    title: Option<String>,
+    author: Option<String>,
+    pieces: Vec<PlacedPiece>,
+    stipulation: Option<Stipulation>,
+    twins: Vec<Twin>,
+    section: Section,
+}
+
+impl ProblemParser {
+    fn parse_line(&mut self, line: &str, problems: &mut Vec<Problem>) -> Result<(), String> {
+        let mut tokens = line.split_whitespace().peekable();
+        while let Some(token) = tokens.next() {
+            match token.to_ascii_lowercase().as_str() {
+                "beginproblem" => {}
+                "endproblem" | "nextproblem" => self.finish(problems)?,
+                // the rest of the line is text
+                "title" | "author" => {
+                    let text = tokens.by_ref().collect::<Vec<_>>().join(" ");
+                    if token.eq_ignore_ascii_case("title") {
+                        self.title = Some(text);
+                    } else {
+                        self.author = Some(text);
+                    }
+                }
+                "pieces" => self.section = Section::Pieces(None),
+                "stipulation" => {
+                    let stipulation = tokens.next().ok_or("Missing stipulation")?;
+                    let stipulation = Stipulation::parse(stipulation)?;
+                    match self.section {
+                        Section::Twin => self.add_twin_change(TwinChange::Stipulation(stipulation))?,
+                        _ => self.stipulation = Some(stipulation),
+                    }
+                }
+                "twin" => {
+                    let continued = tokens.next_if(|token| token.eq_ignore_ascii_case("cont")).is_some();
+                    self.twins.push(Twin {
+                        continued,
+                        changes: Vec::new(),
+                    });
+                    self.section = Section::Twin;
+                }
+                "white" | "black" if matches!(self.section, Section::Pieces(_)) => {
+                    let color = if token.eq_ignore_ascii_case("white") { White } else { Black };
+                    self.section = Section::Pieces(Some(color));
+                }
+                "move" | "exchange" if matches!(self.section, Section::Twin) => {
+                    let a = parse_square(tokens.next())?;
+                    let b = parse_square(tokens.next())?;
+                    let change = if token.eq_ignore_ascii_case("move") {
+                        TwinChange::Move(a, b)
+                    } else {
+                        TwinChange::Exchange(a, b)
+                    };
+                    self.add_twin_change(change)?;
+                }
+                "remove" if matches!(self.section, Section::Twin) => {
+                    let pos = parse_square(tokens.next())?;
+                    self.add_twin_change(TwinChange::Remove(pos))?;
+                }
+                "add" if matches!(self.section, Section::Twin) => {
+                    let color = match tokens.next().map(|color| color.to_ascii_lowercase()).as_deref() {
+                        Some("white") => White,
+                        Some("black") => Black,
+                        _ => return Err("Expected the color of the added piece".to_string()),
+                    };
+                    let pieces = parse_pieces(tokens.next().ok_or("Missing added piece")?, color)?;
+                    for placed in pieces {
+                        self.add_twin_change(TwinChange::Add(placed))?;
+                    }
+                }
+                _ => match self.section {
+                    Section::Pieces(Some(color)) => self.pieces.extend(parse_pieces(token, color)?),
+                    _ => return Err(format!("Unexpected {}", token)),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn add_twin_change(&mut self, change: TwinChange) -> Result<(), String> {
+        let twin = self.twins.last_mut().ok_or("Twin change outside of a twin")?;
+        twin.changes.push(change);
+        Ok(())
+    }
+
+    // Completes the current problem, if anything was given
+    fn finish(&mut self, problems: &mut Vec<Problem>) -> Result<(), String> {
+        let parser = std::mem::take(self);
+        if parser.pieces.is_empty() && parser.stipulation.is_none() {
+            return Ok(());
+        }
+        problems.push(Problem {
+            title: parser.title,
+            author: parser.author,
+            pieces: parser.pieces,
+            stipulation: parser.stipulation.ok_or("Missing stipulation")?,
+            twins: parser.twins,
+        });
+        Ok(())
+    }
+}
+
+fn parse_square(token: Option<&str>) -> Result<Pos, String> {
+    let token = token.ok_or("Missing square")?;
+    Ok(Pos::from_notation(&token.to_ascii_lowercase())?)
+}
+
+// e.g. "Kc8" or "pa7b7"
+fn parse_pieces(token: &str, color: PieceColor) -> Result<Vec<PlacedPiece>, String> {
+    let lower = token.to_ascii_lowercase();
+    let piece = match lower.chars().next() {
+        Some('k') => King,
+        Some('q') => Queen,
+        Some('r') => Rook,
+        Some('b') => Bishop,
+        Some('s') | Some('n') => Knight,
+        Some('p') => Pawn,
+        _ => return Err(format!("Invalid piece {}", token)),
+    };
+    let squares = &lower[1..];
+    if squares.is_empty() || !squares.len().is_multiple_of(2) {
+        return Err(format!("Invalid squares in {}", token));
+    }
+    (0..squares.len())
+        .step_by(2)
+        .map(|i| {
+            let pos = Pos::from_notation(&squares[i..i + 2]).map_err(|err| format!("{} in {}", err, token))?;
+            Ok(PlacedPiece { piece, color, pos })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_stipulations() {
+        assert_eq!(Stipulation::parse("h#2.5").map(|s| s.to_string()), Ok("h#2.5".to_string()));
+        assert_eq!(Stipulation::parse("S#3").map(|s| s.kind), Ok(StipulationKind::SelfMate));
+        assert!(Stipulation::parse("#2.5").is_err());
+        assert!(Stipulation::parse("=3").is_err());
+    }
+
+    #[test]
+    fn parses_pieces_and_twins() -> Result<(), String> {
+        let problems = parse_problems(
+            "BeginProblem\nTitle Test problem\nPieces\nWhite Kc8 Ra1h1\nBlack Ka8 pa7b7\n\
+             Stipulation #2\nTwin Move a1 a2 Remove h1\nTwin Cont Add Black Sb8\nEndProblem",
+        )?;
+        assert_eq!(problems.len(), 1);
+        let problem = &problems[0];
+        assert_eq!(problem.title.as_deref(), Some("Test problem"));
+        assert_eq!(problem.pieces.len(), 6);
+        assert_eq!(problem.twins.len(), 2);
+        assert!(problem.twins[1].continued);
+
+        let positions = problem.positions()?;
+        assert_eq!(positions.len(), 3);
+        assert_eq!(positions[1].label, "b)");
+        assert_eq!(positions[2].game.to_fen(), "knK5/pp6/8/8/8/8/R7/8 w - - 0 1");
+        Ok(())
+    }
+}
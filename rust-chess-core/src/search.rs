@@ -0,0 +1,902 @@
+use crate::board::{Board, PieceColor};
+use crate::eval::{evaluate_for, piece_value};
+use crate::game::Game;
+use crate::polyglot::polyglot_hash;
+use crate::r#move::Move;
+use crate::syzygy::{Syzygy, Wdl};
+use crate::tablebase::{TbResult, Tablebases};
+use crate::time_manager::TimeManager;
+use crate::transposition_table::{Bound, TranspositionTable, TtEntry};
+use std::cmp::Reverse;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+pub const MATE_SCORE: i32 = 30000;
+pub const MAX_PLY: usize = 128;
+pub const MAX_DEPTH: u32 = 64;
+
+// Scores above this value (by absolute value) mean that a forced mate was found
+const MATE_THRESHOLD: i32 = MATE_SCORE - MAX_PLY as i32;
+
+// Syzygy wins score below the mates the search finds, the tables don't tell how far the mate is
+const SYZYGY_WIN_SCORE: i32 = MATE_THRESHOLD - MAX_PLY as i32;
+
+// How often (in nodes) the clock is checked and the node count is shared with other threads
+const TIME_CHECK_INTERVAL: u64 = 256;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Score {
+    Centipawns(i32),
+    /// Number of moves (not plies) until mate. Negative if the side to move is getting mated.
+    Mate(i32),
+}
+
+impl Score {
+    /// Converts the internal search score, which encodes mates as `MATE_SCORE - ply`
+    pub fn from_internal(score: i32) -> Score {
+        if score > MATE_THRESHOLD {
+            Score::Mate((MATE_SCORE - score + 1) / 2)
+        } else if score < -MATE_THRESHOLD {
+            Score::Mate(-(MATE_SCORE + score) / 2)
+        } else {
+            Score::Centipawns(score)
+        }
+    }
+}
+
+impl Display for Score {
+    /// Formats the score the way UCI expects it, e.g. "cp 35" or "mate -3"
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Score::Centipawns(cp) => write!(f, "cp {}", cp),
+            Score::Mate(moves) => write!(f, "mate {}", moves),
+        }
+    }
+}
+
+/// Limits of a single search. The search runs until any of the limits is reached or it is stopped
+/// externally. If no limits are set, it runs until stopped or MAX_DEPTH is reached.
+#[derive(Clone, Default, Debug)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub time_manager: Option<TimeManager>,
+    // set for a ponder search, the time limits only apply after the ponder hit
+    pub ponder: Option<Ponder>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u32) -> SearchLimits {
+        SearchLimits {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    pub fn move_time(move_time: Duration) -> SearchLimits {
+        SearchLimits {
+            time_manager: Some(TimeManager::fixed(move_time)),
+            ..Default::default()
+        }
+    }
+}
+
+/// Switches a ponder search to a normal one. While pondering, the search thinks on the opponent's time
+/// and ignores the time limits. After `ponder_hit` they apply, counted from the moment of the hit,
+/// and the search continues with everything it has found so far.
+#[derive(Clone, Debug, Default)]
+pub struct Ponder {
+    hit_time: Arc<OnceLock<Instant>>,
+}
+
+impl Ponder {
+    pub fn new() -> Ponder {
+        Self::default()
+    }
+
+    /// The opponent played the expected move
+    pub fn ponder_hit(&self) {
+        let _ = self.hit_time.set(Instant::now());
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.hit_time.get().is_none()
+    }
+}
+
+/// Progress of the search, reported after each completed iteration for each of the best lines
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+    pub depth: u32,
+    // the deepest ply reached in the iteration, including quiescence search and extensions
+    pub seldepth: u32,
+    // rank of the line, starting from 1
+    pub multi_pv: usize,
+    pub score: Score,
+    pub nodes: u64,
+    pub time: Duration,
+    // permille of the transposition table in use
+    pub hashfull: u32,
+    pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+    pub fn nps(&self) -> u64 {
+        let millis = self.time.as_millis() as u64;
+        (self.nodes * 1000).checked_div(millis).unwrap_or(0)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    // None if there are no legal moves
+    pub best_move: Option<Move>,
+    // The expected reply of the opponent, if known
+    pub ponder_move: Option<Move>,
+    pub score: Score,
+    pub depth: u32,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+    // the best lines, best first. Only has more than one line in MultiPV mode.
+    pub lines: Vec<PvLine>,
+}
+
+/// One of the best lines found by the search
+#[derive(Clone, Debug, PartialEq)]
+pub struct PvLine {
+    pub mv: Move,
+    pub score: Score,
+    pub depth: u32,
+    pub pv: Vec<Move>,
+}
+
+impl PvLine {
+    /// Returns the moves of the principal variation in short algebraic notation.
+    /// `game` must be the position the search was started from.
+    pub fn pv_short_notation(&self, game: &Game) -> Result<Vec<String>, &'static str> {
+        game.moves_to_short_notation(&self.pv)
+    }
+}
+
+/// Stops a search when cancelled. Clones share the same state.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl From<CancellationToken> for Arc<AtomicBool> {
+    fn from(token: CancellationToken) -> Self {
+        token.0
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SearchEvent {
+    // sent after each completed iteration, once for every line in MultiPV mode
+    Info(SearchInfo),
+    // always the last event
+    Finished(SearchResult),
+}
+
+/// A search running on a background thread, see `Search::spawn`
+pub struct SearchHandle {
+    events: Receiver<SearchEvent>,
+    token: CancellationToken,
+    thread: JoinHandle<SearchResult>,
+}
+
+impl SearchHandle {
+    /// Events of the search. The channel is closed after `SearchEvent::Finished`.
+    pub fn events(&self) -> &Receiver<SearchEvent> {
+        &self.events
+    }
+
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Stops the search, the best move found so far is still returned by `join`
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Waits for the search to finish and returns its result
+    pub fn join(self) -> SearchResult {
+        self.thread.join().expect("Search thread panicked")
+    }
+}
+
+/// Iterative deepening alpha-beta search over `Game`.
+///
+/// With more than one thread it runs a Lazy SMP search: helper threads search the same position
+/// at the same time and share their results only through the transposition table.
+/// The main thread decides when to stop and reports the result.
+pub struct Search {
+    limits: SearchLimits,
+    stop: Arc<AtomicBool>,
+    start: Instant,
+    threads: usize,
+    tt: Arc<TranspositionTable>,
+    tablebases: Option<Arc<Tablebases>>,
+    syzygy: Option<Arc<Syzygy>>,
+    // whether the Syzygy WDL tables are probed in the tree, not when the DTZ tables chose the root moves
+    probe_syzygy: bool,
+    // 0 for the main thread
+    helper_id: usize,
+    // nodes of this thread
+    nodes: u64,
+    seldepth: u32,
+    // nodes of all threads, updated every TIME_CHECK_INTERVAL nodes
+    total_nodes: Arc<AtomicU64>,
+    stopped: bool,
+    killers: [[Option<Move>; 2]; MAX_PLY],
+    previous_pv: Vec<Move>,
+    // number of best lines to search
+    multi_pv: usize,
+    // root moves skipped when searching the second and following lines
+    excluded_root_moves: Vec<Move>,
+    // the only root moves to search, all legal moves if empty
+    search_moves: Vec<Move>,
+    // legal moves searched at the root of the current search
+    root_moves: Vec<Move>,
+    // whether root_moves leaves out some legal moves, so that the root score is not the one of the position
+    root_restricted: bool,
+    // positions since the last irreversible move, used to detect repetitions
+    positions: Vec<(Board, PieceColor)>,
+}
+
+impl Search {
+    /// `stop` can be set from another thread to abort the search. The best move found so far is returned then.
+    /// See also `cancellation_token`.
+    pub fn new(limits: SearchLimits, stop: Arc<AtomicBool>) -> Search {
+        Search {
+            limits,
+            stop,
+            start: Instant::now(),
+            threads: 1,
+            tt: Arc::new(TranspositionTable::default()),
+            tablebases: None,
+            syzygy: None,
+            probe_syzygy: true,
+            helper_id: 0,
+            nodes: 0,
+            seldepth: 0,
+            total_nodes: Arc::new(AtomicU64::new(0)),
+            stopped: false,
+            killers: [[None; 2]; MAX_PLY],
+            previous_pv: Vec::new(),
+            multi_pv: 1,
+            excluded_root_moves: Vec::new(),
+            search_moves: Vec::new(),
+            root_moves: Vec::new(),
+            root_restricted: false,
+            positions: Vec::new(),
+        }
+    }
+
+    pub fn with_limits(limits: SearchLimits) -> Search {
+        Self::new(limits, Arc::new(AtomicBool::new(false)))
+    }
+
+    /// Returns a token that stops the search when cancelled. It can be used from any thread, before or during `run`.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        CancellationToken(self.stop.clone())
+    }
+
+    /// Runs the search on a background thread. Progress and the final result are sent as events,
+    /// the returned handle can also cancel the search and wait for its result.
+    pub fn spawn(mut self, game: Game) -> SearchHandle {
+        let (sender, events) = mpsc::channel();
+        let token = self.cancellation_token();
+        let thread = thread::spawn(move || {
+            // the receiver may be gone if the caller is only interested in the result, that's fine
+            let result = self.run(&game, |info| {
+                let _ = sender.send(SearchEvent::Info(info.clone()));
+            });
+            let _ = sender.send(SearchEvent::Finished(result.clone()));
+            result
+        });
+        SearchHandle { events, token, thread }
+    }
+
+    /// Sets the number of search threads. With one thread (the default) the search is deterministic.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Sets the number of best lines to find, each of them is reported separately and returned in `SearchResult::lines`.
+    /// Searching more lines makes the search slower.
+    pub fn set_multi_pv(&mut self, multi_pv: usize) {
+        self.multi_pv = multi_pv.max(1);
+    }
+
+    /// Only searches these moves at the root, like "go searchmoves" in UCI. Moves that aren't legal are ignored,
+    /// and if none of them is, all moves are searched.
+    pub fn set_search_moves(&mut self, moves: Vec<Move>) {
+        self.search_moves = moves;
+    }
+
+    /// Replaces the transposition table, e.g. to keep it between searches or share it with other searches
+    pub fn set_transposition_table(&mut self, tt: Arc<TranspositionTable>) {
+        self.tt = tt;
+    }
+
+    pub fn transposition_table(&self) -> &Arc<TranspositionTable> {
+        &self.tt
+    }
+
+    /// Endgame tablebases to probe. At the root they choose the move directly, in the tree they end the search.
+    pub fn set_tablebases(&mut self, tablebases: Arc<Tablebases>) {
+        self.tablebases = Some(tablebases);
+    }
+
+    /// Syzygy tablebases to probe. At the root their DTZ tables keep the moves that hold the best result,
+    /// in the tree their WDL tables end the search after captures and pawn moves.
+    pub fn set_syzygy(&mut self, syzygy: Arc<Syzygy>) {
+        self.syzygy = Some(syzygy);
+    }
+
+    /// Nodes searched by all threads
+    pub fn nodes(&self) -> u64 {
+        self.total_nodes.load(Ordering::Relaxed)
+    }
+
+    /// Runs the search. `on_iteration` is called after every completed iteration of iterative deepening.
+    pub fn run(&mut self, game: &Game, on_iteration: impl FnMut(&SearchInfo)) -> SearchResult {
+        self.start = Instant::now();
+        self.total_nodes = Arc::new(AtomicU64::new(0));
+        self.tt.new_search();
+        if self.threads == 1 {
+            return self.iterate(game, on_iteration);
+        }
+
+        // the helpers run without limits until the main thread is done
+        let helpers_stop = Arc::new(AtomicBool::new(false));
+        let mut result = thread::scope(|scope| {
+            for helper_id in 1..self.threads {
+                let mut helper = self.helper(helper_id, helpers_stop.clone());
+                scope.spawn(move || helper.iterate(game, |_| {}));
+            }
+            let result = self.iterate(game, on_iteration);
+            helpers_stop.store(true, Ordering::Relaxed);
+            result
+        });
+        result.nodes = self.nodes();
+        result
+    }
+
+    fn helper(&self, helper_id: usize, stop: Arc<AtomicBool>) -> Search {
+        Search {
+            limits: SearchLimits::default(),
+            stop,
+            start: self.start,
+            threads: 1,
+            tt: self.tt.clone(),
+            tablebases: self.tablebases.clone(),
+            syzygy: self.syzygy.clone(),
+            probe_syzygy: true,
+            helper_id,
+            nodes: 0,
+            seldepth: 0,
+            total_nodes: self.total_nodes.clone(),
+            stopped: false,
+            killers: [[None; 2]; MAX_PLY],
+            previous_pv: Vec::new(),
+            multi_pv: 1,
+            excluded_root_moves: Vec::new(),
+            search_moves: self.search_moves.clone(),
+            root_moves: Vec::new(),
+            root_restricted: false,
+            positions: Vec::new(),
+        }
+    }
+
+    fn iterate(&mut self, game: &Game, mut on_iteration: impl FnMut(&SearchInfo)) -> SearchResult {
+        self.nodes = 0;
+        self.stopped = false;
+        self.killers = [[None; 2]; MAX_PLY];
+        self.previous_pv.clear();
+        self.positions = Self::collect_positions(game);
+        self.root_moves = game
+            .possible_moves()
+            .iter()
+            .filter(|mv| self.search_moves.contains(mv))
+            .copied()
+            .collect();
+        if self.root_moves.is_empty() {
+            self.root_moves = game.possible_moves().to_vec();
+        }
+
+        let mut result = SearchResult {
+            best_move: self.root_moves.first().copied(),
+            ponder_move: None,
+            score: Score::Centipawns(0),
+            depth: 0,
+            nodes: 0,
+            pv: Vec::new(),
+            lines: Vec::new(),
+        };
+        if result.best_move.is_none() {
+            return result;
+        }
+
+        // the tablebases know the best move, no need to search
+        if self.multi_pv == 1
+            && self.root_moves.len() == game.possible_moves().len()
+            && let Some(tablebases) = &self.tablebases
+            && let Some((best_move, tb_result)) = tablebases.best_move(game)
+        {
+            let mut pv = tablebases.best_line(game, MAX_PLY);
+            if pv.is_empty() {
+                pv.push(best_move);
+            }
+            let score = Score::from_internal(tablebase_score(tb_result, 0));
+            on_iteration(&SearchInfo {
+                depth: 1,
+                seldepth: pv.len() as u32,
+                multi_pv: 1,
+                score,
+                nodes: 0,
+                time: self.start.elapsed(),
+                hashfull: self.tt.hashfull(),
+                pv: pv.clone(),
+            });
+            result.best_move = Some(best_move);
+            result.ponder_move = pv.get(1).copied();
+            result.score = score;
+            result.depth = 1;
+            result.lines = vec![PvLine {
+                mv: best_move,
+                score,
+                depth: 1,
+                pv: pv.clone(),
+            }];
+            result.pv = pv;
+            return result;
+        }
+
+        // the Syzygy tables know which moves keep the best result, the search only chooses among them
+        self.probe_syzygy = true;
+        if let Some(syzygy) = &self.syzygy
+            && let Some(moves) = syzygy.root_moves(game)
+        {
+            let kept: Vec<Move> = self.root_moves.iter().filter(|mv| moves.contains(mv)).copied().collect();
+            if !kept.is_empty() {
+                self.root_moves = kept;
+                self.probe_syzygy = false;
+                result.best_move = self.root_moves.first().copied();
+            }
+        }
+        self.root_restricted = self.root_moves.len() < game.possible_moves().len();
+
+        let max_depth = self.limits.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH);
+        let line_count = self.multi_pv.min(self.root_moves.len());
+        // principal variations of the previous iteration, best first
+        let mut previous_pvs: Vec<Vec<Move>> = Vec::new();
+        'iterations: for depth in 1..=max_depth {
+            // every other helper searches one ply deeper, so that the threads don't all do the same work
+            let search_depth = (depth + (self.helper_id % 2) as u32).min(MAX_DEPTH);
+
+            self.seldepth = 0;
+            // each following line is searched without the first moves of the lines found before it
+            let mut lines: Vec<(i32, Vec<Move>)> = Vec::new();
+            for line in 0..line_count {
+                self.previous_pv = previous_pvs.get(line).cloned().unwrap_or_default();
+                self.excluded_root_moves = lines.iter().map(|(_, pv)| pv[0]).collect();
+                let mut pv = Vec::new();
+                let score = self.alpha_beta(game, search_depth, 0, -MATE_SCORE, MATE_SCORE, &mut pv);
+                if self.stopped {
+                    break 'iterations;
+                }
+                lines.push((score, pv));
+            }
+            self.excluded_root_moves.clear();
+            // a later line can come out better when the search is unstable
+            lines.sort_by_key(|(score, _)| Reverse(*score));
+
+            for (i, (score, pv)) in lines.iter().enumerate() {
+                on_iteration(&SearchInfo {
+                    depth,
+                    seldepth: self.seldepth,
+                    multi_pv: i + 1,
+                    score: Score::from_internal(*score),
+                    nodes: self.total_nodes(),
+                    time: self.start.elapsed(),
+                    hashfull: self.tt.hashfull(),
+                    pv: pv.clone(),
+                });
+            }
+
+            let (score, pv) = &lines[0];
+            let score = *score;
+            result.best_move = pv.first().copied();
+            result.ponder_move = pv.get(1).copied();
+            result.score = Score::from_internal(score);
+            result.depth = depth;
+            result.pv = pv.clone();
+            result.lines = lines
+                .iter()
+                .map(|(score, pv)| PvLine {
+                    mv: pv[0],
+                    score: Score::from_internal(*score),
+                    depth,
+                    pv: pv.clone(),
+                })
+                .collect();
+            previous_pvs = lines.into_iter().map(|(_, pv)| pv).collect();
+
+            // no need to search deeper if a forced mate was found, unless the other lines are still of interest
+            if line_count == 1 && score.abs() > MATE_THRESHOLD && (MATE_SCORE - score.abs()) as u32 <= depth {
+                break;
+            }
+            let elapsed = self.time_elapsed();
+            if let Some(time_manager) = &mut self.limits.time_manager {
+                // with a single legal move there is nothing to think about, the first iteration is only for the ponder move
+                if self.root_moves.len() == 1 {
+                    break;
+                }
+                time_manager.on_iteration(result.best_move, score);
+                if elapsed.is_some_and(|elapsed| time_manager.should_stop_iterating(elapsed)) {
+                    break;
+                }
+            }
+        }
+        self.excluded_root_moves.clear();
+        self.total_nodes.fetch_add(self.nodes % TIME_CHECK_INTERVAL, Ordering::Relaxed);
+        result.nodes = self.total_nodes();
+        result
+    }
+
+    fn alpha_beta(
+        &mut self,
+        game: &Game,
+        depth: u32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        pv.clear();
+        if let Some(result) = game.result() {
+            return if result.winner.is_some() { -MATE_SCORE + ply as i32 } else { 0 };
+        }
+        if ply > 0 && self.is_draw(game) {
+            return 0;
+        }
+        if ply >= MAX_PLY - 1 {
+            return evaluate_for(game.board(), game.turn());
+        }
+        if ply > 0
+            && let Some(tablebases) = &self.tablebases
+            && let Some(tb_result) = tablebases.probe(game)
+        {
+            return tablebase_score(tb_result, ply);
+        }
+        // right after a capture or a pawn move the 50-move rule starts over, as the tables assume
+        if ply > 0
+            && self.probe_syzygy
+            && game.halfmove_clock() == 0
+            && let Some(syzygy) = &self.syzygy
+            && let Some(wdl) = syzygy.probe_wdl(game)
+        {
+            return syzygy_score(wdl, ply);
+        }
+
+        // extend checks so that mates behind checks are not missed at the horizon
+        let depth = if game.is_check() { depth + 1 } else { depth };
+        if depth == 0 {
+            return self.quiescence(game, ply, alpha, beta);
+        }
+
+        self.count_node(ply);
+        if self.should_stop() {
+            return 0;
+        }
+
+        let key = polyglot_hash(game);
+        let tt_entry = self.tt.probe(key);
+        if ply > 0
+            && let Some(entry) = tt_entry
+            && entry.depth >= depth
+        {
+            // exact scores inside the window are searched again to get the principal variation
+            let score = score_from_tt(entry.score, ply);
+            if entry.bound != Bound::Upper && score >= beta {
+                return beta;
+            }
+            if entry.bound != Bound::Lower && score <= alpha {
+                return alpha;
+            }
+        }
+
+        // the best move known from the table, or from the principal variation of the previous iteration, is searched first
+        let pv_move = tt_entry
+            .and_then(|entry| entry.best_move)
+            .or_else(|| self.previous_pv.get(ply).copied());
+        let mut moves = self.ordered_moves(game, ply, pv_move);
+        if ply == 0 {
+            moves.retain(|mv| self.root_moves.contains(mv) && !self.excluded_root_moves.contains(mv));
+        }
+        let original_alpha = alpha;
+        let mut child_pv = Vec::new();
+        for mv in moves {
+            let child = self.play(game, &mv);
+            let score = -self.alpha_beta(&child, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+            self.positions.pop();
+            if self.stopped {
+                return 0;
+            }
+
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push(mv);
+                pv.extend_from_slice(&child_pv);
+                if score >= beta {
+                    if !Self::is_capture(game, &mv) {
+                        self.store_killer(ply, mv);
+                    }
+                    self.store(key, Some(mv), beta, depth, Bound::Lower, ply);
+                    return beta;
+                }
+            }
+        }
+        if alpha > original_alpha {
+            self.store(key, pv.first().copied(), alpha, depth, Bound::Exact, ply);
+        } else {
+            self.store(key, None, alpha, depth, Bound::Upper, ply);
+        }
+        alpha
+    }
+
+    fn store(&self, key: u64, best_move: Option<Move>, score: i32, depth: u32, bound: Bound, ply: usize) {
+        // the result of a root search without some of the moves is not the real score of the position
+        if ply == 0 && (!self.excluded_root_moves.is_empty() || self.root_restricted) {
+            return;
+        }
+        self.tt.store(
+            key,
+            TtEntry {
+                best_move,
+                score: score_to_tt(score, ply),
+                depth,
+                bound,
+            },
+        );
+    }
+
+    fn quiescence(&mut self, game: &Game, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        if let Some(result) = game.result() {
+            return if result.winner.is_some() { -MATE_SCORE + ply as i32 } else { 0 };
+        }
+
+        self.count_node(ply);
+        if self.should_stop() {
+            return 0;
+        }
+
+        let stand_pat = evaluate_for(game.board(), game.turn());
+        if ply >= MAX_PLY - 1 {
+            return stand_pat;
+        }
+        if stand_pat >= beta {
+            return beta;
+        }
+        if stand_pat > alpha {
+            alpha = stand_pat;
+        }
+
+        let moves: Vec<Move> = self
+            .ordered_moves(game, ply, None)
+            .into_iter()
+            .filter(|mv| Self::is_capture(game, mv) || mv.has_promotion())
+            .collect();
+        for mv in moves {
+            let child = self.play(game, &mv);
+            let score = -self.quiescence(&child, ply + 1, -beta, -alpha);
+            self.positions.pop();
+            if self.stopped {
+                return 0;
+            }
+
+            if score > alpha {
+                if score >= beta {
+                    return beta;
+                }
+                alpha = score;
+            }
+        }
+        alpha
+    }
+
+    /// Returns a copy of the game with the move made, and remembers the resulting position
+    fn play(&mut self, game: &Game, mv: &Move) -> Game {
+        let mut child = game.clone();
+        child
+            .make_move(mv)
+            .expect("Search should only make legal moves");
+        self.positions.push((*child.board(), child.turn()));
+        child
+    }
+
+    fn ordered_moves(&self, game: &Game, ply: usize, pv_move: Option<Move>) -> Vec<Move> {
+        let mut moves: Vec<(i32, Move)> = game
+            .possible_moves()
+            .iter()
+            .map(|mv| (self.move_order_score(game, mv, ply, pv_move), *mv))
+            .collect();
+        moves.sort_by_key(|(score, _)| Reverse(*score));
+        moves.into_iter().map(|(_, mv)| mv).collect()
+    }
+
+    fn move_order_score(&self, game: &Game, mv: &Move, ply: usize, pv_move: Option<Move>) -> i32 {
+        if pv_move == Some(*mv) {
+            return 1_000_000;
+        }
+        let board = game.board();
+        if let Some((victim, _)) = board.at(mv.to_col, mv.to_row).piece() {
+            // most valuable victim, least valuable attacker
+            let (attacker, _) = board.at(mv.from_col, mv.from_row).piece().unwrap();
+            return 100_000 + piece_value(victim) * 10 - piece_value(attacker) / 10;
+        }
+        if mv.has_promotion() {
+            return 90_000;
+        }
+        if self.killers[ply].contains(&Some(*mv)) {
+            return 80_000;
+        }
+        0
+    }
+
+    fn store_killer(&mut self, ply: usize, mv: Move) {
+        if self.killers[ply][0] != Some(mv) {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = Some(mv);
+        }
+    }
+
+    fn is_capture(game: &Game, mv: &Move) -> bool {
+        game.board().at(mv.to_col, mv.to_row).is_occupied()
+            || game.board().is_en_passant_move(mv).is_some()
+    }
+
+    /// Detects draws by repetition and by the fifty-move rule. A single repetition is treated as a draw,
+    /// because if repeating the position is the best option, it can be repeated again.
+    fn is_draw(&self, game: &Game) -> bool {
+        if game.halfmove_clock() >= 100 {
+            return true;
+        }
+        let Some(current) = self.positions.last() else {
+            return false;
+        };
+        // only positions since the last capture or pawn move can repeat, and only with the same side to move
+        self.positions
+            .iter()
+            .rev()
+            .take(game.halfmove_clock() as usize + 1)
+            .skip(2)
+            .step_by(2)
+            .any(|position| position == current)
+    }
+
+    fn count_node(&mut self, ply: usize) {
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply as u32);
+        if self.nodes.is_multiple_of(TIME_CHECK_INTERVAL) {
+            self.total_nodes.fetch_add(TIME_CHECK_INTERVAL, Ordering::Relaxed);
+        }
+    }
+
+    /// Nodes of all threads, including the ones of this thread that are not shared yet
+    fn total_nodes(&self) -> u64 {
+        self.total_nodes.load(Ordering::Relaxed) + self.nodes % TIME_CHECK_INTERVAL
+    }
+
+    /// Time counted against the time limits, None while pondering
+    fn time_elapsed(&self) -> Option<Duration> {
+        match &self.limits.ponder {
+            None => Some(self.start.elapsed()),
+            Some(ponder) => ponder.hit_time.get().map(|hit_time| hit_time.elapsed()),
+        }
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if self.stopped {
+            return true;
+        }
+        if self.stop.load(Ordering::Relaxed) {
+            self.stopped = true;
+        } else if let Some(max_nodes) = self.limits.nodes
+            && self.total_nodes() >= max_nodes
+        {
+            self.stopped = true;
+        } else if let Some(time_manager) = &self.limits.time_manager
+            && self.nodes.is_multiple_of(TIME_CHECK_INTERVAL)
+            && let Some(elapsed) = self.time_elapsed()
+            && time_manager.is_hard_limit_reached(elapsed)
+        {
+            self.stopped = true;
+        }
+        self.stopped
+    }
+
+    /// Replays the game history to collect the positions that can still be repeated
+    fn collect_positions(game: &Game) -> Vec<(Board, PieceColor)> {
+        let history = game.history();
+        let mut board = history.initial_state().unwrap_or_else(Board::new_chess_game);
+        let mut turn = history.initial_turn().unwrap_or(PieceColor::White);
+        let mut positions = vec![(board, turn)];
+        for mv in history.moves() {
+            board.make_move(mv);
+            turn = turn.opposite();
+            positions.push((board, turn));
+        }
+        let reversible = game.halfmove_clock() as usize + 1;
+        positions.split_off(positions.len().saturating_sub(reversible))
+    }
+}
+
+// Tablebase results as search scores at the given ply, mates count from the root like the ones found by the search
+fn tablebase_score(result: TbResult, ply: usize) -> i32 {
+    match result {
+        TbResult::Win(plies) => MATE_SCORE - (ply as i32 + plies as i32),
+        TbResult::Draw => 0,
+        TbResult::Loss(plies) => -MATE_SCORE + ply as i32 + plies as i32,
+    }
+}
+
+// Syzygy results as search scores at the given ply, wins that the 50-move rule spoils just off a draw
+fn syzygy_score(wdl: Wdl, ply: usize) -> i32 {
+    match wdl {
+        Wdl::Win => SYZYGY_WIN_SCORE - ply as i32,
+        Wdl::CursedWin => 1,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -1,
+        Wdl::Loss => -SYZYGY_WIN_SCORE + ply as i32,
+    }
+}
+
+// Mate scores are stored relative to the node rather than the root, so that they stay correct
+// when the same position is reached at a different ply
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score > MATE_THRESHOLD {
+        score + ply as i32
+    } else if score < -MATE_THRESHOLD {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score > MATE_THRESHOLD {
+        score - ply as i32
+    } else if score < -MATE_THRESHOLD {
+        score + ply as i32
+    } else {
+        score
+    }
+}
@@ -0,0 +1,171 @@
+use crate::r#move::Move;
+use crate::random::Random;
+use crate::search::{PvLine, Score, SearchLimits};
+
+pub const MAX_SKILL_LEVEL: u32 = 20;
+pub const MIN_ELO: u32 = 800;
+pub const MAX_ELO: u32 = 2400;
+
+// a weakened engine searches a few lines to have something to choose from
+const LIMITED_MULTI_PV: usize = 4;
+
+/// Playing strength of the engine from 0 (weakest) to MAX_SKILL_LEVEL (full strength).
+///
+/// Below full strength the search depth and nodes are limited, and the move is chosen
+/// among the best lines with some randomness, sometimes picking a clearly weaker one.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SkillLevel {
+    level: u32,
+}
+
+impl SkillLevel {
+    pub fn new(level: u32) -> SkillLevel {
+        SkillLevel {
+            level: level.min(MAX_SKILL_LEVEL),
+        }
+    }
+
+    pub fn full_strength() -> SkillLevel {
+        Self::new(MAX_SKILL_LEVEL)
+    }
+
+    /// Maps the Elo range MIN_ELO..=MAX_ELO linearly to the skill levels. The mapping is a rough estimate.
+    pub fn from_elo(elo: u32) -> SkillLevel {
+        let elo = elo.clamp(MIN_ELO, MAX_ELO);
+        let range = MAX_ELO - MIN_ELO;
+        Self::new(((elo - MIN_ELO) * MAX_SKILL_LEVEL + range / 2) / range)
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.level < MAX_SKILL_LEVEL
+    }
+
+    /// Adds the depth and node limits of the skill level to the search limits
+    pub fn limit_search(&self, limits: SearchLimits) -> SearchLimits {
+        if !self.is_limited() {
+            return limits;
+        }
+        let max_depth = 1 + self.level / 3;
+        let max_nodes = 500 << (self.level / 2);
+        SearchLimits {
+            depth: Some(limits.depth.map_or(max_depth, |depth| depth.min(max_depth))),
+            nodes: Some(limits.nodes.map_or(max_nodes, |nodes| nodes.min(max_nodes))),
+            ..limits
+        }
+    }
+
+    /// Number of lines to search, at least a few when the strength is limited
+    pub fn multi_pv(&self, requested: usize) -> usize {
+        if self.is_limited() { requested.max(LIMITED_MULTI_PV) } else { requested }
+    }
+
+    /// Chooses the move to play from the best lines of the search, ordered best first.
+    /// At full strength this is always the best line.
+    pub fn choose_line<'a>(&self, lines: &'a [PvLine], random: &mut Random) -> Option<&'a PvLine> {
+        if !self.is_limited() || lines.len() < 2 {
+            return lines.first();
+        }
+
+        // now and then simply play one of the weaker lines
+        let blunder_chance = (MAX_SKILL_LEVEL - self.level) as f64 / 100.0;
+        if random.next_f64() < blunder_chance {
+            return lines.get(1 + random.below(lines.len() as u64 - 1) as usize);
+        }
+
+        // otherwise weaker lines get a random bonus that grows with how much worse they are,
+        // so that lower levels drift towards them more often
+        let weakness = 120 - 2 * self.level as i64;
+        let top = comparable_score(lines[0].score);
+        let delta = (top - comparable_score(lines[lines.len() - 1].score)).min(100);
+        lines.iter().max_by_key(|line| {
+            let score = comparable_score(line.score);
+            let push = (weakness * (top - score) + delta * random.below(weakness as u64) as i64) / 128;
+            score + push
+        })
+    }
+
+    /// Same as `choose_line`, but returns only the move
+    pub fn choose_move(&self, lines: &[PvLine], random: &mut Random) -> Option<Move> {
+        self.choose_line(lines, random).map(|line| line.mv)
+    }
+}
+
+impl Default for SkillLevel {
+    fn default() -> Self {
+        Self::full_strength()
+    }
+}
+
+// Orders mates above any material advantage, faster mates first
+fn comparable_score(score: Score) -> i64 {
+    match score {
+        Score::Centipawns(cp) => cp as i64,
+        Score::Mate(moves) if moves > 0 => 1_000_000 - moves as i64,
+        Score::Mate(moves) => -1_000_000 - moves as i64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines() -> Vec<PvLine> {
+        ["e2e4", "d2d4", "g1f3", "a2a3"]
+            .iter()
+            .zip([30, 25, 20, -150])
+            .map(|(mv, cp)| PvLine {
+                mv: Move::from_long_notation(mv),
+                score: Score::Centipawns(cp),
+                depth: 3,
+                pv: vec![Move::from_long_notation(mv)],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn maps_elo_to_levels() {
+        assert_eq!(SkillLevel::from_elo(0).level(), 0);
+        assert_eq!(SkillLevel::from_elo(1600).level(), 10);
+        assert_eq!(SkillLevel::from_elo(3000), SkillLevel::full_strength());
+    }
+
+    #[test]
+    fn limits_depth_and_nodes() {
+        let limits = SkillLevel::new(3).limit_search(SearchLimits::depth(10));
+        assert_eq!(limits.depth, Some(2));
+        assert_eq!(limits.nodes, Some(1000));
+
+        let limits = SkillLevel::full_strength().limit_search(SearchLimits::depth(10));
+        assert_eq!(limits.depth, Some(10));
+        assert_eq!(limits.nodes, None);
+    }
+
+    #[test]
+    fn full_strength_plays_the_best_move() {
+        let mut random = Random::new(1);
+        for _ in 0..100 {
+            let mv = SkillLevel::full_strength().choose_move(&lines(), &mut random);
+            assert_eq!(mv, Some(Move::from_long_notation("e2e4")));
+        }
+    }
+
+    #[test]
+    fn weak_levels_sometimes_play_worse_moves() {
+        let mut random = Random::new(1);
+        let lines = lines();
+        let count_best = |level, random: &mut Random| {
+            (0..1000)
+                .filter(|_| SkillLevel::new(level).choose_move(&lines, random) == Some(lines[0].mv))
+                .count()
+        };
+        let weakest = count_best(0, &mut random);
+        let strong = count_best(19, &mut random);
+
+        assert!(weakest < 900);
+        assert!(strong > weakest);
+    }
+}
@@ -0,0 +1,1000 @@
+use crate::board::PieceColor::{Black, White};
+use crate::board::PieceType::{Bishop, King, Knight, Pawn, Queen, Rook};
+use crate::board::{Board, PieceColor};
+use crate::game::Game;
+use crate::r#move::Move;
+use crate::tablebase::{Kind, has_castling_or_en_passant, material_signature, parse_signature};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, OnceLock};
+
+pub const WDL_EXTENSION: &str = "rtbw";
+pub const DTZ_EXTENSION: &str = "rtbz";
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// layout byte of the file header
+const SPLIT: u8 = 1;
+const HAS_PAWNS: u8 = 2;
+
+// flags of a side table: which side to move a DTZ table has, whether its values go through a map and are
+// in plies rather than moves, whether the map has 16-bit values and whether the table is a single value
+const STM: u8 = 1;
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const WIDE: u8 = 16;
+const SINGLE_VALUE: u8 = 128;
+
+// rank of the root moves that win or lose regardless of the 50-move rule
+const MAX_DTZ: i32 = 1 << 18;
+
+/// Result of a position for the side to move with perfect play, taking the 50-move rule into account
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Wdl {
+    Loss,
+    /// A loss that the 50-move rule turns into a draw
+    BlessedLoss,
+    Draw,
+    /// A win that the 50-move rule turns into a draw
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    /// The result for the other side
+    pub fn opposite(self) -> Wdl {
+        match self {
+            Wdl::Loss => Wdl::Win,
+            Wdl::BlessedLoss => Wdl::CursedWin,
+            Wdl::Draw => Wdl::Draw,
+            Wdl::CursedWin => Wdl::BlessedLoss,
+            Wdl::Win => Wdl::Loss,
+        }
+    }
+
+    fn from_value(value: u16) -> Option<Wdl> {
+        match value {
+            0 => Some(Wdl::Loss),
+            1 => Some(Wdl::BlessedLoss),
+            2 => Some(Wdl::Draw),
+            3 => Some(Wdl::CursedWin),
+            4 => Some(Wdl::Win),
+            _ => None,
+        }
+    }
+
+    // Distance to zeroing when the best move is a capture or a pawn move
+    fn before_zeroing(self) -> i32 {
+        match self {
+            Wdl::Loss => -1,
+            Wdl::BlessedLoss => -101,
+            Wdl::Draw => 0,
+            Wdl::CursedWin => 101,
+            Wdl::Win => 1,
+        }
+    }
+
+    // The DTZ value maps are by result
+    fn map_index(self) -> usize {
+        match self {
+            Wdl::Loss => 1,
+            Wdl::CursedWin => 2,
+            Wdl::BlessedLoss => 3,
+            Wdl::Win | Wdl::Draw => 0,
+        }
+    }
+}
+
+/// Syzygy endgame tablebases: win/draw/loss tables (.rtbw) and distance to zeroing tables (.rtbz), which count
+/// the plies to the next capture or pawn move with the 50-move rule in mind.
+///
+/// The files are read when they are first probed. Castling and en passant are not part of the tables,
+/// positions where they are possible are not probed.
+#[derive(Default)]
+pub struct Syzygy {
+    // table files by material signature, e.g. "KQvKR"
+    wdl: HashMap<String, TableFile>,
+    dtz: HashMap<String, TableFile>,
+    max_pieces: usize,
+}
+
+impl Syzygy {
+    pub fn new() -> Syzygy {
+        Syzygy::default()
+    }
+
+    /// Finds the tables of a directory
+    pub fn load_dir(dir: &Path) -> Result<Syzygy, String> {
+        let mut syzygy = Syzygy::new();
+        syzygy.add_dir(dir)?;
+        Ok(syzygy)
+    }
+
+    /// Finds the tables of a list of directories separated like the PATH environment variable
+    pub fn load_path(path: &str) -> Result<Syzygy, String> {
+        let mut syzygy = Syzygy::new();
+        for dir in std::env::split_paths(path) {
+            syzygy.add_dir(&dir)?;
+        }
+        Ok(syzygy)
+    }
+
+    /// Finds the tables of a directory, in addition to those already found
+    pub fn add_dir(&mut self, dir: &Path) -> Result<(), String> {
+        let entries = std::fs::read_dir(dir).map_err(|err| format!("Error reading {}: {}", dir.display(), err))?;
+        for entry in entries {
+            let path = entry.map_err(|err| err.to_string())?.path();
+            let is_wdl = match path.extension().and_then(|extension| extension.to_str()) {
+                Some(WDL_EXTENSION) => true,
+                Some(DTZ_EXTENSION) => false,
+                _ => continue,
+            };
+            let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+            let kinds = parse_signature(stem).map_err(|err| format!("{}: {}", path.display(), err))?;
+            self.max_pieces = self.max_pieces.max(kinds.len());
+            let tables = if is_wdl { &mut self.wdl } else { &mut self.dtz };
+            tables.insert(material_signature(&kinds), TableFile { path, kinds, is_wdl, table: OnceLock::new() });
+        }
+        Ok(())
+    }
+
+    /// Material signatures of the available WDL tables, sorted
+    pub fn signatures(&self) -> Vec<&str> {
+        let mut signatures: Vec<&str> = self.wdl.keys().map(|signature| signature.as_str()).collect();
+        signatures.sort();
+        signatures
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wdl.is_empty()
+    }
+
+    /// Largest number of pieces, kings included, of the available tables
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Probes the result of the position, None if there is no table for it or for a position after a capture
+    pub fn probe_wdl(&self, game: &Game) -> Option<Wdl> {
+        if !self.can_probe(game) {
+            return None;
+        }
+        Some(self.probe_captures(game, Wdl::Loss, Wdl::Win)?.0)
+    }
+
+    /// Probes the plies to the next capture or pawn move with perfect play, positive when winning, negative when
+    /// losing and 0 for a draw. Beyond 100 the win or loss is a draw by the 50-move rule. When the tables count
+    /// moves rather than plies, the distance can be one ply longer than the actual one.
+    pub fn probe_dtz(&self, game: &Game) -> Option<i32> {
+        if !self.can_probe(game) {
+            return None;
+        }
+        self.probe_dtz_unchecked(game)
+    }
+
+    /// The moves that keep the best result of the position: when winning, the moves that win before the 50-move
+    /// rule draws the game, or else those that get closest to zeroing; when losing, the ones that resist longest.
+    /// None if the position or a position after one of its moves can't be probed.
+    pub fn root_moves(&self, game: &Game) -> Option<Vec<Move>> {
+        if !self.can_probe(game) || game.possible_moves().is_empty() {
+            return None;
+        }
+        let halfmove_clock = game.halfmove_clock() as i32;
+        let mut ranked = Vec::new();
+        for mv in game.possible_moves() {
+            let mut child = game.clone();
+            child.make_move(mv).ok()?;
+            let dtz = match child.result() {
+                Some(result) if result.winner.is_some() => 1,
+                Some(_) => 0,
+                None if child.halfmove_clock() == 0 => self.probe_wdl(&child)?.opposite().before_zeroing(),
+                None => match -self.probe_dtz(&child)? {
+                    dtz if dtz > 0 => dtz + 1,
+                    dtz if dtz < 0 => dtz - 1,
+                    _ => 0,
+                },
+            };
+            ranked.push((rank(dtz, halfmove_clock), *mv));
+        }
+        let best = ranked.iter().map(|(rank, _)| *rank).max()?;
+        Some(ranked.into_iter().filter(|(rank, _)| *rank == best).map(|(_, mv)| mv).collect())
+    }
+
+    fn can_probe(&self, game: &Game) -> bool {
+        let piece_count = squares(game.board()).len();
+        piece_count <= self.max_pieces && !has_castling_or_en_passant(game)
+    }
+
+    // The tables leave out captures, which are searched with alpha-beta. Returns the result and whether a capture
+    // is the best move.
+    fn probe_captures(&self, game: &Game, mut alpha: Wdl, beta: Wdl) -> Option<(Wdl, bool)> {
+        for mv in game.possible_moves() {
+            if game.board().at(mv.to_col, mv.to_row).is_empty() {
+                continue;
+            }
+            let mut child = game.clone();
+            child.make_move(mv).ok()?;
+            let result = self.probe_captures(&child, beta.opposite(), alpha.opposite())?.0.opposite();
+            if result > alpha {
+                if result >= beta {
+                    return Some((result, true));
+                }
+                alpha = result;
+            }
+        }
+        let result = self.probe_wdl_table(game)?;
+        if alpha >= result { Some((alpha, alpha > Wdl::Draw)) } else { Some((result, false)) }
+    }
+
+    fn probe_wdl_table(&self, game: &Game) -> Option<Wdl> {
+        let pieces = squares(game.board());
+        if pieces.len() == 2 {
+            return Some(Wdl::Draw);
+        }
+        let table = find_table(&self.wdl, &pieces)?;
+        let (side, index, _) = table.encode(&pieces, game.turn())?;
+        Wdl::from_value(table.decompress(side, index)?)
+    }
+
+    fn probe_dtz_unchecked(&self, game: &Game) -> Option<i32> {
+        let (wdl, capture) = self.probe_captures(game, Wdl::Loss, Wdl::Win)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if capture {
+            return Some(wdl.before_zeroing());
+        }
+        if wdl > Wdl::Draw {
+            for mv in game.possible_moves() {
+                if game.board().at(mv.from_col, mv.from_row).piece().is_some_and(|(piece, _)| piece == Pawn)
+                    && game.board().at(mv.to_col, mv.to_row).is_empty()
+                {
+                    let mut child = game.clone();
+                    child.make_move(mv).ok()?;
+                    if child.result().is_none() && has_castling_or_en_passant(&child) {
+                        return None;
+                    }
+                    if self.probe_wdl_after(&child)? == wdl {
+                        return Some(wdl.before_zeroing());
+                    }
+                }
+            }
+        }
+        if let Some(dtz) = self.probe_dtz_table(game, wdl)? {
+            return Some(wdl.before_zeroing() + if wdl > Wdl::Draw { dtz } else { -dtz });
+        }
+
+        // the table only has the other side to move, so look one ply ahead
+        if wdl > Wdl::Draw {
+            let mut best: Option<i32> = None;
+            for mv in game.possible_moves() {
+                if game.board().at(mv.to_col, mv.to_row).is_occupied()
+                    || game.board().at(mv.from_col, mv.from_row).piece().is_some_and(|(piece, _)| piece == Pawn)
+                {
+                    continue;
+                }
+                let mut child = game.clone();
+                child.make_move(mv).ok()?;
+                let dtz = -self.probe_dtz_after(&child)?;
+                if dtz > 0 && best.is_none_or(|best| dtz + 1 < best) {
+                    best = Some(dtz + 1);
+                }
+            }
+            best
+        } else {
+            let mut best = -1;
+            for mv in game.possible_moves() {
+                let mut child = game.clone();
+                child.make_move(mv).ok()?;
+                let dtz = if child.halfmove_clock() == 0 {
+                    if wdl == Wdl::Loss {
+                        -1
+                    } else if self.probe_captures(&child, Wdl::CursedWin, Wdl::Win)?.0 == Wdl::Win {
+                        0
+                    } else {
+                        -101
+                    }
+                } else {
+                    -self.probe_dtz_after(&child)? - 1
+                };
+                best = best.min(dtz);
+            }
+            Some(best)
+        }
+    }
+
+    // Results of the positions after a move, where the game can be over
+    fn probe_wdl_after(&self, child: &Game) -> Option<Wdl> {
+        match child.result() {
+            Some(result) if result.winner.is_some() => Some(Wdl::Loss),
+            Some(_) => Some(Wdl::Draw),
+            None => Some(self.probe_captures(child, Wdl::Loss, Wdl::Win)?.0.opposite()),
+        }
+    }
+
+    fn probe_dtz_after(&self, child: &Game) -> Option<i32> {
+        match child.result() {
+            Some(result) if result.winner.is_some() => Some(-1),
+            Some(_) => Some(0),
+            None => self.probe_dtz_unchecked(child),
+        }
+    }
+
+    // The table value of a position, Ok(None) if the table only has the other side to move
+    fn probe_dtz_table(&self, game: &Game, wdl: Wdl) -> Option<Option<i32>> {
+        let pieces = squares(game.board());
+        let table = find_table(&self.dtz, &pieces)?;
+        let (side, index, black_side) = table.encode(&pieces, game.turn())?;
+        if (side.flags & STM != 0) != black_side && (!table.symmetric || table.has_pawns) {
+            return Some(None);
+        }
+        let value = table.decompress(side, index)?;
+        let value = match side.dtz_maps {
+            Some(maps) if side.flags & WIDE != 0 => read_u16(&table.data, maps[wdl.map_index()] + 2 * value as usize)?,
+            Some(maps) => read_u8(&table.data, maps[wdl.map_index()] + value as usize)? as u16,
+            None => value,
+        } as i32;
+        let plies = match wdl {
+            Wdl::Win => side.flags & WIN_PLIES != 0,
+            Wdl::Loss => side.flags & LOSS_PLIES != 0,
+            _ => false,
+        };
+        Some(Some(if plies { value } else { 2 * value }))
+    }
+}
+
+// Rank of a root move by its distance to zeroing: all wins within the 50-move rule are equal, then the wins that
+// it spoils by how close they get, and the other way round for losses
+fn rank(dtz: i32, halfmove_clock: i32) -> i32 {
+    if dtz > 0 {
+        if dtz + halfmove_clock <= 99 { MAX_DTZ } else { MAX_DTZ - (dtz + halfmove_clock) }
+    } else if dtz < 0 {
+        if -dtz * 2 + halfmove_clock < 100 { -MAX_DTZ } else { -MAX_DTZ + (-dtz + halfmove_clock) }
+    } else {
+        0
+    }
+}
+
+// The pieces of the board with their squares, row * 8 + col
+fn squares(board: &Board) -> Vec<(Kind, u8)> {
+    let mut pieces = Vec::new();
+    for row in 0..8 {
+        for col in 0..8 {
+            if let Some(kind) = board.at(col, row).piece() {
+                pieces.push((kind, (row * 8 + col) as u8));
+            }
+        }
+    }
+    pieces
+}
+
+fn find_table<'a>(tables: &'a HashMap<String, TableFile>, pieces: &[(Kind, u8)]) -> Option<&'a Table> {
+    let kinds: Vec<Kind> = pieces.iter().map(|(kind, _)| *kind).collect();
+    let swapped: Vec<Kind> = kinds.iter().map(|&(piece, color)| (piece, color.opposite())).collect();
+    tables.get(&material_signature(&kinds)).or_else(|| tables.get(&material_signature(&swapped)))?.table()
+}
+
+// A table file, read when it's first probed
+struct TableFile {
+    path: PathBuf,
+    kinds: Vec<Kind>,
+    is_wdl: bool,
+    table: OnceLock<Option<Table>>,
+}
+
+impl TableFile {
+    fn table(&self) -> Option<&Table> {
+        self.table
+            .get_or_init(|| {
+                let data = std::fs::read(&self.path).ok()?;
+                Table::parse(data, &self.kinds, self.is_wdl)
+            })
+            .as_ref()
+    }
+}
+
+struct Table {
+    data: Vec<u8>,
+    // signature of the pieces as the table has them, the board colors are swapped when it doesn't match
+    signature: String,
+    symmetric: bool,
+    has_pawns: bool,
+    // number of pieces that are the only one of their kind and color
+    unique_pieces: usize,
+    // by the file of the leading pawn (one without pawns) and by the side to move
+    files: Vec<Vec<SideTable>>,
+}
+
+// How the positions of a side to move, and of a file of the leading pawn, are indexed and compressed
+#[derive(Default)]
+struct SideTable {
+    flags: u8,
+    // pieces in the order they are indexed, the leading pawns or the first two or three unique pieces first
+    pieces: Vec<Kind>,
+    // sizes of the groups of pieces indexed together, and what the index of each group is multiplied by
+    group_lens: Vec<usize>,
+    group_factors: Vec<u64>,
+    // the value of every position of a single value table
+    single_value: u8,
+    block_size: usize,
+    span: u64,
+    blocks: usize,
+    min_symlen: usize,
+    // lowest symbol of each length, left aligned in 64 bits
+    base: Vec<u64>,
+    // number of values of each symbol minus one
+    symlen: Vec<u8>,
+    // offsets in the file
+    lowest_sym: usize,
+    btree: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    block_lengths: usize,
+    block_lengths_size: usize,
+    data: usize,
+    // DTZ value maps for a win, a loss, a cursed win and a blessed loss
+    dtz_maps: Option<[usize; 4]>,
+}
+
+impl Table {
+    fn parse(data: Vec<u8>, kinds: &[Kind], is_wdl: bool) -> Option<Table> {
+        let magic = if is_wdl { WDL_MAGIC } else { DTZ_MAGIC };
+        if data.get(..4)? != magic {
+            return None;
+        }
+        let swapped: Vec<Kind> = kinds.iter().map(|&(piece, color)| (piece, color.opposite())).collect();
+        let signature = material_signature(kinds);
+        let symmetric = signature == material_signature(&swapped);
+        let has_pawns = kinds.iter().any(|(piece, _)| *piece == Pawn);
+        let both_pawns = kinds.contains(&(Pawn, White)) && kinds.contains(&(Pawn, Black));
+        let unique_pieces = kinds.iter().filter(|kind| kinds.iter().filter(|other| other == kind).count() == 1).count();
+        let layout = read_u8(&data, 4)?;
+        if (layout & HAS_PAWNS != 0) != has_pawns || (layout & SPLIT != 0) == symmetric || unique_pieces < 2 {
+            return None;
+        }
+
+        let file_count = if has_pawns { 4 } else { 1 };
+        let side_count = if is_wdl && !symmetric { 2 } else { 1 };
+        let mut ptr = 5;
+        let mut files = Vec::new();
+        for file in 0..file_count {
+            let first = read_u8(&data, ptr)?;
+            let second = if both_pawns { read_u8(&data, ptr + 1)? } else { 0xff };
+            ptr += 1 + both_pawns as usize;
+            let mut sides = Vec::new();
+            for side in 0..side_count {
+                let nibble = |byte: u8| if side == 0 { byte & 0xf } else { byte >> 4 };
+                let order = [nibble(first), nibble(second)];
+                let pieces = (0..kinds.len())
+                    .map(|i| read_u8(&data, ptr + i).and_then(|byte| piece_kind(nibble(byte))))
+                    .collect::<Option<Vec<Kind>>>()?;
+                let piece_signature = material_signature(&pieces);
+                if piece_signature != signature && piece_signature != material_signature(&swapped) {
+                    return None;
+                }
+                sides.push(SideTable::new(pieces, order, file, has_pawns, unique_pieces)?);
+            }
+            ptr += kinds.len();
+            files.push(sides);
+        }
+        ptr += ptr & 1;
+
+        for side in files.iter_mut().flatten() {
+            ptr = side.read_compression(&data, ptr)?;
+        }
+        if !is_wdl {
+            for sides in &mut files {
+                let side = &mut sides[0];
+                if side.flags & MAPPED == 0 {
+                    continue;
+                }
+                let mut maps = [0; 4];
+                if side.flags & WIDE != 0 {
+                    ptr += ptr & 1;
+                    for map in &mut maps {
+                        *map = ptr + 2;
+                        ptr += 2 * read_u16(&data, ptr)? as usize + 2;
+                    }
+                } else {
+                    for map in &mut maps {
+                        *map = ptr + 1;
+                        ptr += read_u8(&data, ptr)? as usize + 1;
+                    }
+                }
+                side.dtz_maps = Some(maps);
+            }
+            ptr += ptr & 1;
+        }
+        for side in files.iter_mut().flatten() {
+            side.sparse_index = ptr;
+            ptr += side.sparse_index_size * 6;
+        }
+        for side in files.iter_mut().flatten() {
+            side.block_lengths = ptr;
+            ptr += side.block_lengths_size * 2;
+        }
+        for side in files.iter_mut().flatten() {
+            ptr = (ptr + 0x3f) & !0x3f;
+            side.data = ptr;
+            ptr += side.blocks * side.block_size;
+        }
+        if ptr > data.len() {
+            return None;
+        }
+
+        let signature = material_signature(&files[0][0].pieces);
+        Some(Table { data, signature, symmetric, has_pawns, unique_pieces, files })
+    }
+
+    // Index of the position in the table, with the side table that has it and whether that is the black side
+    // of the table, which is the board seen with the colors swapped if the table has them the other way round
+    fn encode(&self, pieces: &[(Kind, u8)], turn: PieceColor) -> Option<(&SideTable, u64, bool)> {
+        let indexes = &*INDEXES;
+        let kinds: Vec<Kind> = pieces.iter().map(|(kind, _)| *kind).collect();
+        let flip = (self.symmetric && turn == Black) || material_signature(&kinds) != self.signature;
+        let black_side = (turn == Black) != flip;
+        let board_kind = |(piece, color): Kind| (piece, if flip { color.opposite() } else { color });
+        let table_square = |square: u8| if flip { square ^ 56 } else { square };
+
+        let mut used = 0u64;
+        let mut squares: Vec<u8> = Vec::with_capacity(pieces.len());
+        let file = if self.has_pawns {
+            let lead = board_kind(self.files[0][0].pieces[0]);
+            for &(kind, square) in pieces {
+                if kind == lead {
+                    used |= 1 << square;
+                    squares.push(table_square(square));
+                }
+            }
+            let first = (0..squares.len()).max_by_key(|&i| indexes.map_pawns[squares[i] as usize])?;
+            squares.swap(0, first);
+            let file = (squares[0] & 7) as usize;
+            if file >= 4 { 7 - file } else { file }
+        } else {
+            0
+        };
+        let sides = &self.files[file];
+        let side = &sides[if black_side { sides.len() - 1 } else { 0 }];
+        let lead_pawns = squares.len();
+        for &kind in &side.pieces[lead_pawns..] {
+            let kind = board_kind(kind);
+            let &(_, square) = pieces.iter().find(|&&(other, square)| other == kind && used & (1 << square) == 0)?;
+            used |= 1 << square;
+            squares.push(table_square(square));
+        }
+        if squares[0] & 7 >= 4 {
+            squares.iter_mut().for_each(|square| *square ^= 7);
+        }
+
+        let mut index = if self.has_pawns {
+            let mut index = indexes.lead_pawn[lead_pawns][squares[0] as usize];
+            squares[1..lead_pawns].sort_by_key(|&square| indexes.map_pawns[square as usize]);
+            for (i, &square) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                index += binomial(indexes.map_pawns[square as usize], i as u64);
+            }
+            index
+        } else {
+            if squares[0] >> 3 >= 4 {
+                squares.iter_mut().for_each(|square| *square ^= 56);
+            }
+            for i in 0..side.group_lens[0] {
+                if !off_diagonal(squares[i]) {
+                    continue;
+                }
+                if squares[i] >> 3 > squares[i] & 7 {
+                    squares[i..].iter_mut().for_each(|square| *square = flip_diagonal(*square));
+                }
+                break;
+            }
+            if self.unique_pieces > 2 {
+                encode_three(&squares, indexes)
+            } else {
+                indexes.kk[indexes.triangle[squares[0] as usize] as usize][squares[1] as usize]
+            }
+        };
+        index *= side.group_factors[0];
+
+        let mut remaining_pawns = self.has_pawns && side.group_lens.len() > 1 && side.pieces[lead_pawns].0 == Pawn;
+        let mut start = side.group_lens[0];
+        for (next, &len) in side.group_lens.iter().enumerate().skip(1) {
+            let (previous, rest) = squares.split_at_mut(start);
+            let group = &mut rest[..len];
+            group.sort_unstable();
+            let mut n = 0;
+            for (i, &square) in group.iter().enumerate() {
+                let below = previous.iter().filter(|&&other| square > other).count() as u64;
+                let pawn_rank = if remaining_pawns { 8 } else { 0 };
+                n += binomial((square as u64).checked_sub(below + pawn_rank)?, i as u64 + 1);
+            }
+            remaining_pawns = false;
+            index += n * side.group_factors[next];
+            start += len;
+        }
+        Some((side, index, black_side))
+    }
+
+    // The value of a position: finds its block through the sparse index, decodes the Huffman symbols of the block
+    // up to the one that has the position, then expands the symbol through its binary tree
+    fn decompress(&self, side: &SideTable, index: u64) -> Option<u16> {
+        if side.flags & SINGLE_VALUE != 0 {
+            return Some(side.single_value as u16);
+        }
+        let data = &self.data;
+        let k = (index / side.span) as usize;
+        let mut block = read_u32(data, side.sparse_index + 6 * k)? as usize;
+        let mut offset =
+            read_u16(data, side.sparse_index + 6 * k + 4)? as i64 + (index % side.span) as i64 - (side.span / 2) as i64;
+        let block_length = |block: usize| {
+            (block < side.block_lengths_size).then_some(())?;
+            read_u16(data, side.block_lengths + 2 * block).map(|length| length as i64 + 1)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)?;
+        }
+        while offset >= block_length(block)? {
+            offset -= block_length(block)?;
+            block += 1;
+        }
+
+        let mut ptr = side.data + block * side.block_size;
+        let mut buffer = read_u64_be(data, ptr)?;
+        ptr += 8;
+        let mut buffer_bits = 64usize;
+        let mut symbol;
+        loop {
+            let mut len = 0;
+            while buffer < *side.base.get(len)? {
+                len += 1;
+            }
+            let shift = 64usize.checked_sub(len + side.min_symlen)?;
+            symbol = ((buffer - side.base[len]).checked_shr(shift as u32).unwrap_or(0)) as usize
+                + read_u16(data, side.lowest_sym + 2 * len)? as usize;
+            let values = *side.symlen.get(symbol)? as i64 + 1;
+            if offset < values {
+                break;
+            }
+            offset -= values;
+            len += side.min_symlen;
+            buffer = buffer.checked_shl(len as u32).unwrap_or(0);
+            buffer_bits = buffer_bits.checked_sub(len)?;
+            if buffer_bits <= 32 {
+                buffer_bits += 32;
+                buffer |= (read_u32_be(data, ptr)? as u64) << (64 - buffer_bits);
+                ptr += 4;
+            }
+        }
+        while side.symlen[symbol] != 0 {
+            let (left, right) = symbol_children(data, side.btree, symbol)?;
+            let left_values = *side.symlen.get(left as usize)? as i64 + 1;
+            if offset < left_values {
+                symbol = left as usize;
+            } else {
+                offset -= left_values;
+                symbol = right as usize;
+            }
+        }
+        Some(symbol_children(data, side.btree, symbol)?.0)
+    }
+}
+
+impl SideTable {
+    fn new(pieces: Vec<Kind>, order: [u8; 2], file: usize, has_pawns: bool, unique_pieces: usize) -> Option<SideTable> {
+        let indexes = &*INDEXES;
+        let first_len = if has_pawns {
+            0
+        } else if unique_pieces >= 3 {
+            3
+        } else {
+            2
+        };
+        let mut group_lens = Vec::new();
+        if first_len > 0 {
+            group_lens.push(first_len);
+        }
+        let mut i = first_len;
+        while i < pieces.len() {
+            let len = pieces[i..].iter().take_while(|&&kind| kind == pieces[i]).count();
+            group_lens.push(len);
+            i += len;
+        }
+        if has_pawns && group_lens[0] > 5 {
+            return None;
+        }
+
+        let pawn_groups = if has_pawns && group_lens.len() > 1 && pieces[group_lens[0]].0 == Pawn { 2 } else { 1 };
+        let mut group_factors = vec![0; group_lens.len() + 1];
+        let mut free = 64 - group_lens[0] as u64 - if pawn_groups == 2 { group_lens[1] as u64 } else { 0 };
+        let mut next = pawn_groups;
+        let mut size = 1u64;
+        let mut k = 0;
+        while next < group_lens.len() || k == order[0] || k == order[1] {
+            if k == order[0] {
+                group_factors[0] = size;
+                size *= if has_pawns {
+                    indexes.lead_pawns_size[group_lens[0]][file]
+                } else if unique_pieces >= 3 {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                group_factors[1] = size;
+                size *= binomial(48 - group_lens[0] as u64, group_lens[1] as u64);
+            } else {
+                group_factors[next] = size;
+                size *= binomial(free, group_lens[next] as u64);
+                free -= group_lens[next] as u64;
+                next += 1;
+            }
+            k += 1;
+            if k > 15 {
+                return None;
+            }
+        }
+        group_factors[group_lens.len()] = size;
+        Some(SideTable { pieces, group_lens, group_factors, ..SideTable::default() })
+    }
+
+    // Reads the compression parameters of the side table and returns the offset that follows them
+    fn read_compression(&mut self, data: &[u8], ptr: usize) -> Option<usize> {
+        self.flags = read_u8(data, ptr)?;
+        if self.flags & SINGLE_VALUE != 0 {
+            self.single_value = read_u8(data, ptr + 1)?;
+            return Some(ptr + 2);
+        }
+        let size = self.group_factors[self.group_lens.len()];
+        self.block_size = 1usize.checked_shl(read_u8(data, ptr + 1)? as u32)?;
+        self.span = 1u64.checked_shl(read_u8(data, ptr + 2)? as u32)?;
+        self.sparse_index_size = size.div_ceil(self.span) as usize;
+        self.blocks = read_u32(data, ptr + 4)? as usize;
+        self.block_lengths_size = self.blocks + read_u8(data, ptr + 3)? as usize;
+        let max_symlen = read_u8(data, ptr + 8)? as usize;
+        self.min_symlen = read_u8(data, ptr + 9)? as usize;
+        if self.min_symlen == 0 || max_symlen < self.min_symlen || max_symlen > 64 {
+            return None;
+        }
+        let lengths = max_symlen - self.min_symlen + 1;
+        self.lowest_sym = ptr + 10;
+        self.base = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = read_u16(data, self.lowest_sym + 2 * i)? as u64;
+            let next = read_u16(data, self.lowest_sym + 2 * i + 2)? as u64;
+            self.base[i] = (self.base[i + 1] + lowest).checked_sub(next)? / 2;
+        }
+        for (i, base) in self.base.iter_mut().enumerate() {
+            *base = base.checked_shl((64 - (self.min_symlen + i)) as u32).unwrap_or(0);
+        }
+
+        let ptr = self.lowest_sym + 2 * lengths;
+        let symbols = read_u16(data, ptr)? as usize;
+        self.btree = ptr + 2;
+        self.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for symbol in 0..symbols {
+            symbol_length(data, self.btree, &mut self.symlen, &mut visited, symbol)?;
+        }
+        Some(self.btree + 3 * symbols + (symbols & 1))
+    }
+}
+
+// The two children of a symbol in the binary tree of the table, 0xfff on the right for a leaf
+fn symbol_children(data: &[u8], btree: usize, symbol: usize) -> Option<(u16, u16)> {
+    let [b0, b1, b2] = bytes::<3>(data, btree + 3 * symbol)?.map(u16::from);
+    Some((((b1 & 0xf) << 8) | b0, (b2 << 4) | (b1 >> 4)))
+}
+
+fn symbol_length(data: &[u8], btree: usize, symlen: &mut [u8], visited: &mut [bool], symbol: usize) -> Option<()> {
+    if *visited.get(symbol)? {
+        return Some(());
+    }
+    visited[symbol] = true;
+    let (left, right) = symbol_children(data, btree, symbol)?;
+    if right != 0xfff {
+        let (left, right) = (left as usize, right as usize);
+        symbol_length(data, btree, symlen, visited, left)?;
+        symbol_length(data, btree, symlen, visited, right)?;
+        symlen[symbol] = symlen[left].checked_add(symlen[right])?.checked_add(1)?;
+    }
+    Some(())
+}
+
+fn piece_kind(code: u8) -> Option<Kind> {
+    let piece = match code & 7 {
+        1 => Pawn,
+        2 => Knight,
+        3 => Bishop,
+        4 => Rook,
+        5 => Queen,
+        6 => King,
+        _ => return None,
+    };
+    Some((piece, if code & 8 != 0 { Black } else { White }))
+}
+
+fn bytes<const N: usize>(data: &[u8], at: usize) -> Option<[u8; N]> {
+    data.get(at..at.checked_add(N)?)?.try_into().ok()
+}
+
+fn read_u8(data: &[u8], at: usize) -> Option<u8> {
+    data.get(at).copied()
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes(data, at)?))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes(data, at)?))
+}
+
+fn read_u32_be(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes(data, at)?))
+}
+
+fn read_u64_be(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(bytes(data, at)?))
+}
+
+// Index of the first three unique pieces of a pawnless table, the first one in the a1-d1-d4 triangle
+fn encode_three(squares: &[u8], indexes: &Indexes) -> u64 {
+    let [s0, s1, s2] = [squares[0], squares[1], squares[2]].map(u64::from);
+    let adjust1 = (s1 > s0) as u64;
+    let adjust2 = (s2 > s0) as u64 + (s2 > s1) as u64;
+    let rank = |square: u64| square >> 3;
+    if off_diagonal(squares[0]) {
+        indexes.triangle[s0 as usize] * 63 * 62 + (s1 - adjust1) * 62 + (s2 - adjust2)
+    } else if off_diagonal(squares[1]) {
+        6 * 63 * 62 + rank(s0) * 28 * 62 + indexes.lower[s1 as usize] * 62 + s2 - adjust2
+    } else if off_diagonal(squares[2]) {
+        6 * 63 * 62 + 4 * 28 * 62 + rank(s0) * 7 * 28 + (rank(s1) - adjust1) * 28 + indexes.lower[s2 as usize]
+    } else {
+        6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + rank(s0) * 7 * 6 + (rank(s1) - adjust1) * 6 + (rank(s2) - adjust2)
+    }
+}
+
+fn off_diagonal(square: u8) -> bool {
+    square >> 3 != square & 7
+}
+
+fn flip_diagonal(square: u8) -> u8 {
+    (square >> 3) | ((square & 7) << 3)
+}
+
+fn binomial(n: u64, k: u64) -> u64 {
+    if k > n {
+        return 0;
+    }
+    (0..k).fold(1, |result, i| result * (n - i) / (i + 1))
+}
+
+// Indexes of the squares of the first pieces of a table, which use the symmetries of the board
+struct Indexes {
+    // a1-d1-d4 triangle square of every square: b1 to d3 below the diagonal are 0 to 5, a1 to d4 are 6 to 9
+    triangle: [u64; 64],
+    // squares below the a1-h8 diagonal are 0 to 27 and the diagonal 28 to 35, squares above as their mirror
+    lower: [u64; 64],
+    // the two kings of a table without other unique pieces by the triangle square of the first one, 462 in all
+    kk: [[u64; 64]; 10],
+    // pawn squares of the a-d files, and their mirror, from the 7th rank down
+    map_pawns: [u64; 64],
+    // first index of the leading pawn by the number of leading pawns, and the table size by their file
+    lead_pawn: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+static INDEXES: LazyLock<Indexes> = LazyLock::new(Indexes::new);
+
+impl Indexes {
+    fn new() -> Indexes {
+        let mut indexes = Indexes {
+            triangle: [0; 64],
+            lower: [0; 64],
+            kk: [[0; 64]; 10],
+            map_pawns: [0; 64],
+            lead_pawn: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        let in_triangle = |square: u8| square & 7 <= 3 && square >> 3 <= square & 7;
+        let mut triangle_squares = Vec::new();
+        for diagonal in [false, true] {
+            for square in (0..64).filter(|&square| in_triangle(square) && off_diagonal(square) != diagonal) {
+                triangle_squares.push(square);
+            }
+        }
+        for square in 0..64u8 {
+            let mut canonical = square;
+            if canonical & 7 >= 4 {
+                canonical ^= 7;
+            }
+            if canonical >> 3 >= 4 {
+                canonical ^= 56;
+            }
+            if canonical >> 3 > canonical & 7 {
+                canonical = flip_diagonal(canonical);
+            }
+            indexes.triangle[square as usize] = triangle_squares.iter().position(|&s| s == canonical).unwrap() as u64;
+        }
+
+        for (code, square) in (0..64u8).filter(|&square| square & 7 > square >> 3).enumerate() {
+            indexes.lower[square as usize] = code as u64;
+        }
+        for square in 0..64u8 {
+            if !off_diagonal(square) {
+                indexes.lower[square as usize] = 28 + (square >> 3) as u64;
+            } else if square >> 3 > square & 7 {
+                indexes.lower[square as usize] = indexes.lower[flip_diagonal(square) as usize];
+            }
+        }
+
+        let mut code = 0;
+        for (i, &first) in triangle_squares.iter().enumerate() {
+            let mut both_diagonal = Vec::new();
+            for second in 0..64u8 {
+                let (dc, dr) = ((first & 7).abs_diff(second & 7), (first >> 3).abs_diff(second >> 3));
+                if dc <= 1 && dr <= 1 {
+                    continue;
+                }
+                if !off_diagonal(first) && second >> 3 > second & 7 {
+                    continue;
+                }
+                if !off_diagonal(first) && !off_diagonal(second) {
+                    both_diagonal.push(second);
+                    continue;
+                }
+                indexes.kk[i][second as usize] = code;
+                code += 1;
+            }
+            for second in both_diagonal {
+                indexes.kk[i][second as usize] = code;
+                code += 1;
+            }
+        }
+
+        let mut available = 47;
+        for lead_pawns in 1..=5 {
+            for file in 0..4u8 {
+                let mut index = 0;
+                for rank in 1..7u8 {
+                    let square = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        indexes.map_pawns[square as usize] = available;
+                        indexes.map_pawns[(square ^ 7) as usize] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    indexes.lead_pawn[lead_pawns][square as usize] = index;
+                    index += binomial(indexes.map_pawns[square as usize], lead_pawns as u64 - 1);
+                }
+                indexes.lead_pawns_size[lead_pawns][file as usize] = index;
+            }
+        }
+        indexes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexes_cover_the_symmetries() {
+        let indexes = &*INDEXES;
+        assert_eq!(&indexes.triangle[..8], &[6, 0, 1, 2, 2, 1, 0, 6]);
+        assert_eq!(&indexes.triangle[8..16], &[0, 7, 3, 4, 4, 3, 7, 0]);
+        assert_eq!(indexes.lower[1], 0);
+        assert_eq!(indexes.lower[9], 29);
+        assert_eq!(indexes.kk.iter().flatten().max(), Some(&461));
+        assert_eq!(binomial(48, 2), 1128);
+    }
+
+    #[test]
+    fn ranks_root_moves_by_the_fifty_move_rule() {
+        assert_eq!(rank(21, 0), MAX_DTZ);
+        assert!(rank(21, 90) < MAX_DTZ && rank(21, 90) > 0);
+        assert!(rank(-5, 0) < rank(-5, 98));
+        assert_eq!(rank(0, 50), 0);
+    }
+}
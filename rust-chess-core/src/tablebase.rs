@@ -0,0 +1,876 @@
+use crate::board::PieceColor::{Black, White};
+use crate::board::PieceType::{Bishop, King, Knight, Pawn, Queen, Rook};
+use crate::board::{Board, PieceColor, PieceType};
+use crate::game::{CastlingRights, Game};
+use crate::mate_solver::all_legal_moves;
+use crate::r#move::Move;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Largest number of pieces, kings included, that tables can be generated for
+pub const MAX_TABLEBASE_PIECES: usize = 4;
+pub const TABLEBASE_EXTENSION: &str = "rctb";
+
+const FILE_MAGIC: &[u8; 4] = b"RCTB";
+const FILE_VERSION: u8 = 1;
+
+// Table entries, from the point of view of the side to move: 0 is a draw, 1..=127 a mate in that many plies,
+// 128..=254 getting mated in (entry - 128) plies and 255 an illegal position
+const DRAW: u8 = 0;
+const LOSS: u8 = 128;
+const INVALID: u8 = 255;
+const MAX_PLIES: usize = 127;
+
+// file encoding, see `write_table`
+const MIN_RUN: usize = 3;
+const MAX_RUN: usize = 127 + MIN_RUN;
+const MAX_LITERALS: usize = 128;
+
+pub(crate) type Kind = (PieceType, PieceColor);
+
+/// Result of a position with perfect play, from the point of view of the side to move
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TbResult {
+    /// Mates in this many plies
+    Win(u32),
+    Draw,
+    /// Gets mated in this many plies
+    Loss(u32),
+}
+
+impl TbResult {
+    fn from_entry(entry: u8) -> Option<TbResult> {
+        match entry {
+            DRAW => Some(TbResult::Draw),
+            INVALID => None,
+            LOSS.. => Some(TbResult::Loss((entry - LOSS) as u32)),
+            _ => Some(TbResult::Win(entry as u32)),
+        }
+    }
+
+    // Result of the position before the move that led to this one
+    fn parent(self) -> TbResult {
+        match self {
+            TbResult::Win(plies) => TbResult::Loss(plies + 1),
+            TbResult::Draw => TbResult::Draw,
+            TbResult::Loss(plies) => TbResult::Win(plies + 1),
+        }
+    }
+
+    // Higher is better: fast wins first, slow losses last
+    fn rank(self) -> i64 {
+        match self {
+            TbResult::Win(plies) => 1000 - plies as i64,
+            TbResult::Draw => 0,
+            TbResult::Loss(plies) => -1000 + plies as i64,
+        }
+    }
+}
+
+/// Endgame tablebases with win/draw/loss and distance to mate for positions with up to MAX_TABLEBASE_PIECES pieces.
+///
+/// The tables are built by retrograde analysis with `generate` and can be saved to and loaded from a directory,
+/// one compressed file per material signature, e.g. "KQvKR.rctb".
+/// Castling and en passant are not part of the tables, positions where they are possible are not probed.
+#[derive(Default)]
+pub struct Tablebases {
+    // entries of each table by material signature, indexed by `index`
+    tables: HashMap<String, Vec<u8>>,
+}
+
+impl Tablebases {
+    pub fn new() -> Tablebases {
+        Tablebases::default()
+    }
+
+    /// Loads all tables of a directory
+    pub fn load_dir(dir: &Path) -> Result<Tablebases, String> {
+        let mut tablebases = Tablebases::new();
+        let entries = std::fs::read_dir(dir).map_err(|err| format!("Error reading {}: {}", dir.display(), err))?;
+        for entry in entries {
+            let path = entry.map_err(|err| err.to_string())?.path();
+            if path.extension().is_some_and(|extension| extension == TABLEBASE_EXTENSION) {
+                let file = File::open(&path).map_err(|err| format!("Error opening {}: {}", path.display(), err))?;
+                tablebases
+                    .read_table(&mut BufReader::new(file))
+                    .map_err(|err| format!("{}: {}", path.display(), err))?;
+            }
+        }
+        Ok(tablebases)
+    }
+
+    /// Saves all tables to a directory, which must exist
+    pub fn save_dir(&self, dir: &Path) -> Result<(), String> {
+        for signature in self.signatures() {
+            let path = dir.join(format!("{}.{}", signature, TABLEBASE_EXTENSION));
+            let file = File::create(&path).map_err(|err| format!("Error creating {}: {}", path.display(), err))?;
+            let mut writer = BufWriter::new(file);
+            self.write_table(signature, &mut writer)
+                .and_then(|_| writer.flush())
+                .map_err(|err| format!("Error writing {}: {}", path.display(), err))?;
+        }
+        Ok(())
+    }
+
+    /// Material signatures of the available tables, sorted
+    pub fn signatures(&self) -> Vec<&str> {
+        let mut signatures: Vec<&str> = self.tables.keys().map(|signature| signature.as_str()).collect();
+        signatures.sort();
+        signatures
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Generates the table for a material signature like "KQvK", "KPK" or "KQvKR", and all tables it depends on.
+    /// The colors can be given either way round, "KvKR" generates "KRvK".
+    pub fn generate(&mut self, signature: &str) -> Result<(), String> {
+        let kinds = canonical_kinds(&parse_signature(signature)?);
+        self.generate_kinds(&kinds)
+    }
+
+    /// Probes the position, None if there is no table for it
+    pub fn probe(&self, game: &Game) -> Option<TbResult> {
+        if self.tables.is_empty() {
+            return None;
+        }
+        let placement = Placement::from_board(game.board())?;
+        if has_castling_or_en_passant(game) {
+            return None;
+        }
+        self.lookup(&placement, game.turn())
+    }
+
+    /// The best move of the position with its result, i.e. the fastest mate when winning
+    /// and the longest resistance when losing
+    pub fn best_move(&self, game: &Game) -> Option<(Move, TbResult)> {
+        self.probe(game)?;
+        let mut best: Option<(Move, TbResult)> = None;
+        for mv in all_legal_moves(game) {
+            let mut child = game.clone();
+            child.make_move(&mv).ok()?;
+            let result = match child.result() {
+                Some(result) if result.winner.is_some() => TbResult::Loss(0),
+                Some(_) => TbResult::Draw,
+                None => self.probe(&child)?,
+            }
+            .parent();
+            if best.is_none_or(|(_, best)| result.rank() > best.rank()) {
+                best = Some((mv, result));
+            }
+        }
+        best
+    }
+
+    /// The moves of the best line from the position, until mate or as long as the tables are available
+    pub fn best_line(&self, game: &Game, max_plies: usize) -> Vec<Move> {
+        let mut game = game.clone();
+        let mut line = Vec::new();
+        while line.len() < max_plies
+            && game.result().is_none()
+            && let Some((mv, result)) = self.best_move(&game)
+            && result != TbResult::Draw
+        {
+            if game.make_move(&mv).is_err() {
+                break;
+            }
+            line.push(mv);
+        }
+        line
+    }
+
+    fn lookup(&self, placement: &Placement, turn: PieceColor) -> Option<TbResult> {
+        // two bare kings
+        if placement.len == 2 {
+            return Some(TbResult::Draw);
+        }
+        let swap = needs_color_swap(placement.kinds());
+        let mut pieces: Vec<(Kind, u8)> = placement
+            .kinds()
+            .iter()
+            .zip(placement.squares())
+            .map(|(&(piece, color), &square)| if swap { ((piece, color.opposite()), square ^ 56) } else { ((piece, color), square) })
+            .collect();
+        pieces.sort_by_key(|((piece, color), _)| (*color == Black, kind_order(*piece)));
+        let turn = if swap { turn.opposite() } else { turn };
+
+        let kinds: Vec<Kind> = pieces.iter().map(|(kind, _)| *kind).collect();
+        let squares: Vec<u8> = pieces.iter().map(|(_, square)| *square).collect();
+        let table = self.tables.get(&material_signature(&kinds))?;
+        TbResult::from_entry(table[index(&squares, turn)])
+    }
+
+    fn generate_kinds(&mut self, kinds: &[Kind]) -> Result<(), String> {
+        let signature = material_signature(kinds);
+        if kinds.len() <= 2 || self.tables.contains_key(&signature) {
+            return Ok(());
+        }
+        if kinds.len() > MAX_TABLEBASE_PIECES {
+            return Err(format!("{} has more than {} pieces", signature, MAX_TABLEBASE_PIECES));
+        }
+
+        // captures and promotions lead to other tables, which are needed first
+        for (i, &(piece, color)) in kinds.iter().enumerate() {
+            if piece == King {
+                continue;
+            }
+            let mut captured = kinds.to_vec();
+            captured.remove(i);
+            self.generate_kinds(&canonical_kinds(&captured))?;
+            if piece == Pawn {
+                for promotion in [Queen, Rook, Bishop, Knight] {
+                    let mut promoted = kinds.to_vec();
+                    promoted[i] = (promotion, color);
+                    self.generate_kinds(&canonical_kinds(&promoted))?;
+                }
+            }
+        }
+
+        let entries = self.retrograde_analysis(kinds)?;
+        self.tables.insert(signature, entries);
+        Ok(())
+    }
+
+    // Solves all positions of the table layer by layer: first the mates, then the positions where the side to move
+    // can mate in one ply or all moves lead to a mate in one ply, and so on. Positions that are never reached are draws.
+    fn retrograde_analysis(&self, kinds: &[Kind]) -> Result<Vec<u8>, String> {
+        let count = kinds.len();
+        let size = table_size(count);
+        let mut entries = vec![INVALID; size];
+        // moves that stay in this table and whose result isn't known yet
+        let mut unknown = vec![0u8; size];
+        // plies of the slowest loss among the known moves
+        let mut longest_loss = vec![0u8; size];
+        // plies of the fastest known win, 0 if none
+        let mut win_in = vec![0u8; size];
+        let mut has_draw = vec![false; size];
+        let mut done = vec![false; size];
+        let mut wins: Vec<Vec<u32>> = vec![Vec::new(); MAX_PLIES + 2];
+        let mut losses: Vec<Vec<u32>> = vec![Vec::new(); MAX_PLIES + 2];
+
+        let mut moves = Vec::new();
+        for index in 0..size {
+            let (squares, turn) = decode(index, count);
+            let placement = Placement::new(kinds, &squares[..count]);
+            if !placement.is_valid(turn) {
+                continue;
+            }
+            entries[index] = DRAW;
+
+            moves.clear();
+            placement.pseudo_moves(turn, &mut moves);
+            let mut legal_moves = 0;
+            for mv in &moves {
+                let child = placement.after(mv);
+                if child.in_check(turn) {
+                    continue;
+                }
+                legal_moves += 1;
+                if mv.capture.is_none() && mv.promotion.is_none() {
+                    unknown[index] += 1;
+                    continue;
+                }
+                let child_result = self
+                    .lookup(&child, turn.opposite())
+                    .ok_or_else(|| format!("Missing table for {}", material_signature(child.kinds())))?;
+                match child_result.parent() {
+                    TbResult::Win(plies) if win_in[index] == 0 || plies < win_in[index] as u32 => win_in[index] = plies as u8,
+                    TbResult::Win(_) => {}
+                    TbResult::Draw => has_draw[index] = true,
+                    TbResult::Loss(plies) => longest_loss[index] = longest_loss[index].max(plies as u8),
+                }
+            }
+
+            if legal_moves == 0 {
+                if placement.in_check(turn) {
+                    losses[0].push(index as u32);
+                } else {
+                    done[index] = true;
+                }
+            } else if win_in[index] > 0 {
+                wins[win_in[index] as usize].push(index as u32);
+            } else if unknown[index] == 0 && !has_draw[index] {
+                losses[longest_loss[index] as usize].push(index as u32);
+            }
+        }
+
+        let mut parents = Vec::new();
+        for plies in 0..=MAX_PLIES {
+            for is_win in [true, false] {
+                let layer = std::mem::take(if is_win { &mut wins[plies] } else { &mut losses[plies] });
+                for index in layer {
+                    let index = index as usize;
+                    if done[index] {
+                        continue;
+                    }
+                    done[index] = true;
+                    entries[index] = if is_win { plies as u8 } else { LOSS + plies as u8 };
+
+                    let (squares, turn) = decode(index, count);
+                    let mover = turn.opposite();
+                    parents.clear();
+                    Placement::new(kinds, &squares[..count]).unmoves(mover, &mut parents);
+                    for parent_squares in &parents {
+                        let parent = self::index(&parent_squares[..count], mover);
+                        if entries[parent] == INVALID || done[parent] {
+                            continue;
+                        }
+                        let parent_plies = plies + 1;
+                        if parent_plies > MAX_PLIES {
+                            return Err(format!("Mates longer than {} plies are not supported", MAX_PLIES));
+                        }
+                        if !is_win {
+                            if win_in[parent] == 0 || win_in[parent] as usize > parent_plies {
+                                win_in[parent] = parent_plies as u8;
+                                wins[parent_plies].push(parent as u32);
+                            }
+                        } else {
+                            unknown[parent] -= 1;
+                            longest_loss[parent] = longest_loss[parent].max(parent_plies as u8);
+                            if unknown[parent] == 0 && win_in[parent] == 0 && !has_draw[parent] {
+                                losses[longest_loss[parent] as usize].push(parent as u32);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Writes a table: magic, version, signature and the entries, compressed with run-length encoding
+    pub fn write_table(&self, signature: &str, writer: &mut impl Write) -> std::io::Result<()> {
+        let Some(table) = self.tables.get(signature) else {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("No table for {}", signature)));
+        };
+        writer.write_all(FILE_MAGIC)?;
+        writer.write_all(&[FILE_VERSION, signature.len() as u8])?;
+        writer.write_all(signature.as_bytes())?;
+
+        // PackBits: a header byte below 128 is followed by that many + 1 literal entries,
+        // otherwise the next entry is repeated header - 128 + MIN_RUN times
+        let mut literals_start = 0;
+        let mut i = 0;
+        while i < table.len() {
+            let run = table[i..].iter().take(MAX_RUN).take_while(|&&entry| entry == table[i]).count();
+            if run >= MIN_RUN || i + 1 == table.len() {
+                let literals_end = if run >= MIN_RUN { i } else { i + 1 };
+                for chunk in table[literals_start..literals_end].chunks(MAX_LITERALS) {
+                    writer.write_all(&[(chunk.len() - 1) as u8])?;
+                    writer.write_all(chunk)?;
+                }
+                if run >= MIN_RUN {
+                    writer.write_all(&[(run - MIN_RUN + 128) as u8, table[i]])?;
+                    i += run;
+                } else {
+                    i += 1;
+                }
+                literals_start = i;
+            } else {
+                i += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a table written by `write_table` and adds it
+    pub fn read_table(&mut self, reader: &mut impl Read) -> Result<(), String> {
+        let mut header = [0u8; 6];
+        reader.read_exact(&mut header).map_err(|err| err.to_string())?;
+        if &header[..4] != FILE_MAGIC || header[4] != FILE_VERSION {
+            return Err("Not a tablebase file".to_string());
+        }
+        let mut signature = vec![0u8; header[5] as usize];
+        reader.read_exact(&mut signature).map_err(|err| err.to_string())?;
+        let signature = String::from_utf8(signature).map_err(|_| "Invalid signature")?;
+        let kinds = parse_signature(&signature)?;
+        if material_signature(&kinds) != signature || canonical_kinds(&kinds) != kinds {
+            return Err(format!("Invalid signature {}", signature));
+        }
+
+        let size = table_size(kinds.len());
+        let mut entries = Vec::with_capacity(size);
+        while entries.len() < size {
+            let mut header = [0u8];
+            reader.read_exact(&mut header).map_err(|_| "Unexpected end of file")?;
+            let header = header[0] as usize;
+            let (count, is_run) = if header < 128 { (header + 1, false) } else { (header - 128 + MIN_RUN, true) };
+            if entries.len() + count > size {
+                return Err("Too many entries".to_string());
+            }
+            let start = entries.len();
+            entries.resize(start + if is_run { 1 } else { count }, 0);
+            reader.read_exact(&mut entries[start..]).map_err(|_| "Unexpected end of file")?;
+            if is_run {
+                entries.resize(start + count, entries[start]);
+            }
+        }
+        self.tables.insert(signature, entries);
+        Ok(())
+    }
+}
+
+// Castling and en passant aren't part of the tables, so positions where they are possible can't be probed
+pub(crate) fn has_castling_or_en_passant(game: &Game) -> bool {
+    game.castling_rights() != CastlingRights::none()
+        || game.en_passant_target().is_some()
+            && game
+                .possible_moves()
+                .iter()
+                .any(|mv| game.board().is_en_passant_move(mv).is_some())
+}
+
+/// Parses a material signature like "KQvKR" or, without the separator, "KQKR"
+pub(crate) fn parse_signature(signature: &str) -> Result<Vec<Kind>, String> {
+    let upper = signature.to_ascii_uppercase();
+    let (white, black) = match upper.split_once('V') {
+        Some(sides) => sides,
+        None => match upper.get(1..).and_then(|rest| rest.find('K')) {
+            Some(i) => upper.split_at(i + 1),
+            None => return Err(format!("Invalid material signature {}", signature)),
+        },
+    };
+    let mut kinds = Vec::new();
+    for (side, color) in [(white, White), (black, Black)] {
+        for c in side.chars() {
+            let piece = match c {
+                'K' => King,
+                'Q' => Queen,
+                'R' => Rook,
+                'B' => Bishop,
+                'N' => Knight,
+                'P' => Pawn,
+                _ => return Err(format!("Invalid piece {} in material signature {}", c, signature)),
+            };
+            kinds.push((piece, color));
+        }
+        if side.chars().filter(|&c| c == 'K').count() != 1 {
+            return Err(format!("Expected one king per side in material signature {}", signature));
+        }
+    }
+    Ok(kinds)
+}
+
+pub(crate) fn material_signature(kinds: &[Kind]) -> String {
+    let mut sorted = kinds.to_vec();
+    sorted.sort_by_key(|(piece, color)| (*color == Black, kind_order(*piece)));
+    let mut signature = String::new();
+    for (piece, color) in sorted {
+        if color == Black && !signature.contains('v') {
+            signature.push('v');
+        }
+        signature.push(match piece {
+            King => 'K',
+            Queen => 'Q',
+            Rook => 'R',
+            Bishop => 'B',
+            Knight => 'N',
+            Pawn => 'P',
+        });
+    }
+    signature
+}
+
+fn kind_order(piece: PieceType) -> usize {
+    match piece {
+        King => 0,
+        Queen => 1,
+        Rook => 2,
+        Bishop => 3,
+        Knight => 4,
+        Pawn => 5,
+    }
+}
+
+// Tables are stored with the stronger side as White, by material and then by signature
+fn needs_color_swap(kinds: &[Kind]) -> bool {
+    let side = |color: PieceColor| {
+        let mut pieces: Vec<PieceType> = kinds.iter().filter(|(_, c)| *c == color).map(|(piece, _)| *piece).collect();
+        pieces.sort_by_key(|piece| kind_order(*piece));
+        let value: u32 = pieces
+            .iter()
+            .map(|piece| match piece {
+                Queen => 9,
+                Rook => 5,
+                Bishop | Knight => 3,
+                Pawn => 1,
+                King => 0,
+            })
+            .sum();
+        (value, pieces.iter().map(|piece| Reverse(kind_order(*piece))).collect::<Vec<_>>())
+    };
+    side(Black) > side(White)
+}
+
+fn canonical_kinds(kinds: &[Kind]) -> Vec<Kind> {
+    let swap = needs_color_swap(kinds);
+    let mut canonical: Vec<Kind> = kinds
+        .iter()
+        .map(|&(piece, color)| (piece, if swap { color.opposite() } else { color }))
+        .collect();
+    canonical.sort_by_key(|(piece, color)| (*color == Black, kind_order(*piece)));
+    canonical
+}
+
+// The white king is kept on the files a-d by mirroring the board, so the table has 32 white king squares
+fn table_size(piece_count: usize) -> usize {
+    2 * 32 * 64usize.pow(piece_count as u32 - 1)
+}
+
+// `squares` in signature order, a square is row * 8 + col
+fn index(squares: &[u8], turn: PieceColor) -> usize {
+    let mirror = if squares[0] & 7 >= 4 { 7 } else { 0 };
+    let king = squares[0] ^ mirror;
+    let mut index = (turn == Black) as usize * 32 + (king >> 3) as usize * 4 + (king & 7) as usize;
+    for &square in &squares[1..] {
+        index = index * 64 + (square ^ mirror) as usize;
+    }
+    index
+}
+
+fn decode(mut index: usize, piece_count: usize) -> ([u8; MAX_TABLEBASE_PIECES], PieceColor) {
+    let mut squares = [0u8; MAX_TABLEBASE_PIECES];
+    for square in squares[1..piece_count].iter_mut().rev() {
+        *square = (index % 64) as u8;
+        index /= 64;
+    }
+    let king = index % 32;
+    squares[0] = ((king / 4) * 8 + king % 4) as u8;
+    (squares, if index / 32 == 0 { White } else { Black })
+}
+
+#[derive(Copy, Clone)]
+struct TbMove {
+    piece: usize,
+    to: u8,
+    capture: Option<usize>,
+    promotion: Option<PieceType>,
+}
+
+const KING_STEPS: [(i8, i8); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+const KNIGHT_JUMPS: [(i8, i8); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (-1, 1), (-1, -1), (1, -1)];
+
+// A small position representation for the generator, much faster than `Game` for a handful of pieces
+#[derive(Copy, Clone)]
+struct Placement {
+    kinds: [Kind; MAX_TABLEBASE_PIECES],
+    squares: [u8; MAX_TABLEBASE_PIECES],
+    len: usize,
+}
+
+impl Placement {
+    fn new(kinds: &[Kind], squares: &[u8]) -> Placement {
+        let mut placement = Placement {
+            kinds: [(King, White); MAX_TABLEBASE_PIECES],
+            squares: [0; MAX_TABLEBASE_PIECES],
+            len: kinds.len(),
+        };
+        placement.kinds[..kinds.len()].copy_from_slice(kinds);
+        placement.squares[..kinds.len()].copy_from_slice(squares);
+        placement
+    }
+
+    // None if there are too many pieces
+    fn from_board(board: &Board) -> Option<Placement> {
+        let mut kinds = Vec::new();
+        let mut squares = Vec::new();
+        for row in 0..8 {
+            for col in 0..8 {
+                if let Some(kind) = board.at(col, row).piece() {
+                    if kinds.len() == MAX_TABLEBASE_PIECES {
+                        return None;
+                    }
+                    kinds.push(kind);
+                    squares.push((row * 8 + col) as u8);
+                }
+            }
+        }
+        Some(Placement::new(&kinds, &squares))
+    }
+
+    fn kinds(&self) -> &[Kind] {
+        &self.kinds[..self.len]
+    }
+
+    fn squares(&self) -> &[u8] {
+        &self.squares[..self.len]
+    }
+
+    fn occupied(&self) -> u64 {
+        self.squares().iter().fold(0, |occupied, &square| occupied | 1 << square)
+    }
+
+    fn piece_at(&self, square: u8) -> Option<usize> {
+        self.squares().iter().position(|&s| s == square)
+    }
+
+    fn is_valid(&self, turn: PieceColor) -> bool {
+        let occupied = self.occupied();
+        if occupied.count_ones() as usize != self.len {
+            return false;
+        }
+        let pawn_on_last_row = self
+            .kinds()
+            .iter()
+            .zip(self.squares())
+            .any(|(&(piece, _), &square)| piece == Pawn && !(8..56).contains(&square));
+        !pawn_on_last_row && !self.in_check(turn.opposite())
+    }
+
+    fn in_check(&self, color: PieceColor) -> bool {
+        let king = self.kinds().iter().position(|&kind| kind == (King, color)).expect("Both kings are on the board");
+        self.is_attacked(self.squares[king], color.opposite())
+    }
+
+    fn is_attacked(&self, target: u8, by: PieceColor) -> bool {
+        let occupied = self.occupied();
+        self.kinds()
+            .iter()
+            .zip(self.squares())
+            .any(|(&(piece, color), &from)| color == by && attacks(piece, color, from, target, occupied))
+    }
+
+    fn after(&self, mv: &TbMove) -> Placement {
+        let mut child = *self;
+        child.squares[mv.piece] = mv.to;
+        if let Some(promotion) = mv.promotion {
+            child.kinds[mv.piece].0 = promotion;
+        }
+        if let Some(captured) = mv.capture {
+            for i in captured..self.len - 1 {
+                child.kinds[i] = child.kinds[i + 1];
+                child.squares[i] = child.squares[i + 1];
+            }
+            child.len -= 1;
+        }
+        child
+    }
+
+    fn pseudo_moves(&self, turn: PieceColor, moves: &mut Vec<TbMove>) {
+        for (i, (&(piece, color), &from)) in self.kinds().iter().zip(self.squares()).enumerate() {
+            if color != turn {
+                continue;
+            }
+            match piece {
+                King | Knight => {
+                    let steps = if piece == King { &KING_STEPS } else { &KNIGHT_JUMPS };
+                    for &(dc, dr) in steps {
+                        if let Some(to) = offset(from, dc, dr) {
+                            self.add_move(i, to, moves);
+                        }
+                    }
+                }
+                Rook | Bishop | Queen => {
+                    let rook = if piece == Bishop { &[][..] } else { &ROOK_DIRECTIONS[..] };
+                    let bishop = if piece == Rook { &[][..] } else { &BISHOP_DIRECTIONS[..] };
+                    for &(dc, dr) in rook.iter().chain(bishop) {
+                        let mut square = from;
+                        while let Some(to) = offset(square, dc, dr) {
+                            if !self.add_move(i, to, moves) {
+                                break;
+                            }
+                            square = to;
+                        }
+                    }
+                }
+                Pawn => self.add_pawn_moves(i, from, color, moves),
+            }
+        }
+    }
+
+    // Adds a move to an empty square or a capture, returns whether a slider can continue behind the square
+    fn add_move(&self, piece: usize, to: u8, moves: &mut Vec<TbMove>) -> bool {
+        let color = self.kinds[piece].1;
+        match self.piece_at(to) {
+            None => {
+                moves.push(TbMove {
+                    piece,
+                    to,
+                    capture: None,
+                    promotion: None,
+                });
+                true
+            }
+            Some(other) => {
+                if self.kinds[other].1 != color && self.kinds[other].0 != King {
+                    moves.push(TbMove {
+                        piece,
+                        to,
+                        capture: Some(other),
+                        promotion: None,
+                    });
+                }
+                false
+            }
+        }
+    }
+
+    fn add_pawn_moves(&self, piece: usize, from: u8, color: PieceColor, moves: &mut Vec<TbMove>) {
+        let (direction, start_row) = if color == White { (1, 1) } else { (-1, 6) };
+        let mut targets = Vec::with_capacity(4);
+        if let Some(to) = offset(from, 0, direction).filter(|&to| self.piece_at(to).is_none()) {
+            targets.push((to, None));
+            if (from >> 3) as i8 == start_row
+                && let Some(to) = offset(to, 0, direction).filter(|&to| self.piece_at(to).is_none())
+            {
+                targets.push((to, None));
+            }
+        }
+        for dc in [-1, 1] {
+            if let Some(to) = offset(from, dc, direction)
+                && let Some(other) = self.piece_at(to)
+                && self.kinds[other].1 != color
+                && self.kinds[other].0 != King
+            {
+                targets.push((to, Some(other)));
+            }
+        }
+        for (to, capture) in targets {
+            if !(8..56).contains(&to) {
+                for promotion in [Queen, Rook, Bishop, Knight] {
+                    moves.push(TbMove {
+                        piece,
+                        to,
+                        capture,
+                        promotion: Some(promotion),
+                    });
+                }
+            } else {
+                moves.push(TbMove {
+                    piece,
+                    to,
+                    capture,
+                    promotion: None,
+                });
+            }
+        }
+    }
+
+    // Squares of the positions before a move of `mover` that didn't capture or promote
+    fn unmoves(&self, mover: PieceColor, parents: &mut Vec<[u8; MAX_TABLEBASE_PIECES]>) {
+        let occupied = self.occupied();
+        let empty = |square: u8| occupied & (1 << square) == 0;
+        for (i, (&(piece, color), &to)) in self.kinds().iter().zip(self.squares()).enumerate() {
+            if color != mover {
+                continue;
+            }
+            let mut add = |from: u8| {
+                let mut parent = self.squares;
+                parent[i] = from;
+                parents.push(parent);
+            };
+            match piece {
+                King | Knight => {
+                    let steps = if piece == King { &KING_STEPS } else { &KNIGHT_JUMPS };
+                    for &(dc, dr) in steps {
+                        if let Some(from) = offset(to, dc, dr).filter(|&from| empty(from)) {
+                            add(from);
+                        }
+                    }
+                }
+                Rook | Bishop | Queen => {
+                    let rook = if piece == Bishop { &[][..] } else { &ROOK_DIRECTIONS[..] };
+                    let bishop = if piece == Rook { &[][..] } else { &BISHOP_DIRECTIONS[..] };
+                    for &(dc, dr) in rook.iter().chain(bishop) {
+                        let mut square = to;
+                        while let Some(from) = offset(square, dc, dr).filter(|&from| empty(from)) {
+                            add(from);
+                            square = from;
+                        }
+                    }
+                }
+                Pawn => {
+                    let (back, double_row) = if color == White { (-1, 3) } else { (1, 4) };
+                    if let Some(from) = offset(to, 0, back).filter(|&from| empty(from)) {
+                        if (8..56).contains(&from) {
+                            add(from);
+                        }
+                        if (to >> 3) as i8 == double_row
+                            && let Some(from) = offset(from, 0, back).filter(|&from| empty(from))
+                        {
+                            add(from);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn offset(square: u8, dc: i8, dr: i8) -> Option<u8> {
+    let col = (square & 7) as i8 + dc;
+    let row = (square >> 3) as i8 + dr;
+    if (0..8).contains(&col) && (0..8).contains(&row) { Some((row * 8 + col) as u8) } else { None }
+}
+
+fn attacks(piece: PieceType, color: PieceColor, from: u8, target: u8, occupied: u64) -> bool {
+    let dc = (target & 7) as i8 - (from & 7) as i8;
+    let dr = (target >> 3) as i8 - (from >> 3) as i8;
+    let straight = (dc == 0) != (dr == 0);
+    let diagonal = dc != 0 && dc.abs() == dr.abs();
+    match piece {
+        King => dc.abs() <= 1 && dr.abs() <= 1 && (dc, dr) != (0, 0),
+        Knight => matches!((dc.abs(), dr.abs()), (1, 2) | (2, 1)),
+        Pawn => dc.abs() == 1 && dr == if color == White { 1 } else { -1 },
+        Rook => straight && is_path_clear(from, dc, dr, occupied),
+        Bishop => diagonal && is_path_clear(from, dc, dr, occupied),
+        Queen => (straight || diagonal) && is_path_clear(from, dc, dr, occupied),
+    }
+}
+
+// Checks the squares between `from` and `from + (dc, dr)`, which must be on a line
+fn is_path_clear(from: u8, dc: i8, dr: i8, occupied: u64) -> bool {
+    let steps = dc.abs().max(dr.abs());
+    let mut square = from;
+    for _ in 1..steps {
+        square = offset(square, dc.signum(), dr.signum()).expect("Path stays on the board");
+        if occupied & (1 << square) != 0 {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexes_round_trip() {
+        let squares = [3, 12, 63];
+        let (decoded, turn) = decode(index(&squares, Black), 3);
+        assert_eq!(&decoded[..3], &squares);
+        assert_eq!(turn, Black);
+
+        // the white king on the e-h files is mirrored
+        assert_eq!(index(&[4, 12, 63], White), index(&[3, 11, 56], White));
+    }
+
+    #[test]
+    fn parses_signatures() -> Result<(), String> {
+        assert_eq!(material_signature(&parse_signature("kqkr")?), "KQvKR");
+        assert_eq!(material_signature(&canonical_kinds(&parse_signature("KvKQ")?)), "KQvK");
+        assert!(parse_signature("KQ").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn run_length_encoding_round_trips() -> Result<(), String> {
+        let mut tablebases = Tablebases::new();
+        tablebases.generate("KQvK")?;
+        let mut data = Vec::new();
+        tablebases.write_table("KQvK", &mut data).map_err(|err| err.to_string())?;
+        assert!(data.len() < table_size(3));
+
+        let mut loaded = Tablebases::new();
+        loaded.read_table(&mut data.as_slice())?;
+        assert!(loaded.tables["KQvK"] == tablebases.tables["KQvK"]);
+        Ok(())
+    }
+}
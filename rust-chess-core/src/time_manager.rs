@@ -0,0 +1,172 @@
+use crate::r#move::Move;
+use std::time::Duration;
+
+// If the number of moves until the next time control is unknown, the game is assumed to last this many more moves
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+// The hard limit is this many times the soft limit, but never more than MAX_TIME_SHARE of the clock
+const HARD_LIMIT_FACTOR: u32 = 4;
+const MAX_TIME_SHARE: f64 = 0.4;
+// When this is the last move before the time control, almost the whole clock can be used
+const LAST_MOVE_TIME_SHARE: f64 = 0.9;
+
+// A new iteration usually takes longer than all the previous ones together,
+// so it isn't started if this share of the soft limit is already used
+const NEW_ITERATION_SHARE: f64 = 0.6;
+
+// Extra time when the best move changes between iterations, decays by half every iteration
+const BEST_MOVE_CHANGE_EXTENSION: f64 = 0.5;
+// Extra time when the score drops by more than SCORE_DROP_MARGIN centipawns between iterations
+const SCORE_DROP_EXTENSION: f64 = 0.5;
+const SCORE_DROP_MARGIN: i32 = 30;
+
+/// Decides how long to think on a move in a game with a clock.
+///
+/// The soft limit is the time the search normally uses. It is checked between iterations and extended
+/// when the search is unsure about the best move. The hard limit is checked during the search and is never exceeded.
+#[derive(Clone, Debug)]
+pub struct TimeManager {
+    soft_limit: Duration,
+    hard_limit: Duration,
+    // multiplier of the soft limit, updated after every iteration
+    extension: f64,
+    best_move_changes: f64,
+    previous_best_move: Option<Move>,
+    previous_score: Option<i32>,
+}
+
+impl TimeManager {
+    /// `move_overhead` is reserved for communication delays and is never used for thinking.
+    pub fn new(
+        time_left: Duration,
+        increment: Duration,
+        moves_to_go: Option<u32>,
+        move_overhead: Duration,
+    ) -> TimeManager {
+        let usable = time_left.saturating_sub(move_overhead);
+        let moves_to_go = moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let max_time = if moves_to_go == 1 {
+            usable.mul_f64(LAST_MOVE_TIME_SHARE)
+        } else {
+            usable.mul_f64(MAX_TIME_SHARE)
+        };
+
+        let soft_limit = (usable / moves_to_go + increment * 3 / 4).min(max_time);
+        let hard_limit = (soft_limit * HARD_LIMIT_FACTOR).min(max_time).max(soft_limit);
+        Self::with_limits(soft_limit, hard_limit)
+    }
+
+    /// Time manager for a fixed time per move, which is never extended
+    pub fn fixed(move_time: Duration) -> TimeManager {
+        Self::with_limits(move_time, move_time)
+    }
+
+    fn with_limits(soft_limit: Duration, hard_limit: Duration) -> TimeManager {
+        TimeManager {
+            soft_limit,
+            hard_limit,
+            extension: 1.0,
+            best_move_changes: 0.0,
+            previous_best_move: None,
+            previous_score: None,
+        }
+    }
+
+    /// The soft limit with all the current extensions applied
+    pub fn soft_limit(&self) -> Duration {
+        self.soft_limit.mul_f64(self.extension).min(self.hard_limit)
+    }
+
+    pub fn hard_limit(&self) -> Duration {
+        self.hard_limit
+    }
+
+    /// Updates the extensions after a completed iteration. `score` is the internal search score.
+    pub fn on_iteration(&mut self, best_move: Option<Move>, score: i32) {
+        self.best_move_changes /= 2.0;
+        if self.previous_best_move.is_some() && best_move != self.previous_best_move {
+            self.best_move_changes += 1.0;
+        }
+
+        let score_drop = match self.previous_score {
+            Some(previous) if previous - score > SCORE_DROP_MARGIN => SCORE_DROP_EXTENSION,
+            _ => 0.0,
+        };
+
+        self.extension = 1.0 + self.best_move_changes * BEST_MOVE_CHANGE_EXTENSION + score_drop;
+        self.previous_best_move = best_move;
+        self.previous_score = Some(score);
+    }
+
+    /// Returns true if another iteration shouldn't be started
+    pub fn should_stop_iterating(&self, elapsed: Duration) -> bool {
+        elapsed >= self.soft_limit().mul_f64(NEW_ITERATION_SHARE)
+    }
+
+    /// Returns true if the search must be stopped immediately
+    pub fn is_hard_limit_reached(&self, elapsed: Duration) -> bool {
+        elapsed >= self.hard_limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_TIME: Duration = Duration::ZERO;
+
+    #[test]
+    fn limits_follow_the_clock() {
+        let tm = TimeManager::new(Duration::from_secs(60), NO_TIME, None, NO_TIME);
+        assert_eq!(tm.soft_limit(), Duration::from_secs(2));
+        assert_eq!(tm.hard_limit(), Duration::from_secs(8));
+
+        // the increment is mostly spent on top of the share of the clock
+        let tm = TimeManager::new(Duration::from_secs(60), Duration::from_secs(4), None, NO_TIME);
+        assert_eq!(tm.soft_limit(), Duration::from_secs(5));
+
+        // the hard limit never uses too much of the clock
+        let tm = TimeManager::new(Duration::from_secs(10), Duration::from_secs(10), None, NO_TIME);
+        assert_eq!(tm.hard_limit(), Duration::from_secs(4));
+        assert_eq!(tm.soft_limit(), Duration::from_secs(4));
+    }
+
+    #[test]
+    fn moves_to_go_and_overhead() {
+        let tm = TimeManager::new(Duration::from_secs(40), NO_TIME, Some(10), Duration::from_secs(10));
+        assert_eq!(tm.soft_limit(), Duration::from_secs(3));
+
+        // before the time control almost the whole clock can be used
+        let tm = TimeManager::new(Duration::from_secs(10), NO_TIME, Some(1), NO_TIME);
+        assert_eq!(tm.soft_limit(), Duration::from_secs(9));
+    }
+
+    #[test]
+    fn extends_on_instability_and_score_drops() {
+        let e2e4 = Some(Move::from_long_notation("e2e4"));
+        let d2d4 = Some(Move::from_long_notation("d2d4"));
+        let mut tm = TimeManager::new(Duration::from_secs(60), NO_TIME, None, NO_TIME);
+        let base = tm.soft_limit();
+
+        tm.on_iteration(e2e4, 20);
+        tm.on_iteration(e2e4, 25);
+        assert_eq!(tm.soft_limit(), base);
+
+        tm.on_iteration(d2d4, 25);
+        assert!(tm.soft_limit() > base);
+
+        let mut tm = TimeManager::new(Duration::from_secs(60), NO_TIME, None, NO_TIME);
+        tm.on_iteration(e2e4, 20);
+        tm.on_iteration(e2e4, -50);
+        assert!(tm.soft_limit() > base);
+        assert!(tm.soft_limit() <= tm.hard_limit());
+    }
+
+    #[test]
+    fn fixed_time_is_not_extended() {
+        let mut tm = TimeManager::fixed(Duration::from_secs(1));
+        tm.on_iteration(Some(Move::from_long_notation("e2e4")), 0);
+        tm.on_iteration(Some(Move::from_long_notation("d2d4")), -100);
+        assert_eq!(tm.soft_limit(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,239 @@
+use crate::board::PieceType;
+use crate::r#move::Move;
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+
+pub const DEFAULT_HASH_SIZE_MB: usize = 16;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Bound {
+    // the score is exact
+    Exact,
+    // the search failed high, the real score is at least this
+    Lower,
+    // the search failed low, the real score is at most this
+    Upper,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TtEntry {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u32,
+    pub bound: Bound,
+}
+
+// Each slot stores the key xor-ed with the data, so that an entry torn by two threads writing at the same time
+// doesn't match any key and is simply ignored. This keeps the table lock-free.
+#[derive(Default)]
+struct Slot {
+    key_xor_data: AtomicU64,
+    data: AtomicU64,
+}
+
+/// Hash table of search results shared by all search threads
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    // incremented for every new search, older entries are replaced first
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// Creates a table of at most `size_mb` megabytes. The number of slots is rounded down to a power of two.
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let max_slots = (size_mb.max(1) * 1024 * 1024 / size_of::<Slot>()).max(1);
+        let slot_count = 1 << max_slots.ilog2();
+        TranspositionTable {
+            slots: (0..slot_count).map(|_| Slot::default()).collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    /// Removes all entries, e.g. when a new game starts
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.key_xor_data.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Marks the start of a new search, so that the entries of previous searches are replaced first
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TtEntry> {
+        let slot = &self.slots[self.index(key)];
+        let data = slot.data.load(Ordering::Relaxed);
+        if data == 0 || slot.key_xor_data.load(Ordering::Relaxed) ^ data != key {
+            return None;
+        }
+        Some(unpack(data))
+    }
+
+    pub fn store(&self, key: u64, entry: TtEntry) {
+        let slot = &self.slots[self.index(key)];
+        let generation = self.generation.load(Ordering::Relaxed);
+        let old_data = slot.data.load(Ordering::Relaxed);
+        let same_position = slot.key_xor_data.load(Ordering::Relaxed) ^ old_data == key;
+        // keep deeper results of the current search for other positions
+        if old_data != 0
+            && !same_position
+            && data_generation(old_data) == generation
+            && data_depth(old_data) > entry.depth
+        {
+            return;
+        }
+
+        // keep the known best move if the new result doesn't have one
+        let entry = match entry.best_move {
+            None if same_position && old_data != 0 => TtEntry {
+                best_move: unpack(old_data).best_move,
+                ..entry
+            },
+            _ => entry,
+        };
+        let data = pack(&entry, generation);
+        slot.key_xor_data.store(key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    /// Permille of the table used by the current search, estimated from the first thousand slots
+    pub fn hashfull(&self) -> u32 {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let sample = self.slots.len().min(1000);
+        let used = self.slots[..sample]
+            .iter()
+            .filter(|slot| {
+                let data = slot.data.load(Ordering::Relaxed);
+                data != 0 && data_generation(data) == generation
+            })
+            .count();
+        (used * 1000 / sample) as u32
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key as usize) & (self.slots.len() - 1)
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_HASH_SIZE_MB)
+    }
+}
+
+// Data layout: move (16 bits), score (16 bits), depth (8 bits), bound (2 bits), generation (8 bits).
+// The bound is stored as 1..=3, so that a used slot never has zero data.
+fn pack(entry: &TtEntry, generation: u8) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+    pack_move(entry.best_move) as u64
+        | (entry.score as i16 as u16 as u64) << 16
+        | (entry.depth.min(255) as u64) << 32
+        | bound << 40
+        | (generation as u64) << 42
+}
+
+fn unpack(data: u64) -> TtEntry {
+    TtEntry {
+        best_move: unpack_move(data as u16),
+        score: (data >> 16) as u16 as i16 as i32,
+        depth: data_depth(data),
+        bound: match (data >> 40) & 0x3 {
+            2 => Bound::Lower,
+            3 => Bound::Upper,
+            _ => Bound::Exact,
+        },
+    }
+}
+
+fn data_depth(data: u64) -> u32 {
+    ((data >> 32) & 0xFF) as u32
+}
+
+fn data_generation(data: u64) -> u8 {
+    (data >> 42) as u8
+}
+
+// from square (6 bits), to square (6 bits), promotion (3 bits), 0 means no move
+fn pack_move(mv: Option<Move>) -> u16 {
+    let Some(mv) = mv else { return 0 };
+    let promotion = match mv.promotion_to {
+        None => 0,
+        Some(PieceType::Knight) => 1,
+        Some(PieceType::Bishop) => 2,
+        Some(PieceType::Rook) => 3,
+        Some(_) => 4,
+    };
+    let from = (mv.from_row * 8 + mv.from_col) as u16;
+    let to = (mv.to_row * 8 + mv.to_col) as u16;
+    from | to << 6 | promotion << 12
+}
+
+fn unpack_move(packed: u16) -> Option<Move> {
+    if packed == 0 {
+        return None;
+    }
+    let from = (packed & 0x3F) as i8;
+    let to = ((packed >> 6) & 0x3F) as i8;
+    let mv = Move::new(from % 8, from / 8, to % 8, to / 8);
+    let promotion = match packed >> 12 {
+        1 => Some(PieceType::Knight),
+        2 => Some(PieceType::Bishop),
+        3 => Some(PieceType::Rook),
+        4 => Some(PieceType::Queen),
+        _ => None,
+    };
+    Some(Move {
+        promotion_to: promotion,
+        ..mv
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_and_probes_entries() {
+        let tt = TranspositionTable::new(1);
+        let entry = TtEntry {
+            best_move: Some(Move::from_long_notation("e7e8n")),
+            score: -29990,
+            depth: 7,
+            bound: Bound::Lower,
+        };
+        tt.store(0x1234_5678_0000_0010, entry);
+
+        assert_eq!(tt.probe(0x1234_5678_0000_0010), Some(entry));
+        assert_eq!(tt.probe(0x1234_5678_0000_0011), None);
+        assert!(tt.hashfull() > 0);
+
+        tt.clear();
+        assert_eq!(tt.probe(0x1234_5678_0000_0010), None);
+    }
+
+    #[test]
+    fn keeps_deeper_entries_of_the_current_search() {
+        let tt = TranspositionTable::new(1);
+        let entry = |depth| TtEntry {
+            best_move: None,
+            score: 0,
+            depth,
+            bound: Bound::Exact,
+        };
+        let slots = tt.slots.len() as u64;
+        tt.store(1, entry(5));
+        tt.store(1 + slots, entry(2));
+        assert_eq!(tt.probe(1), Some(entry(5)));
+
+        // entries of old searches are replaced
+        tt.new_search();
+        tt.store(1 + slots, entry(2));
+        assert_eq!(tt.probe(1), None);
+        assert_eq!(tt.probe(1 + slots), Some(entry(2)));
+    }
+}
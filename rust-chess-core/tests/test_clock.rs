@@ -0,0 +1,179 @@
+use rust_chess_core::board::PieceColor::{Black, White};
+use rust_chess_core::clock::{Bonus, Clock, ManualTimeSource, TimeControl, TimedGame};
+use rust_chess_core::game::{Game, Termination};
+use rust_chess_core::r#move::Move;
+use std::time::Duration;
+
+fn secs(seconds: u64) -> Duration {
+    Duration::from_secs(seconds)
+}
+
+fn running_clock(control: &str) -> (Clock<ManualTimeSource>, ManualTimeSource) {
+    let time = ManualTimeSource::new();
+    let mut clock = Clock::with_time_source(TimeControl::parse(control).unwrap(), time.clone());
+    clock.start();
+    (clock, time)
+}
+
+#[test]
+fn parses_time_controls() {
+    let control = TimeControl::parse("40/90+30,30+30").unwrap();
+    assert_eq!(control.stages.len(), 2);
+    assert_eq!(control.stages[0].moves, Some(40));
+    assert_eq!(control.stages[0].time, secs(90 * 60));
+    assert_eq!(control.stages[1].bonus, Bonus::Increment(secs(30)));
+    assert_eq!(control.to_string(), "40/90+30,30+30");
+
+    assert_eq!(TimeControl::parse("5+3").unwrap(), TimeControl::increment(secs(300), secs(3)));
+    assert_eq!(TimeControl::parse("10d5").unwrap(), TimeControl::simple_delay(secs(600), secs(5)));
+    assert_eq!(TimeControl::parse("3b2").unwrap(), TimeControl::bronstein(secs(180), secs(2)));
+    assert_eq!(TimeControl::parse("0.5").unwrap(), TimeControl::sudden_death(secs(30)));
+    for invalid in ["", "0", "5+", "x/5", "0/5", "5+x"] {
+        assert!(TimeControl::parse(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn sudden_death_runs_out() {
+    let (mut clock, time) = running_clock("1");
+    time.advance(secs(20));
+    assert_eq!(clock.remaining(White), secs(40));
+    clock.press().unwrap();
+    assert_eq!(clock.turn(), Black);
+
+    // only the clock of the side to move runs
+    time.advance(secs(50));
+    assert_eq!(clock.remaining(White), secs(40));
+    assert_eq!(clock.remaining(Black), secs(10));
+    assert_eq!(clock.flagged(), None);
+    time.advance(secs(10));
+    assert_eq!(clock.flagged(), Some(Black));
+    assert!(clock.press().is_err());
+}
+
+#[test]
+fn applies_increments_and_delays() {
+    let (mut clock, time) = running_clock("1+5");
+    time.advance(secs(2));
+    clock.press().unwrap();
+    assert_eq!(clock.remaining(White), secs(63));
+
+    // Bronstein gives back the time used, up to the delay
+    let (mut clock, time) = running_clock("1b5");
+    time.advance(secs(2));
+    clock.press().unwrap();
+    assert_eq!(clock.remaining(White), secs(60));
+    time.advance(secs(8));
+    clock.press().unwrap();
+    assert_eq!(clock.remaining(Black), secs(57));
+
+    // simple delay: the clock counts down only after the delay
+    let (mut clock, time) = running_clock("1d5");
+    time.advance(secs(4));
+    assert_eq!(clock.remaining(White), secs(60));
+    assert_eq!(clock.time_until_flag(), Some(secs(61)));
+    time.advance(secs(4));
+    assert_eq!(clock.remaining(White), secs(57));
+    clock.press().unwrap();
+    assert_eq!(clock.remaining(White), secs(57));
+}
+
+#[test]
+fn adds_time_of_the_next_stage() {
+    let (mut clock, time) = running_clock("2/10,5+1");
+    assert_eq!(clock.moves_to_go(White), Some(2));
+    for _ in 0..2 {
+        time.advance(secs(60));
+        clock.press().unwrap();
+        time.advance(secs(30));
+        clock.press().unwrap();
+    }
+    assert_eq!(clock.remaining(White), secs(8 * 60 + 5 * 60));
+    assert_eq!(clock.remaining(Black), secs(9 * 60 + 5 * 60));
+    assert_eq!(clock.moves_in_stage(White), 0);
+    assert_eq!(clock.moves_to_go(White), None);
+    assert_eq!(clock.bonus(White), Bonus::Increment(secs(1)));
+
+    // the second stage has an increment
+    time.advance(secs(10));
+    clock.press().unwrap();
+    assert_eq!(clock.remaining(White), secs(13 * 60 - 9));
+    assert_eq!(clock.moves_in_stage(White), 1);
+
+    // a last stage with moves repeats
+    let (mut clock, _) = running_clock("1/1");
+    clock.press().unwrap();
+    assert_eq!(clock.remaining(White), secs(120));
+}
+
+#[test]
+fn stopped_clock_keeps_its_time() {
+    let (mut clock, time) = running_clock("1");
+    time.advance(secs(10));
+    clock.stop();
+    time.advance(secs(100));
+    assert_eq!(clock.remaining(White), secs(50));
+    clock.start();
+    time.advance(secs(5));
+    assert_eq!(clock.remaining(White), secs(45));
+}
+
+#[test]
+fn timed_game_ends_on_time() -> Result<(), String> {
+    let time = ManualTimeSource::new();
+    let control = TimeControl::sudden_death(secs(60));
+    let mut game = TimedGame::with_time_source(Game::new(), control.clone(), time.clone());
+    game.start();
+    time.advance(secs(10));
+    game.make_move(&Move::from_long_notation("e2e4"))?;
+    assert_eq!(game.clock().remaining(White), secs(50));
+
+    time.advance(secs(61));
+    assert!(game.make_move(&Move::from_long_notation("e7e5")).is_err());
+    assert_eq!(game.game().history().moves().len(), 1);
+    let result = game.game().result().unwrap();
+    assert_eq!(result.winner, Some(White));
+    assert_eq!(result.termination, Termination::Timeout);
+
+    // a lone king can't win on time
+    let position = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1")?;
+    let mut game = TimedGame::with_time_source(position, control, time.clone());
+    game.start();
+    time.advance(secs(60));
+    assert!(game.check_flag());
+    let result = game.game().result().unwrap();
+    assert_eq!(result.winner, None);
+    assert_eq!(result.termination, Termination::Timeout);
+    Ok(())
+}
+
+#[test]
+fn detects_insufficient_material() -> Result<(), String> {
+    let cases = [
+        ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", true),
+        ("4k3/8/8/8/8/8/8/2N1K3 w - - 0 1", true),
+        // the knight can mate when a black pawn blocks the king
+        ("4k3/4p3/8/8/8/8/8/2N1K3 w - - 0 1", false),
+        ("4k3/8/8/8/8/8/8/2NNK3 w - - 0 1", false),
+        ("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1", true),
+        // bishops of opposite shades
+        ("4k1b1/8/8/8/8/8/8/2B1K3 w - - 0 1", false),
+        ("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1", true),
+        ("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", false),
+        // a rook or queen can take the flight square of its own king, as in Kh1 Rg1 against Kg3 Bd5
+        ("4kr2/8/8/8/8/8/8/2B1K3 w - - 0 1", false),
+        ("4kq2/8/8/8/8/8/8/2B1K3 w - - 0 1", false),
+        // but a queen next to the king guards every square a knight could check from
+        ("4kq2/8/8/8/8/8/8/2N1K3 w - - 0 1", true),
+        ("4kr2/8/8/8/8/8/8/2N1K3 w - - 0 1", false),
+        // two bishops of one shade, or of different shades
+        ("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1", false),
+        ("4k3/8/8/8/8/8/4B3/2B1K3 w - - 0 1", false),
+        ("4k3/8/8/8/8/8/1B6/2B1K3 w - - 0 1", true),
+        ("4k3/8/8/8/8/8/8/2BNK3 w - - 0 1", false),
+    ];
+    for (fen, insufficient) in cases {
+        assert_eq!(Game::from_fen(fen)?.has_insufficient_material(White), insufficient, "{}", fen);
+    }
+    Ok(())
+}
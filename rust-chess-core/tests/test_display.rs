@@ -0,0 +1,66 @@
+use rust_chess_core::board::Board;
+use rust_chess_core::board::PieceColor::Black;
+use rust_chess_core::display::{DisplayOptions, PieceStyle, render};
+use rust_chess_core::game::Game;
+use rust_chess_core::pos::Pos;
+use rust_chess_core::r#move::Move;
+
+#[test]
+fn letters_match_the_board_display() -> Result<(), String> {
+    let game = Game::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")?;
+    for board in [Board::new_chess_game(), *game.board()] {
+        assert_eq!(render(&board, &DisplayOptions::default()), board.get_display_str(true));
+    }
+    Ok(())
+}
+
+#[test]
+fn renders_from_blacks_side() {
+    let options = DisplayOptions {
+        perspective: Black,
+        ..Default::default()
+    };
+    let display = render(&Board::new_chess_game(), &options);
+    let lines: Vec<&str> = display.lines().collect();
+    assert_eq!(lines[1], "1  wR wN wB wK wQ wB wN wR");
+    assert_eq!(lines[8], "8  bR bN bB bK bQ bB bN bR");
+    assert_eq!(lines[9], "    h  g  f  e  d  c  b  a");
+    assert_eq!(display, Board::new_chess_game().get_display_str_for(true, Black));
+}
+
+#[test]
+fn renders_glyphs_and_marks_destinations() -> Result<(), String> {
+    let game = Game::new();
+    let options = DisplayOptions {
+        style: PieceStyle::Unicode,
+        ..Default::default()
+    }
+    .mark_destinations(&game, Pos::from_notation("g1")?);
+    let display = render(game.board(), &options);
+    let lines: Vec<&str> = display.lines().collect();
+    assert_eq!(lines[1], "8  ♜  ♞  ♝  ♛  ♚  ♝  ♞  ♜ ");
+    assert_eq!(lines[6], "3     ·     ·     •     • ");
+    assert_eq!(lines[8], "1  ♖  ♘  ♗  ♕  ♔  ♗  ♘  ♖ ");
+    assert_eq!(lines[9], "   a  b  c  d  e  f  g  h");
+    Ok(())
+}
+
+#[test]
+fn colors_highlight_last_move_and_check() -> Result<(), String> {
+    let mut game = Game::from_fen("rnbqkbnr/ppppp1pp/8/5p2/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2")?;
+    game.make_move(&Move::from_long_notation("d1h5"))?;
+    let options = DisplayOptions {
+        colors: true,
+        ..Default::default()
+    }
+    .highlight_game(&game);
+    let display = render(game.board(), &options);
+    let lines: Vec<&str> = display.lines().collect();
+
+    // h5 is light, d1 is light, e8 is light and holds the checked king
+    assert!(lines[4].ends_with("\x1b[48;5;187m\x1b[38;5;231m wQ\x1b[0m"));
+    assert!(lines[8].contains("\x1b[48;5;187m\x1b[38;5;16m   "));
+    assert!(lines[1].contains("\x1b[48;5;167m\x1b[38;5;16m bK"));
+    assert_eq!(display.matches("48;5;187m").count() + display.matches("48;5;143m").count(), 2);
+    Ok(())
+}
@@ -1,5 +1,7 @@
 use rust_chess_core::board::PieceColor;
 use rust_chess_core::game::{Game};
+use rust_chess_core::r#move::Move;
+use rust_chess_core::pgn::parse_pgn;
 
 #[test]
 fn test_pgn_games() {
@@ -97,4 +99,64 @@ fn assert_game_result_str(game: &Game, game_result_str: &str) {
     } else {
         panic!("Invalid game result in PGN: {}", game_result_str);
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn undo_move_restores_previous_position() -> Result<(), String> {
+    let mut game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1")?;
+    let fen_before = game.to_fen();
+
+    let castle = Move::from_long_notation("e1g1");
+    game.make_move(&castle)?;
+    game.make_move(&Move::from_long_notation("a8a7"))?;
+
+    assert_eq!(game.undo_move(), Some(Move::from_long_notation("a8a7")));
+    assert_eq!(game.undo_move(), Some(castle));
+    assert_eq!(game.undo_move(), None);
+    assert_eq!(game.to_fen(), fen_before);
+    Ok(())
+}
+
+#[test]
+fn short_notation_round_trips_pgn_moves() {
+    for file in std::fs::read_dir("./tests/pgn_games").unwrap() {
+        let content = std::fs::read_to_string(file.unwrap().path()).unwrap();
+        for pgn_game in parse_pgn(&content) {
+            assert!(pgn_game.validate().is_empty());
+            pgn_game
+                .replay_with(|game, mv, pgn_move| {
+                    assert_eq!(game.to_short_notation(mv).unwrap(), pgn_move.san);
+                })
+                .unwrap();
+        }
+    }
+}
+
+#[test]
+fn short_notation_disambiguates() -> Result<(), String> {
+    let game = Game::from_fen("4k3/8/8/8/8/2N3N1/8/N3K2R w K - 0 1")?;
+    assert_eq!(game.to_short_notation(&Move::from_long_notation("c3e2"))?, "Nce2");
+    assert_eq!(game.to_short_notation(&Move::from_long_notation("a1b3"))?, "Nb3");
+    assert_eq!(game.to_short_notation(&Move::from_long_notation("e1g1"))?, "O-O");
+    assert_eq!(game.to_short_notation(&Move::from_long_notation("h1h8"))?, "Rh8+");
+
+    let game = Game::from_fen("4k3/1P6/8/8/8/8/8/N1N1K3 w - - 0 1")?;
+    assert_eq!(game.to_short_notation(&Move::from_long_notation("a1b3"))?, "Nab3");
+    assert_eq!(game.to_short_notation(&Move::from_long_notation("b7b8n"))?, "b8=N");
+    assert_eq!(game.to_short_notation(&Move::from_long_notation("b7b8q"))?, "b8=Q+");
+    Ok(())
+}
+
+#[test]
+fn en_passant_needs_a_pawn_double_step() -> Result<(), String> {
+    // the king steps from f7 to f6 like a double pawn step would pass it
+    let mut game = Game::from_fen("8/5k2/3KP3/8/8/8/8/8 b - - 0 1")?;
+    game.make_move(&Move::from_long_notation("f7f6"))?;
+    assert!(game.make_move(&Move::from_long_notation("e6f7")).is_err());
+    assert!(!game.possible_moves().contains(&Move::from_long_notation("e6f7")));
+
+    let mut game = Game::from_fen("4k3/5p2/8/4P3/8/8/8/4K3 b - - 0 1")?;
+    game.make_move(&Move::from_long_notation("f7f5"))?;
+    game.make_move(&Move::from_long_notation("e5f6"))?;
+    Ok(())
+}
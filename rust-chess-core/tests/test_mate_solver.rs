@@ -0,0 +1,62 @@
+use rust_chess_core::game::Game;
+use rust_chess_core::mate_solver::{AttackerMove, solve_mate};
+use rust_chess_core::r#move::Move;
+
+#[test]
+fn solves_mate_in_two_with_all_defences() -> Result<(), String> {
+    // Morphy: 1. Ra6! bxa6 2. b7#
+    let game = Game::from_fen("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1")?;
+    let solution = solve_mate(&game, 2);
+
+    assert!(solution.is_mate());
+    assert!(!solution.is_cooked());
+    assert!(solution.duals.is_empty());
+    let key = &solution.keys[0];
+    assert_eq!(key.mv, Move::from_long_notation("a1a6"));
+    assert_eq!(key.defences.len(), 7);
+    for defence in &key.defences {
+        assert_eq!(defence.continuations.len(), 1);
+        assert!(defence.continuations[0].defences.is_empty());
+    }
+
+    let tree = solution.format_tree(&game);
+    assert!(tree.starts_with("1. Ra6!\n"));
+    assert!(tree.contains("  1... bxa6 2. b7#\n"));
+    Ok(())
+}
+
+#[test]
+fn proves_there_is_no_shorter_mate() -> Result<(), String> {
+    let game = Game::from_fen("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1")?;
+    assert!(!solve_mate(&game, 1).is_mate());
+    Ok(())
+}
+
+#[test]
+fn reports_cooks_and_duals() -> Result<(), String> {
+    let game = Game::from_fen("4k3/8/4K3/8/8/8/8/R6R w - - 0 1")?;
+    let solution = solve_mate(&game, 1);
+    assert!(solution.is_cooked());
+    assert_eq!(solution.keys.len(), 2);
+
+    // 1. Kf7 Kh7 and White mates in three ways
+    let game = Game::from_fen("7k/8/5K2/8/8/8/8/6Q1 w - - 0 1")?;
+    let solution = solve_mate(&game, 2);
+    assert_eq!(solution.duals.len(), 1);
+    // every reported dual is a variation with more than one continuation
+    for dual in &solution.duals {
+        let mut moves = dual.iter();
+        let mut attacker_moves: &[AttackerMove] = &solution.keys;
+        let continuations = loop {
+            let (attacker_mv, defence_mv) = (moves.next(), moves.next());
+            let attacker = attacker_moves.iter().find(|node| Some(&node.mv) == attacker_mv).unwrap();
+            let defence = attacker.defences.iter().find(|node| Some(&node.mv) == defence_mv).unwrap();
+            if moves.len() == 0 {
+                break &defence.continuations;
+            }
+            attacker_moves = &defence.continuations;
+        };
+        assert!(continuations.len() > 1);
+    }
+    Ok(())
+}
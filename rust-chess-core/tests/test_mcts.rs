@@ -0,0 +1,78 @@
+use rust_chess_core::game::Game;
+use rust_chess_core::mcts::{LeafValue, MctsLimits, MctsOptions, MctsPlayer, Selection};
+use rust_chess_core::r#move::Move;
+
+fn player(selection: Selection, leaf_value: LeafValue) -> MctsPlayer {
+    let mut player = MctsPlayer::new(MctsOptions { selection, leaf_value });
+    player.set_seed(7);
+    player
+}
+
+#[test]
+fn finds_mate_in_one() -> Result<(), String> {
+    let game = Game::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1")?;
+    for selection in [Selection::puct(), Selection::uct()] {
+        let result = player(selection, LeafValue::Evaluation).search(&game, &MctsLimits::iterations(2000));
+        assert_eq!(result.best_move, Some(Move::from_long_notation("a1a8")));
+        assert!(result.root_moves[0].value > 0.9);
+    }
+    Ok(())
+}
+
+#[test]
+fn takes_hanging_queen_with_rollouts() -> Result<(), String> {
+    let game = Game::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1")?;
+    let leaf_value = LeafValue::Rollout { max_plies: 4 };
+    let result = player(Selection::puct(), leaf_value).search(&game, &MctsLimits::iterations(300));
+    assert_eq!(result.best_move, Some(Move::from_long_notation("d2d5")));
+    Ok(())
+}
+
+#[test]
+fn reports_visits_of_every_root_move() -> Result<(), String> {
+    let game = Game::new();
+    let result = player(Selection::uct(), LeafValue::Evaluation).search(&game, &MctsLimits::iterations(100));
+
+    assert_eq!(result.iterations, 100);
+    assert_eq!(result.reused_visits, 0);
+    assert_eq!(result.root_moves.len(), 20);
+    // the first iteration only expands the root
+    assert_eq!(result.root_moves.iter().map(|stats| stats.visits).sum::<u32>(), 99);
+    assert!(result.root_moves.windows(2).all(|pair| pair[0].visits >= pair[1].visits));
+    assert_eq!(result.best_move, Some(result.root_moves[0].mv));
+
+    let report = result.format_report(&game);
+    assert_eq!(report.lines().count(), 20);
+    assert!(report.lines().any(|line| line.starts_with("Nf3 ")));
+    Ok(())
+}
+
+#[test]
+fn reuses_the_tree_between_moves() -> Result<(), String> {
+    let mut game = Game::new();
+    let mut player = player(Selection::puct(), LeafValue::Evaluation);
+    let first = player.search(&game, &MctsLimits::iterations(500));
+
+    // the engine's move and the most visited reply are already in the tree
+    game.make_move(&first.best_move.unwrap())?;
+    let mut reply = game.clone();
+    reply.make_move(&player.search(&game, &MctsLimits::iterations(1)).best_move.unwrap())?;
+    let result = player.search(&reply, &MctsLimits::iterations(100));
+    assert!(result.reused_visits > 0);
+    let visits: u32 = result.root_moves.iter().map(|stats| stats.visits).sum();
+    assert_eq!(visits + 1, result.reused_visits + 100);
+
+    // an unrelated position starts a new tree
+    let other = Game::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1")?;
+    assert_eq!(player.search(&other, &MctsLimits::iterations(10)).reused_visits, 0);
+    Ok(())
+}
+
+#[test]
+fn finished_games_have_no_moves() -> Result<(), String> {
+    let game = Game::from_fen("R5k1/5ppp/8/8/8/8/5PPP/6K1 b - - 0 1")?;
+    let result = player(Selection::puct(), LeafValue::Evaluation).search(&game, &MctsLimits::default());
+    assert_eq!(result.best_move, None);
+    assert_eq!(result.iterations, 0);
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use rust_chess_core::problem::{Problem, Stipulation, parse_problems, solve_stipulation};
+use rust_chess_core::r#move::Move;
+
+fn parse_problem(text: &str) -> Result<Problem, String> {
+    let mut problems = parse_problems(text)?;
+    assert_eq!(problems.len(), 1);
+    Ok(problems.remove(0))
+}
+
+fn moves(line: &str) -> Vec<Move> {
+    line.split_whitespace().map(Move::from_long_notation).collect()
+}
+
+#[test]
+fn solves_helpmates_with_all_solutions() -> Result<(), String> {
+    let problem = parse_problem("Pieces White Kc2 Rh8 Black Ka1 pa2 Bg8\nStipulation h#1\nTwin Stipulation h#1.5")?;
+    let solutions = problem.solve()?;
+    assert_eq!(solutions.len(), 2);
+
+    // 1.Bc4 Rh1+ 2.Bf1 and 1.Bd5 covers h1
+    let helpmate = &solutions[0];
+    assert_eq!(helpmate.solutions, vec![moves("g8f7 h8h1"), moves("g8e6 h8h1")]);
+    assert_eq!(helpmate.format_solution(&helpmate.solutions[0]), "1.Bf7 Rh1#");
+
+    // White starts in h#n.5
+    let half_move = &solutions[1];
+    assert_eq!(half_move.position.label, "b)");
+    assert!(half_move.solutions.contains(&moves("c2c3 a1b1 h8h1")));
+    assert!(half_move.solutions.iter().all(|line| line.len() == 3));
+    Ok(())
+}
+
+#[test]
+fn solves_selfmates() -> Result<(), String> {
+    // after 1.Qd4 or 1.Qa4 Black can only play g2#
+    let problem = parse_problem("Pieces White Kh1 Bg1 Qd1 Black Kh3 pg3\nStipulation s#1")?;
+    let solutions = problem.solve()?;
+    assert_eq!(solutions[0].solutions, vec![moves("d1d4"), moves("d1a4")]);
+    assert_eq!(solutions[0].format_solution(&solutions[0].solutions[0]), "1.Qd4!");
+
+    let game = &solutions[0].position.game;
+    assert!(solve_stipulation(game, Stipulation::parse("s#2")?).contains(&moves("d1d4")));
+    Ok(())
+}
+
+#[test]
+fn reflexmates_require_mate_in_one_when_possible() -> Result<(), String> {
+    let problem = parse_problem("Pieces White Kh1 Bg1 Qd1 Black Kh3 pg3\nStipulation r#1")?;
+    // White must play 1.Qh5# instead
+    assert!(problem.solve()?[0].solutions.is_empty());
+
+    // without the mate Black has to give mate after the key
+    let problem = parse_problem("Pieces White Kh1 Bg1 Qc2 Black Kh3 pg3\nStipulation r#1")?;
+    assert!(problem.solve()?[0].solutions.contains(&moves("c2c4")));
+    Ok(())
+}
+
+#[test]
+fn direct_mates_use_the_mate_solver() -> Result<(), String> {
+    let problem = parse_problem("Pieces White Kc8 Ra1 Pb6 Black Ka8 Bb8 Pa7 Pb7\nStipulation #2")?;
+    assert_eq!(problem.solve()?[0].solutions, vec![moves("a1a6")]);
+    Ok(())
+}
+
+#[test]
+fn reports_parse_errors_with_line_numbers() {
+    let error = parse_problems("Pieces\nWhite Kc8 Xa1\nStipulation #2").err();
+    assert_eq!(error.as_deref(), Some("line 2: Invalid piece Xa1"));
+    assert!(parse_problems("Pieces White Ke1 Black Ke8\nStipulation =2").is_err());
+}
@@ -0,0 +1,246 @@
+use rust_chess_core::game::Game;
+use rust_chess_core::r#move::Move;
+use rust_chess_core::search::{MAX_DEPTH, Ponder, Score, Search, SearchEvent, SearchInfo, SearchLimits};
+use rust_chess_core::time_manager::TimeManager;
+use rust_chess_core::transposition_table::TranspositionTable;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn finds_mate_in_one() -> Result<(), String> {
+    let game = Game::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1")?;
+    let result = Search::with_limits(SearchLimits::depth(2)).run(&game, |_| {});
+
+    assert_eq!(result.best_move, Some(Move::from_long_notation("a1a8")));
+    assert_eq!(result.score, Score::Mate(1));
+    Ok(())
+}
+
+#[test]
+fn takes_hanging_queen() -> Result<(), String> {
+    let game = Game::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1")?;
+    let result = Search::with_limits(SearchLimits::depth(2)).run(&game, |_| {});
+
+    assert_eq!(result.best_move, Some(Move::from_long_notation("d2d5")));
+    Ok(())
+}
+
+#[test]
+fn searches_only_the_given_root_moves() -> Result<(), String> {
+    let game = Game::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1")?;
+    let mut search = Search::with_limits(SearchLimits::depth(2));
+    search.set_search_moves(vec![Move::from_long_notation("d2d3"), Move::from_long_notation("e1f2")]);
+    let result = search.run(&game, |info| assert_ne!(info.pv[0], Move::from_long_notation("d2d5")));
+    assert!(matches!(result.best_move, Some(mv) if mv.to_string() == "d2d3" || mv.to_string() == "e1f2"));
+
+    // illegal moves are ignored, without any legal one all moves are searched
+    search.set_search_moves(vec![Move::from_long_notation("a1a2")]);
+    let result = search.run(&game, |_| {});
+    assert_eq!(result.best_move, Some(Move::from_long_notation("d2d5")));
+    Ok(())
+}
+
+#[test]
+fn reports_every_iteration() {
+    let game = Game::new();
+    let mut depths = Vec::new();
+    let result = Search::with_limits(SearchLimits::depth(3)).run(&game, |info| {
+        assert!(!info.pv.is_empty());
+        depths.push(info.depth);
+    });
+
+    assert_eq!(depths, vec![1, 2, 3]);
+    assert_eq!(result.depth, 3);
+    assert!(result.best_move.is_some());
+}
+
+#[test]
+fn stopped_search_still_returns_a_move() {
+    let game = Game::new();
+    let stop = Arc::new(AtomicBool::new(true));
+    let result = Search::new(SearchLimits::default(), stop).run(&game, |_| {});
+
+    assert!(result.best_move.is_some());
+    assert_eq!(result.depth, 0);
+}
+
+#[test]
+fn no_move_when_game_is_over() -> Result<(), String> {
+    // black is checkmated
+    let game = Game::from_fen("R5k1/5ppp/8/8/8/8/5PPP/6K1 b - - 1 1")?;
+    let result = Search::with_limits(SearchLimits::depth(2)).run(&game, |_| {});
+
+    assert_eq!(result.best_move, None);
+    Ok(())
+}
+
+#[test]
+fn single_legal_move_is_played_immediately() -> Result<(), String> {
+    // the white king is in check and can only go to a2
+    let game = Game::from_fen("8/8/8/8/8/2k5/8/K6r w - - 0 1")?;
+    let limits = SearchLimits::move_time(Duration::from_secs(60));
+    let result = Search::with_limits(limits).run(&game, |_| {});
+
+    assert_eq!(result.best_move, Some(Move::from_long_notation("a1a2")));
+    assert_eq!(result.depth, 1);
+    Ok(())
+}
+
+#[test]
+fn hard_time_limit_stops_the_search() {
+    let game = Game::new();
+    let time_manager = TimeManager::new(Duration::from_millis(300), Duration::ZERO, None, Duration::ZERO);
+    let limits = SearchLimits {
+        time_manager: Some(time_manager.clone()),
+        ..Default::default()
+    };
+    let start = Instant::now();
+    let result = Search::with_limits(limits).run(&game, |_| {});
+
+    assert!(result.best_move.is_some());
+    // allow some slack for checking the clock only every few nodes
+    assert!(start.elapsed() < time_manager.hard_limit() + Duration::from_millis(200));
+}
+
+#[test]
+fn single_thread_search_is_deterministic() {
+    let game = Game::new();
+    let run = || Search::with_limits(SearchLimits::depth(3)).run(&game, |_| {});
+    let (first, second) = (run(), run());
+
+    assert_eq!(first.pv, second.pv);
+    assert_eq!(first.nodes, second.nodes);
+}
+
+#[test]
+fn multithreaded_search_finds_mate() -> Result<(), String> {
+    let game = Game::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1")?;
+    let mut search = Search::with_limits(SearchLimits::depth(3));
+    search.set_threads(4);
+    let result = search.run(&game, |_| {});
+
+    assert_eq!(result.best_move, Some(Move::from_long_notation("a1a8")));
+    assert_eq!(result.score, Score::Mate(1));
+    Ok(())
+}
+
+#[test]
+fn nodes_are_counted_across_threads() {
+    let game = Game::new();
+    let limits = SearchLimits {
+        nodes: Some(5000),
+        ..Default::default()
+    };
+    let mut search = Search::with_limits(limits);
+    search.set_threads(3);
+    let mut reported_nodes = Vec::new();
+    let result = search.run(&game, |info| reported_nodes.push(info.nodes));
+
+    assert!(result.nodes >= 5000);
+    assert_eq!(result.nodes, search.nodes());
+    assert!(reported_nodes.iter().all(|&nodes| nodes <= result.nodes));
+}
+
+#[test]
+fn transposition_table_is_reused_between_searches() {
+    let game = Game::new();
+    let tt = Arc::new(TranspositionTable::new(1));
+    let mut first = Search::with_limits(SearchLimits::depth(4));
+    first.set_transposition_table(tt.clone());
+    let first = first.run(&game, |_| {});
+
+    let mut second = Search::with_limits(SearchLimits::depth(4));
+    second.set_transposition_table(tt);
+    let second = second.run(&game, |_| {});
+
+    assert_eq!(first.best_move, second.best_move);
+    assert!(second.nodes < first.nodes);
+}
+
+#[test]
+fn multi_pv_returns_ranked_lines() -> Result<(), String> {
+    // the queen can be taken by the rook or by the knight
+    let game = Game::from_fen("4k3/8/8/3q4/8/4N3/3R4/4K3 w - - 0 1")?;
+    let mut search = Search::with_limits(SearchLimits::depth(2));
+    search.set_multi_pv(3);
+    let mut reported = Vec::new();
+    let result = search.run(&game, |info| reported.push((info.depth, info.multi_pv)));
+
+    assert_eq!(result.lines.len(), 3);
+    assert_eq!(result.lines[0].mv, result.best_move.unwrap());
+    assert!(result.lines.windows(2).all(|lines| match (lines[0].score, lines[1].score) {
+        (Score::Centipawns(first), Score::Centipawns(second)) => first >= second,
+        _ => false,
+    }));
+    let mut first_moves: Vec<String> = result.lines.iter().map(|line| line.mv.to_string()).collect();
+    first_moves.sort();
+    first_moves.dedup();
+    assert_eq!(first_moves.len(), 3);
+    assert_eq!(reported, vec![(1, 1), (1, 2), (1, 3), (2, 1), (2, 2), (2, 3)]);
+
+    let captures: Vec<String> = result.lines[..2]
+        .iter()
+        .map(|line| line.pv_short_notation(&game).unwrap()[0].clone())
+        .collect();
+    assert!(captures.contains(&"Nxd5".to_string()) && captures.contains(&"Rxd5".to_string()));
+    Ok(())
+}
+
+#[test]
+fn spawned_search_streams_events() {
+    let handle = Search::with_limits(SearchLimits::depth(3)).spawn(Game::new());
+    let events: Vec<SearchEvent> = handle.events().iter().collect();
+
+    let infos: Vec<&SearchInfo> = events
+        .iter()
+        .filter_map(|event| match event {
+            SearchEvent::Info(info) => Some(info),
+            SearchEvent::Finished(_) => None,
+        })
+        .collect();
+    assert_eq!(infos.iter().map(|info| info.depth).collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert!(infos.iter().all(|info| info.seldepth >= info.depth && info.hashfull <= 1000));
+    let Some(SearchEvent::Finished(result)) = events.last() else {
+        panic!("The last event must be the result");
+    };
+    assert_eq!(result.depth, 3);
+    assert_eq!(handle.join().best_move, result.best_move);
+}
+
+#[test]
+fn cancellation_token_stops_the_search() {
+    let search = Search::with_limits(SearchLimits::default());
+    let token = search.cancellation_token();
+    let handle = search.spawn(Game::new());
+
+    // wait for the first iteration, so that there is a result to return
+    let first_event = handle.events().recv().unwrap();
+    assert!(matches!(first_event, SearchEvent::Info(_)));
+    token.cancel();
+
+    let result = handle.join();
+    assert!(result.best_move.is_some());
+    assert!(result.depth < MAX_DEPTH);
+}
+
+#[test]
+fn ponder_search_ignores_time_until_ponder_hit() {
+    let ponder = Ponder::new();
+    let limits = SearchLimits {
+        ponder: Some(ponder.clone()),
+        ..SearchLimits::move_time(Duration::from_millis(50))
+    };
+    let handle = Search::with_limits(limits).spawn(Game::new());
+
+    thread::sleep(Duration::from_millis(300));
+    assert!(!handle.is_finished());
+
+    let hit_time = Instant::now();
+    ponder.ponder_hit();
+    let result = handle.join();
+    assert!(result.best_move.is_some());
+    // allow some slack for checking the clock only every few nodes
+    assert!(hit_time.elapsed() < Duration::from_millis(500));
+}
@@ -0,0 +1,191 @@
+use rust_chess_core::game::Game;
+use rust_chess_core::r#move::Move;
+use rust_chess_core::random::Random;
+use rust_chess_core::search::{Score, Search, SearchLimits};
+use rust_chess_core::syzygy::{Syzygy, Wdl};
+use rust_chess_core::tablebase::{Tablebases, TbResult};
+use std::path::Path;
+use std::sync::Arc;
+
+fn syzygy() -> Syzygy {
+    Syzygy::load_dir(Path::new("./tests/syzygy")).unwrap()
+}
+
+// Random legal positions with the given pieces, no pawns on the first or last rank and the side not to move
+// not in check
+fn random_positions(pieces: &str, count: usize, seed: u64) -> Vec<Game> {
+    let mut random = Random::new(seed);
+    let mut games = Vec::new();
+    while games.len() < count {
+        let mut board = [[None; 8]; 8];
+        for piece in pieces.chars() {
+            loop {
+                let (col, row) = (random.below(8) as usize, random.below(8) as usize);
+                if board[row][col].is_none() && !(piece.eq_ignore_ascii_case(&'p') && (row == 0 || row == 7)) {
+                    board[row][col] = Some(piece);
+                    break;
+                }
+            }
+        }
+        let rows: Vec<String> = board
+            .iter()
+            .rev()
+            .map(|row| {
+                let mut fen = String::new();
+                let mut empty = 0;
+                for square in row {
+                    match square {
+                        Some(piece) => {
+                            if empty > 0 {
+                                fen.push_str(&empty.to_string());
+                                empty = 0;
+                            }
+                            fen.push(*piece);
+                        }
+                        None => empty += 1,
+                    }
+                }
+                if empty > 0 {
+                    fen.push_str(&empty.to_string());
+                }
+                fen
+            })
+            .collect();
+        let turn = if random.below(2) == 0 { "w" } else { "b" };
+        if let Ok(game) = Game::from_fen(&format!("{} {} - - 0 1", rows.join("/"), turn))
+            && !game.board().is_check(game.turn().opposite())
+            && game.result().is_none()
+        {
+            games.push(game);
+        }
+    }
+    games
+}
+
+#[test]
+fn finds_the_tables() {
+    let syzygy = syzygy();
+    assert_eq!(syzygy.signatures(), ["KBvK", "KBvKB", "KNvK", "KNvKN", "KPvK", "KQvK", "KRvK", "KRvKR"]);
+    assert_eq!(syzygy.max_pieces(), 4);
+    assert!(Syzygy::load_dir(Path::new("./tests/missing")).is_err());
+}
+
+#[test]
+fn probes_reference_positions() -> Result<(), String> {
+    let syzygy = syzygy();
+    let positions = [
+        ("8/4r3/8/K7/4R3/8/8/5k2 w - -", Wdl::Win, 1),
+        ("8/8/R2k4/8/8/K7/8/4r3 b - -", Wdl::Draw, 0),
+        ("8/8/8/8/8/1K2B3/b6k/8 w - -", Wdl::Draw, 0),
+        ("R7/8/6k1/8/8/8/2K4r/8 w - -", Wdl::Draw, 0),
+        ("8/3r4/7K/5R2/8/8/8/5k2 b - -", Wdl::Draw, 0),
+        ("8/5p2/6k1/K7/8/8/8/8 w - -", Wdl::Loss, -2),
+        ("7k/1R6/8/8/8/8/4r3/K7 w - -", Wdl::Draw, 0),
+        ("8/8/8/3Kn3/8/8/4k3/N7 b - -", Wdl::Draw, 0),
+        ("5K2/8/8/8/8/8/N7/4k2n b - -", Wdl::Draw, 0),
+        ("1bB5/8/1k6/8/8/8/6K1/8 b - -", Wdl::Draw, 0),
+        ("8/8/8/2K5/5kp1/8/8/8 b - -", Wdl::Win, 1),
+        ("8/3K4/5k2/8/1N6/4n3/8/8 b - -", Wdl::Draw, 0),
+        ("7K/8/8/8/4k3/1N6/3n4/8 b - -", Wdl::Draw, 0),
+        ("8/7n/8/N1K5/6k1/8/8/8 w - -", Wdl::Draw, 0),
+        ("8/2K5/8/8/8/4k2N/8/6n1 w - -", Wdl::Draw, 0),
+        ("8/7k/K7/6r1/8/5R2/8/8 w - -", Wdl::Draw, 0),
+        ("8/8/8/2R5/1K6/8/5k2/8 w - -", Wdl::Win, 21),
+        ("5N2/8/8/6K1/1k6/8/3n4/8 w - -", Wdl::Draw, 0),
+        ("8/3k4/8/8/8/8/4P3/3K4 w - -", Wdl::Draw, 0),
+        ("6k1/8/8/8/8/4n3/8/K7 b - -", Wdl::Draw, 0),
+    ];
+    for (fen, wdl, dtz) in positions {
+        let game = Game::from_fen(&format!("{} 0 1", fen))?;
+        assert_eq!(syzygy.probe_wdl(&game), Some(wdl), "{}", fen);
+        assert_eq!(syzygy.probe_dtz(&game), Some(dtz), "{}", fen);
+    }
+
+    // no table for five pieces, and no probing with castling rights
+    let game = Game::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1")?;
+    assert_eq!(syzygy.probe_wdl(&game), None);
+    let game = Game::from_fen("4k3/8/8/8/8/8/2Q5/RN2K3 w - - 0 1")?;
+    assert_eq!(syzygy.probe_wdl(&game), None);
+    Ok(())
+}
+
+#[test]
+fn agrees_with_generated_tables() -> Result<(), String> {
+    let syzygy = syzygy();
+    let mut tablebases = Tablebases::new();
+    for signature in ["KQvK", "KRvK", "KPvK"] {
+        tablebases.generate(signature)?;
+    }
+    for (pieces, seed) in [("KQk", 1), ("KRk", 2), ("KPk", 3), ("kpK", 4)] {
+        for game in random_positions(pieces, 300, seed) {
+            let fen = game.to_fen();
+            let wdl = syzygy.probe_wdl(&game);
+            let dtz = syzygy.probe_dtz(&game);
+            match tablebases.probe(&game) {
+                Some(TbResult::Win(plies)) => {
+                    assert_eq!(wdl, Some(Wdl::Win), "{}", fen);
+                    // without pawns the only zeroing moves are mates and captures, which the loser avoids
+                    if !pieces.contains('P') && !pieces.contains('p') {
+                        let dtz = dtz.unwrap() as u32;
+                        assert!(dtz == plies || dtz == plies + 1, "{} dtz {} mate in {}", fen, dtz, plies);
+                    }
+                }
+                Some(TbResult::Loss(_)) => {
+                    assert_eq!(wdl, Some(Wdl::Loss), "{}", fen);
+                    assert!(dtz.unwrap() < 0, "{}", fen);
+                }
+                Some(TbResult::Draw) => {
+                    assert_eq!(wdl, Some(Wdl::Draw), "{}", fen);
+                    assert_eq!(dtz, Some(0), "{}", fen);
+                }
+                None => panic!("{} not in the generated tables", fen),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn keeps_the_root_moves_with_the_best_result() -> Result<(), String> {
+    let syzygy = syzygy();
+
+    // taking the queen is the only move that doesn't lose
+    let game = Game::from_fen("8/8/8/8/8/2k5/1Q6/7K b - - 0 1")?;
+    assert_eq!(syzygy.root_moves(&game), Some(vec![Move::from_long_notation("c3b2")]));
+
+    // with the halfmove clock this high only the fastest wins are kept, and all of them win
+    let game = Game::from_fen("8/8/8/2R5/1K6/8/5k2/8 w - - 80 1")?;
+    let moves = syzygy.root_moves(&game).unwrap();
+    assert!(!moves.is_empty() && moves.len() < game.possible_moves().len());
+    for mv in &moves {
+        let mut after = game.clone();
+        after.make_move(mv)?;
+        assert_eq!(syzygy.probe_wdl(&after), Some(Wdl::Loss), "{}", mv);
+    }
+
+    // the mate is the fastest way to zero the halfmove clock
+    let game = Game::from_fen("7k/8/6K1/8/8/8/8/R7 w - - 0 1")?;
+    assert!(syzygy.root_moves(&game).unwrap().contains(&Move::from_long_notation("a1a8")));
+    Ok(())
+}
+
+#[test]
+fn search_plays_tablebase_moves() -> Result<(), String> {
+    let syzygy = Arc::new(syzygy());
+
+    // at the root only the moves the tables keep are searched
+    let game = Game::from_fen("8/8/8/2R5/1K6/8/5k2/8 w - - 80 1")?;
+    let mut search = Search::with_limits(SearchLimits::depth(3));
+    search.set_syzygy(syzygy.clone());
+    let result = search.run(&game, |_| {});
+    assert!(syzygy.root_moves(&game).unwrap().contains(&result.best_move.unwrap()));
+
+    // there is no table for the position, but there is one after taking the knight
+    let game = Game::from_fen("4k3/8/8/8/3n4/8/1Q6/K7 w - - 0 1")?;
+    let mut search = Search::with_limits(SearchLimits::depth(2));
+    search.set_syzygy(syzygy);
+    let result = search.run(&game, |_| {});
+    assert_eq!(result.best_move, Some(Move::from_long_notation("b2d4")));
+    assert!(matches!(result.score, Score::Centipawns(score) if score > 20000));
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use rust_chess_core::game::Game;
+use rust_chess_core::mate_solver::solve_mate;
+use rust_chess_core::r#move::Move;
+use rust_chess_core::search::{Score, Search, SearchLimits};
+use rust_chess_core::tablebase::{TbResult, Tablebases};
+use std::sync::Arc;
+
+fn tablebases(signatures: &[&str]) -> Result<Tablebases, String> {
+    let mut tablebases = Tablebases::new();
+    for signature in signatures {
+        tablebases.generate(signature)?;
+    }
+    Ok(tablebases)
+}
+
+#[test]
+fn agrees_with_the_mate_solver() -> Result<(), String> {
+    let tablebases = tablebases(&["KQvK", "KRvK"])?;
+
+    let game = Game::from_fen("7k/8/6K1/8/8/8/8/R7 w - - 0 1")?;
+    assert_eq!(tablebases.probe(&game), Some(TbResult::Win(1)));
+    let (mv, _) = tablebases.best_move(&game).unwrap();
+    assert_eq!(mv, Move::from_long_notation("a1a8"));
+
+    // longer mates, the tablebase move must be a key the solver accepts
+    for fen in ["6k1/8/5K2/8/8/8/8/1Q6 w - - 0 1", "7k/8/5K2/8/8/8/8/6R1 w - - 0 1"] {
+        let game = Game::from_fen(fen)?;
+        let Some(TbResult::Win(plies)) = tablebases.probe(&game) else {
+            panic!("{} is not a win", fen);
+        };
+        let moves = plies.div_ceil(2);
+        assert!(!solve_mate(&game, moves - 1).is_mate());
+        let solution = solve_mate(&game, moves);
+        let (mv, _) = tablebases.best_move(&game).unwrap();
+        assert!(solution.keys.iter().any(|key| key.mv == mv));
+
+        let line = tablebases.best_line(&game, 20);
+        assert_eq!(line.len(), plies as usize);
+        let mut after = game.clone();
+        for mv in &line {
+            after.make_move(mv)?;
+        }
+        assert!(after.result().is_some());
+    }
+
+    // the same position with colors swapped, and the losing side to move
+    let white = Game::from_fen("6k1/8/5K2/8/8/8/8/1Q6 w - - 0 1")?;
+    let black = Game::from_fen("1q6/8/8/8/8/5k2/8/6K1 b - - 0 1")?;
+    assert_eq!(tablebases.probe(&black), tablebases.probe(&white));
+    let game = Game::from_fen("6k1/8/5K2/8/8/8/8/1Q6 b - - 0 1")?;
+    assert!(matches!(tablebases.probe(&game), Some(TbResult::Loss(_))));
+    Ok(())
+}
+
+#[test]
+fn knows_pawn_endings() -> Result<(), String> {
+    let tablebases = tablebases(&["KPvK"])?;
+
+    // the king in front of its pawn with the opposition wins, behind it is a draw
+    let game = Game::from_fen("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1")?;
+    assert!(matches!(tablebases.probe(&game), Some(TbResult::Win(_))));
+    let game = Game::from_fen("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1")?;
+    assert!(matches!(tablebases.probe(&game), Some(TbResult::Loss(_))));
+    let game = Game::from_fen("8/8/8/4k3/8/4K3/4P3/8 w - - 0 1")?;
+    assert_eq!(tablebases.probe(&game), Some(TbResult::Draw));
+
+    // positions not covered by the tables
+    assert_eq!(tablebases.probe(&Game::new()), None);
+    let game = Game::from_fen("4k3/8/4K3/4R3/8/8/8/3n4 w - - 0 1")?;
+    assert_eq!(tablebases.probe(&game), None);
+    Ok(())
+}
+
+#[test]
+fn saves_and_loads_tables() -> Result<(), String> {
+    let tablebases = tablebases(&["KRvK"])?;
+    let dir = std::env::temp_dir().join(format!("rust-chess-tablebases-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    tablebases.save_dir(&dir)?;
+    let loaded = Tablebases::load_dir(&dir);
+    std::fs::remove_dir_all(&dir).map_err(|err| err.to_string())?;
+    let loaded = loaded?;
+
+    assert_eq!(loaded.signatures(), tablebases.signatures());
+    for fen in ["7k/8/6K1/8/8/8/8/R7 w - - 0 1", "8/8/3k4/8/8/3K4/8/R7 b - - 0 1", "8/8/8/8/8/2k5/8/K6R b - - 0 1"] {
+        let game = Game::from_fen(fen)?;
+        assert_eq!(loaded.probe(&game), tablebases.probe(&game));
+    }
+    Ok(())
+}
+
+#[test]
+fn search_plays_tablebase_moves() -> Result<(), String> {
+    let tablebases = Arc::new(tablebases(&["KQvK"])?);
+    let game = Game::from_fen("6k1/8/5K2/8/8/8/8/1Q6 w - - 0 1")?;
+    let expected = match tablebases.probe(&game) {
+        Some(TbResult::Win(plies)) => plies,
+        _ => panic!("not a win"),
+    };
+    let mut search = Search::with_limits(SearchLimits::depth(1));
+    search.set_tablebases(tablebases);
+    let result = search.run(&game, |_| {});
+
+    assert_eq!(result.score, Score::Mate(expected.div_ceil(2) as i32));
+    assert_eq!(result.pv.len(), expected as usize);
+    Ok(())
+}